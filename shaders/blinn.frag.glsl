@@ -5,18 +5,80 @@ struct PointLight {
     float strength;
 };
 
-uniform PointLight light;
+struct DirectionalLight {
+    vec3 direction;
+    vec3 color;
+};
+
+struct MaterialTexture {
+    sampler2D tex;
+    int enabled;
+    float scale;
+};
+
+struct BlinnMaterial {
+    vec3 diffuse;
+    vec3 specular;
+    float shininess;
+    float ambient;
+    MaterialTexture diffuse_texture;
+};
+
+uniform BlinnMaterial material;
+
+uniform vec3 camera_position;
+
+// 0 draws normally, (0, 1) fades this lod level in and (-1, 0) fades it out
+uniform float lodFade;
+
+uniform int numPointLights;
+uniform PointLight[4] pointLights;
+uniform DirectionalLight dirLight;
 
 in vec3 fragPosition; // Position of the fragment in world space
 in vec3 normal;       // Normal of the fragment in world space
+in vec2 oUVs;
 
 out vec4 final_color;
 
+float random(vec2 coords) {
+    return fract(sin(dot(coords.xy, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+vec3 blinn(vec3 N, vec3 V, vec3 L, vec3 radiance, vec3 diffuseColor) {
+    vec3 H = normalize(L + V);
+    float diff = max(dot(N, L), 0.0);
+    float spec = diff > 0.0 ? pow(max(dot(N, H), 0.0), material.shininess) : 0.0;
+    return (diff * diffuseColor + spec * material.specular) * radiance;
+}
+
 void main() {
-    vec3 norm = normalize(normal);
-    vec3 light_dir = normalize(light.position - fragPosition);
-    float diff = max(dot(norm, light_dir), 0.0);
-    vec3 diffuse = diff * light.color * light.strength * vec3(0.5, 0.0, 0.0);
+    if(lodFade != 0.0) {
+        float threshold = random(floor(gl_FragCoord.xy));
+        if((lodFade > 0.0 && threshold >= lodFade) || (lodFade < 0.0 && threshold < -lodFade)) {
+            discard;
+        }
+    }
+
+    vec3 N = normalize(normal);
+    vec3 V = normalize(camera_position - fragPosition);
+
+    vec3 diffuseColor = material.diffuse;
+    if(material.diffuse_texture.enabled == 1) {
+        diffuseColor = pow(texture(material.diffuse_texture.tex, oUVs * material.diffuse_texture.scale).rgb, vec3(2.2));
+    }
+
+    vec3 color = material.ambient * diffuseColor;
+    color += blinn(N, V, normalize(-dirLight.direction), dirLight.color, diffuseColor);
+
+    for(int i = 0; i < numPointLights; i++) {
+        vec3 toLight = pointLights[i].position - fragPosition;
+        float distance = length(toLight);
+        float attenuation = 1.0 / (1.0 + 0.09 * distance + 0.0032 * (distance * distance));
+        vec3 radiance = pointLights[i].color * pointLights[i].strength * attenuation;
+        color += blinn(N, V, toLight / distance, radiance, diffuseColor);
+    }
 
-    final_color = vec4(diffuse, 1.0);
+    color = pow(color, vec3(1.0 / 2.2));
+    final_color = vec4(color, 1.0);
 }
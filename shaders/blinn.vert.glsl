@@ -1,18 +1,28 @@
 #version 330 core
+// shared by the blinn, unlit and toon materials
 layout(location = 0) in vec3 pos;
 layout(location = 1) in vec3 norm;
+layout(location = 2) in vec2 uvs;
+
+// per instance model matrix, only read when drawing an InstancedObject
+layout(location = 3) in mat4 instanceModel;
 
 uniform mat4 projection;
 uniform mat4 model;
 uniform mat4 view;
+uniform int instanced;
 
 out vec3 fragPosition;
 out vec3 normal;
+out vec2 oUVs;
 
 void main() {
-    vec4 worldPosition = model * vec4(pos, 1.0);
+    mat4 modelMatrix = instanced == 1 ? instanceModel : model;
+
+    vec4 worldPosition = modelMatrix * vec4(pos, 1.0);
     fragPosition = vec3(worldPosition);
-    normal = mat3(transpose(inverse(model))) * norm;
+    normal = mat3(transpose(inverse(modelMatrix))) * norm;
 
-    gl_Position = projection * view * model * vec4(pos, 1.0);
+    oUVs = uvs;
+    gl_Position = projection * view * worldPosition;
 }
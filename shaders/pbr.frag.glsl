@@ -24,9 +24,25 @@ struct PhysicalMaterial {
     float metallic;
     float roughness;
     float ao;
+    float opacity;
+    int alpha_mode;
+    float alpha_cutoff;
+    int double_sided;
     MaterialTexture diffuse_texture;
     MaterialTexture normal_texture;
     MaterialTexture arm_texture; // ao, roughness, metallic all in one
+    vec3 emissive;
+    float emissive_strength;
+    MaterialTexture emissive_texture;
+    // single channel maps, they win over the matching arm channel
+    MaterialTexture ao_texture;
+    MaterialTexture roughness_texture;
+    MaterialTexture metallic_texture;
+    // white is high, the surface is pushed in by up to height_scale in uv units of the height map
+    MaterialTexture height_texture;
+    float height_scale;
+    int parallax_steps;
+    int parallax_shadows;
 };
 
 uniform PhysicalMaterial material;
@@ -34,6 +50,15 @@ uniform PhysicalMaterial material;
 uniform vec3 camera_position;
 uniform vec2 resolution;
 
+// 0 draws normally, (0, 1) fades this lod level in and (-1, 0) fades it out
+uniform float lodFade;
+
+// instances carry their own albedo, metallic, roughness and ao
+uniform int instanced;
+
+// shows one lighting input instead of the lit color, see DebugView::shader_value
+uniform int debugView;
+
 uniform int numPointLights;
 uniform PointLight[4] pointLights;
 uniform DirectionalLight dirLight;
@@ -41,9 +66,32 @@ uniform DirectionalLight dirLight;
 in vec3 fragPosition;
 in vec3 normal;
 in vec2 oUVs;
+in vec3 oTangent;
+flat in vec3 oInstanceAlbedo;
+flat in vec3 oInstanceMaterial;
 
 // physical rendering components
 const float PI = 3.14159265359;
+
+// material.alpha_mode values, see AlphaMode::shader_value
+const int ALPHA_OPAQUE = 0;
+const int ALPHA_MASK = 1;
+const int ALPHA_BLEND = 2;
+const int ALPHA_ADDITIVE = 3;
+
+const int DEBUG_LIT = 0;
+const int DEBUG_ALBEDO = 1;
+const int DEBUG_METALLIC = 2;
+const int DEBUG_ROUGHNESS = 3;
+const int DEBUG_AO = 4;
+const int DEBUG_NORMAL = 5;
+const int DEBUG_EMISSIVE = 6;
+
+// below this the ggx highlight collapses to a point and aliases badly
+const float MIN_ROUGHNESS = 0.045;
+
+// loops need a constant bound, material.parallax_steps picks how many are used
+const int MAX_PARALLAX_STEPS = 128;
 const highp float NOISE_GRANULARITY = 1.0 / 255.0;
 
 highp float random(highp vec2 coords) {
@@ -90,51 +138,176 @@ float GeometrySmith(vec3 N, vec3 V, vec3 L, float roughness) {
     return ggx1 * ggx2;
 }
 
-vec3 getNormalFromMap() {
-    vec3 tangentNormal = texture(material.normal_texture.tex, oUVs * material.normal_texture.scale).xyz * 2.0 - 1.0;
+vec3 getNormalFromMap(vec2 uv, mat3 TBN) {
+    vec3 tangentNormal = texture(material.normal_texture.tex, uv * material.normal_texture.scale).xyz * 2.0 - 1.0;
+    return normalize(TBN * tangentNormal);
+}
+
+// tangent, bitangent and normal from the mesh's tangents, the tangent is made perpendicular again
+// after interpolation
+mat3 tangentFrame(vec3 N) {
+    vec3 T = normalize(oTangent - N * dot(N, oTangent));
+    vec3 B = cross(N, T);
+    return mat3(T, B, N);
+}
 
-    vec3 Q1 = dFdx(fragPosition);
-    vec3 Q2 = dFdy(fragPosition);
-    vec2 st1 = dFdx(oUVs);
-    vec2 st2 = dFdy(oUVs);
+// depth into the surface at a point of the height map, 0 at the top
+float heightDepth(vec2 huv, vec2 dx, vec2 dy) {
+    return 1.0 - textureGrad(material.height_texture.tex, huv, dx, dy).r;
+}
 
-    vec3 N = normalize(normal);
-    vec3 T = normalize(Q1 * st2.t - Q2 * st1.t);
-    vec3 B = -normalize(cross(N, T));
-    mat3 TBN = mat3(T, B, N);
+// steps along the view ray through the height field until it drops below the surface, then
+// interpolates between the last two steps. huv is in the height map's (scaled) uv space with dx
+// and dy its screen derivatives, the depth where the ray hit comes back through surfaceDepth
+vec2 parallaxOcclusion(vec2 huv, vec2 dx, vec2 dy, vec3 viewTS, out float surfaceDepth) {
+    // more steps at grazing angles where the ray crosses more of the texture
+    float maxSteps = float(clamp(material.parallax_steps, 1, MAX_PARALLAX_STEPS));
+    float steps = mix(maxSteps, max(maxSteps * 0.25, 1.0), abs(viewTS.z));
+    float layerDepth = 1.0 / steps;
+    vec2 delta = viewTS.xy / max(viewTS.z, 0.05) * material.height_scale / steps;
+
+    vec2 current = huv;
+    float currentDepth = 0.0;
+    float mapDepth = heightDepth(current, dx, dy);
+    for(int i = 0; i < MAX_PARALLAX_STEPS; i++) {
+        if(float(i) >= steps || currentDepth >= mapDepth) {
+            break;
+        }
+        current -= delta;
+        currentDepth += layerDepth;
+        mapDepth = heightDepth(current, dx, dy);
+    }
 
-    return normalize(TBN * tangentNormal);
+    vec2 previous = current + delta;
+    float after = mapDepth - currentDepth;
+    float before = heightDepth(previous, dx, dy) - (currentDepth - layerDepth);
+    float weight = after / (after - before + 1e-5);
+
+    surfaceDepth = currentDepth - weight * layerDepth;
+    return mix(current, previous, weight);
 }
 
-out vec4 final_color;
+// marches from the displaced point toward a light, 1 lit and down to 0 where the height field
+// rises above the ray. closer occluders shadow harder
+float parallaxShadow(vec2 huv, vec2 dx, vec2 dy, vec3 lightTS, float surfaceDepth) {
+    if(lightTS.z <= 0.0) {
+        return 0.0;
+    }
+    if(surfaceDepth <= 0.0) {
+        return 1.0;
+    }
+
+    float steps = float(clamp(material.parallax_steps, 1, MAX_PARALLAX_STEPS));
+    float layerDepth = surfaceDepth / steps;
+    vec2 delta = lightTS.xy / lightTS.z * material.height_scale * surfaceDepth / steps;
+
+    float shadow = 0.0;
+    vec2 current = huv + delta;
+    float depth = surfaceDepth - layerDepth;
+    for(int i = 1; i < MAX_PARALLAX_STEPS; i++) {
+        if(float(i) >= steps || depth <= 0.0) {
+            break;
+        }
+        float penetration = depth - heightDepth(current, dx, dy);
+        shadow = max(shadow, penetration * (1.0 - float(i) / steps) * 8.0);
+        current += delta;
+        depth -= layerDepth;
+    }
+
+    return 1.0 - clamp(shadow, 0.0, 1.0);
+}
+
+layout(location = 0) out vec4 final_color;
 
 void main() {
-    vec3 N = normalize(normal);
-    if(material.normal_texture.enabled == 1) {
-        N = getNormalFromMap();
+    // derivatives are only defined while every pixel of the quad runs the same code, so they are
+    // taken before any discard or early return and passed to the height field marches
+    vec2 huv = oUVs * material.height_texture.scale;
+    vec2 huvDx = dFdx(huv);
+    vec2 huvDy = dFdy(huv);
+
+    // cross fade between lod levels with complementary dither patterns
+    if(lodFade != 0.0) {
+        float threshold = random(floor(gl_FragCoord.xy));
+        if((lodFade > 0.0 && threshold >= lodFade) || (lodFade < 0.0 && threshold < -lodFade)) {
+            discard;
+        }
     }
 
     vec3 V = normalize(camera_position - fragPosition);
 
-    vec3 total = vec3(0.0, 0.0, 0.0);
+    // parallax moves every texture lookup to where the view ray meets the height field
+    vec2 uv = oUVs;
+    mat3 TBN = tangentFrame(normalize(normal));
+    float surfaceDepth = 0.0;
+    bool parallax = material.height_texture.enabled == 1 && material.height_scale > 0.0;
+    if(parallax) {
+        vec3 viewTS = normalize(transpose(TBN) * V);
+        huv = parallaxOcclusion(huv, huvDx, huvDy, viewTS, surfaceDepth);
+        uv = huv / material.height_texture.scale;
+    }
+
+    vec3 N = normalize(normal);
+    if(material.normal_texture.enabled == 1) {
+        N = getNormalFromMap(uv, TBN);
+    }
+    // light the back of double sided surfaces as if they were the front
+    if(material.double_sided == 1 && !gl_FrontFacing) {
+        N = -N;
+    }
 
     // calculate reflectance at normal incidence; if dia-electric (like plastic) use F0
     // of 0.04 and if it's a metal, use the albedo color as F0 (metallic workflow)
     vec3 F0 = vec3(0.04);
 
-    // check if we want to use diffuse map
     vec3 albedoColor = material.albedo;
+    float metallic = material.metallic;
+    float roughness = material.roughness;
+    float ao = material.ao;
+    if(instanced == 1) {
+        albedoColor = oInstanceAlbedo;
+        metallic = oInstanceMaterial.x;
+        roughness = oInstanceMaterial.y;
+        ao = oInstanceMaterial.z;
+    }
+
+    // check if we want to use diffuse map
+    float alpha = material.opacity;
     if(material.diffuse_texture.enabled == 1) {
-        albedoColor = pow(texture(material.diffuse_texture.tex, oUVs * material.diffuse_texture.scale).rgb, vec3(2.2));
+        vec4 diffuseSample = texture(material.diffuse_texture.tex, uv * material.diffuse_texture.scale);
+        albedoColor = pow(diffuseSample.rgb, vec3(2.2));
+        alpha *= diffuseSample.a;
     }
 
-    // check if we want to use roughness map
-    float roughness = material.roughness;
-    if (material.arm_texture.enabled == 1) {
-        roughness = texture(material.arm_texture.tex, oUVs * material.arm_texture.scale).g;
+    if(material.alpha_mode == ALPHA_MASK && alpha < material.alpha_cutoff) {
+        discard;
+    }
+    if(material.alpha_mode == ALPHA_OPAQUE || material.alpha_mode == ALPHA_MASK) {
+        alpha = 1.0;
     }
 
-    F0 = mix(F0, albedoColor, material.metallic);
+    // ao, roughness and metallic packed in r, g and b
+    if(material.arm_texture.enabled == 1) {
+        vec3 arm = texture(material.arm_texture.tex, uv * material.arm_texture.scale).rgb;
+        ao = arm.r;
+        roughness = arm.g;
+        metallic = arm.b;
+    }
+    if(material.ao_texture.enabled == 1) {
+        ao = texture(material.ao_texture.tex, uv * material.ao_texture.scale).r;
+    }
+    if(material.roughness_texture.enabled == 1) {
+        roughness = texture(material.roughness_texture.tex, uv * material.roughness_texture.scale).r;
+    }
+    if(material.metallic_texture.enabled == 1) {
+        metallic = texture(material.metallic_texture.tex, uv * material.metallic_texture.scale).r;
+    }
+
+    roughness = clamp(roughness, MIN_ROUGHNESS, 1.0);
+    metallic = clamp(metallic, 0.0, 1.0);
+    ao = clamp(ao, 0.0, 1.0);
+
+    F0 = mix(F0, albedoColor, metallic);
 
     // reflectance equation
     vec3 Lo = vec3(0.0);
@@ -155,10 +328,14 @@ void main() {
 
         vec3 kS = F;
         vec3 kD = vec3(1.0) - kS;
-        kD *= 1.0 - material.metallic;
+        kD *= 1.0 - metallic;
 
         float NdotL = max(dot(N, L), 0.0);
         vec3 radiance = dirLight.color;
+        if(parallax && material.parallax_shadows == 1) {
+            vec3 lightTS = normalize(transpose(TBN) * L);
+            radiance *= parallaxShadow(huv, huvDx, huvDy, lightTS, surfaceDepth);
+        }
         Lo += (kD * albedoColor / PI + specular) * radiance * NdotL;
     }
 
@@ -169,6 +346,10 @@ void main() {
         float distance = length(pointLights[i].position - fragPosition);
         float attenuation = 1.0 / (1.0 + 0.09 * distance + 0.0032 * (distance * distance));
         vec3 radiance = pointLights[i].color * pointLights[i].strength * attenuation;
+        if(parallax && material.parallax_shadows == 1) {
+            vec3 lightTS = normalize(transpose(TBN) * L);
+            radiance *= parallaxShadow(huv, huvDx, huvDy, lightTS, surfaceDepth);
+        }
 
         // Cook-Torrance BRDF
         float NDF = DistributionGGX(N, H, roughness);
@@ -188,26 +369,45 @@ void main() {
         // multiply kD by the inverse metalness such that only non-metals
         // have diffuse lighting, or a linear blend if partly metal (pure metals
         // have no diffuse light).
-        kD *= 1.0 - material.metallic;
+        kD *= 1.0 - metallic;
 
         // scale light by NdotL
         float NdotL = max(dot(N, L), 0.0);
 
         // add to outgoing radiance Lo
         Lo += (kD * albedoColor / PI + specular) * radiance * NdotL;  // note that we already multiplied the BRDF by the Fresnel (kS) so we won't multiply by kS again
+    }
 
-        total += Lo;
+    // constant ambient split the same way as the direct light so metals don't go black, until
+    // there is environment lighting to replace it
+    vec3 ambientS = fresnelSchlick(max(dot(N, V), 0.0), F0);
+    vec3 ambientD = (vec3(1.0) - ambientS) * (1.0 - metallic);
+    vec3 ambient = vec3(0.03) * (ambientD * albedoColor + ambientS) * ao;
+
+    // emission is unlit and added in linear space before tonemapping
+    vec3 emission = material.emissive * material.emissive_strength;
+    if(material.emissive_texture.enabled == 1) {
+        emission *= pow(texture(material.emissive_texture.tex, uv * material.emissive_texture.scale).rgb, vec3(2.2));
     }
 
-    // ambient lighting (note that the next IBL tutorial will replace
-    // this ambient lighting with environment lighting).
-    float ao = material.ao;
-    if (material.arm_texture.enabled == 1) {
-        ao = texture(material.arm_texture.tex, oUVs).r;
+    if(debugView != DEBUG_LIT) {
+        vec3 debugColor = emission;
+        if(debugView == DEBUG_ALBEDO) {
+            debugColor = pow(albedoColor, vec3(1.0 / 2.2));
+        } else if(debugView == DEBUG_METALLIC) {
+            debugColor = vec3(metallic);
+        } else if(debugView == DEBUG_ROUGHNESS) {
+            debugColor = vec3(roughness);
+        } else if(debugView == DEBUG_AO) {
+            debugColor = vec3(ao);
+        } else if(debugView == DEBUG_NORMAL) {
+            debugColor = N * 0.5 + 0.5;
+        }
+        final_color = vec4(debugColor, 1.0);
+        return;
     }
 
-    vec3 ambient = vec3(0.03) * albedoColor * material.ao;
-    vec3 color = ambient + total;
+    vec3 color = ambient + Lo + emission;
 
     // HDR tonemapping
     color = color / (color + vec3(1.0));
@@ -218,5 +418,5 @@ void main() {
     highp float dither = random(coordinates);
     color += dither * NOISE_GRANULARITY;
 
-    final_color = vec4(color, 1.0);
+    final_color = vec4(color, alpha);
 }
@@ -3,20 +3,36 @@ layout(location = 0) in vec3 pos;
 layout(location = 1) in vec3 norm;
 layout(location = 2) in vec2 uvs;
 
+// per instance data, only read when drawing an InstancedObject
+layout(location = 3) in mat4 instanceModel;
+layout(location = 7) in vec3 instanceAlbedo;
+layout(location = 8) in vec3 instanceMaterial; // metallic, roughness, ao
+layout(location = 9) in vec3 tangent;
+
 uniform mat4 projection;
 uniform mat4 model;
 uniform mat4 view;
+uniform int instanced;
 
 out vec3 fragPosition;
 out vec3 normal;
 out vec2 oUVs;
+out vec3 oTangent;
+flat out vec3 oInstanceAlbedo;
+flat out vec3 oInstanceMaterial;
 
 void main() {
-    vec4 worldPosition = model * vec4(pos, 1.0);
+    mat4 modelMatrix = instanced == 1 ? instanceModel : model;
+
+    vec4 worldPosition = modelMatrix * vec4(pos, 1.0);
     fragPosition = vec3(worldPosition);
 
-    normal = mat3(transpose(inverse(model))) * norm;
+    normal = mat3(transpose(inverse(modelMatrix))) * norm;
+    // tangents lie in the surface so they move with it, unlike normals
+    oTangent = mat3(modelMatrix) * tangent;
 
     oUVs = uvs;
-    gl_Position = projection * view * model * vec4(pos, 1.0);
+    oInstanceAlbedo = instanceAlbedo;
+    oInstanceMaterial = instanceMaterial;
+    gl_Position = projection * view * worldPosition;
 }
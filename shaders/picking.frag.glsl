@@ -0,0 +1,9 @@
+#version 330 core
+// writes the object's id for the picking pass, paired with blinn.vert
+uniform uint objectId;
+
+out uint id;
+
+void main() {
+    id = objectId;
+}
@@ -0,0 +1,79 @@
+#version 330 core
+struct PointLight {
+    vec3 position;
+    vec3 color;
+    float strength;
+};
+
+struct DirectionalLight {
+    vec3 direction;
+    vec3 color;
+};
+
+struct ToonMaterial {
+    vec3 color;
+    int bands;
+    vec3 rim_color;
+    float rim_power;
+    vec3 outline_color;
+    float outline_width;
+};
+
+uniform ToonMaterial material;
+
+uniform vec3 camera_position;
+
+// 0 draws normally, (0, 1) fades this lod level in and (-1, 0) fades it out
+uniform float lodFade;
+
+uniform int numPointLights;
+uniform PointLight[4] pointLights;
+uniform DirectionalLight dirLight;
+
+in vec3 fragPosition;
+in vec3 normal;
+in vec2 oUVs;
+
+out vec4 final_color;
+
+float random(vec2 coords) {
+    return fract(sin(dot(coords.xy, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+// snaps the lambert term to a fixed number of flat steps
+float band(float NdotL) {
+    float bands = float(max(material.bands, 1));
+    return floor(max(NdotL, 0.0) * bands + 0.5) / bands;
+}
+
+void main() {
+    if(lodFade != 0.0) {
+        float threshold = random(floor(gl_FragCoord.xy));
+        if((lodFade > 0.0 && threshold >= lodFade) || (lodFade < 0.0 && threshold < -lodFade)) {
+            discard;
+        }
+    }
+
+    vec3 N = normalize(normal);
+    vec3 V = normalize(camera_position - fragPosition);
+    float NdotV = max(dot(N, V), 0.0);
+
+    // silhouette edges, where the surface turns away from the camera, are drawn as a solid line
+    if(NdotV < material.outline_width) {
+        final_color = vec4(material.outline_color, 1.0);
+        return;
+    }
+
+    vec3 light = 0.1 + band(dot(N, normalize(-dirLight.direction))) * dirLight.color;
+    for(int i = 0; i < numPointLights; i++) {
+        vec3 toLight = pointLights[i].position - fragPosition;
+        float distance = length(toLight);
+        float attenuation = 1.0 / (1.0 + 0.09 * distance + 0.0032 * (distance * distance));
+        light += band(dot(N, toLight / distance)) * pointLights[i].color * min(pointLights[i].strength * attenuation, 1.0);
+    }
+
+    float rim = pow(1.0 - NdotV, material.rim_power);
+    vec3 color = material.color * light + rim * material.rim_color;
+
+    final_color = vec4(color, 1.0);
+}
@@ -0,0 +1,44 @@
+#version 330 core
+struct MaterialTexture {
+    sampler2D tex;
+    int enabled;
+    float scale;
+};
+
+struct UnlitMaterial {
+    vec3 color;
+    float opacity;
+    MaterialTexture diffuse_texture;
+};
+
+uniform UnlitMaterial material;
+
+// 0 draws normally, (0, 1) fades this lod level in and (-1, 0) fades it out
+uniform float lodFade;
+
+in vec3 fragPosition;
+in vec3 normal;
+in vec2 oUVs;
+
+out vec4 final_color;
+
+float random(vec2 coords) {
+    return fract(sin(dot(coords.xy, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+    if(lodFade != 0.0) {
+        float threshold = random(floor(gl_FragCoord.xy));
+        if((lodFade > 0.0 && threshold >= lodFade) || (lodFade < 0.0 && threshold < -lodFade)) {
+            discard;
+        }
+    }
+
+    // color and texture are both in display space already, nothing to light or tonemap
+    vec4 color = vec4(material.color, material.opacity);
+    if(material.diffuse_texture.enabled == 1) {
+        color *= texture(material.diffuse_texture.tex, oUVs * material.diffuse_texture.scale);
+    }
+
+    final_color = color;
+}
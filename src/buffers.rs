@@ -1,4 +1,4 @@
-use crate::vertex::Vertex;
+use crate::{obj, vertex::Vertex};
 use gl::types::*;
 use nalgebra::{Vector2, Vector3};
 
@@ -7,6 +7,7 @@ pub struct RenderBuffers {
     pub vbo_positions: u32,
     pub vbo_normals: u32, // Separate buffer for normals
     pub vbo_uvs: u32,
+    pub vbo_tangents: u32,
     pub size: i32,
 }
 
@@ -17,6 +18,7 @@ impl RenderBuffers {
             vbo_positions: 0,
             vbo_normals: 0,
             vbo_uvs: 0,
+            vbo_tangents: 0,
             size: 0,
         }
     }
@@ -26,7 +28,17 @@ impl RenderBuffers {
         vertices: &Vec<Vector3<f32>>,
         normals: &Vec<Vector3<f32>>,
         uvs: &Vec<Vector2<f32>>,
+        tangents: &[Vector3<f32>],
     ) {
+        // meshes that don't bring their own tangents get them from their uvs
+        let computed;
+        let tangents = if tangents.len() == vertices.len() {
+            tangents
+        } else {
+            computed = obj::compute_tangents(vertices, normals, uvs);
+            &computed
+        };
+
         unsafe {
             // Generate and bind the VAO
             gl::GenVertexArrays(1, &mut self.vao);
@@ -99,6 +111,27 @@ impl RenderBuffers {
             );
             gl::EnableVertexAttribArray(2);
 
+            // tangent attribute, after the per instance ones in 3 to 8
+            gl::GenBuffers(1, &mut self.vbo_tangents);
+            assert_ne!(self.vbo_tangents, 0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo_tangents);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                std::mem::size_of_val(tangents) as isize,
+                tangents.as_ptr().cast(),
+                gl::STATIC_DRAW,
+            );
+
+            gl::VertexAttribPointer(
+                9,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                size_of::<Vertex>().try_into().unwrap(),
+                std::ptr::null(),
+            );
+            gl::EnableVertexAttribArray(9);
+
             self.size = vertices
                 .len()
                 .try_into()
@@ -0,0 +1,655 @@
+// bounding volume hierarchies for ray queries. Bvh only knows about boxes, MeshBvh puts one over
+// a mesh's triangles and SceneBvh over the world bounds of a scene's objects.
+// built top down, each split picked by the surface area heuristic over a fixed number of bins
+
+use nalgebra::{Vector2, Vector3};
+
+use crate::{collision::BoundingBox, raycast::Ray};
+
+const BINS: usize = 12;
+// leaves this small are never split
+const MIN_SPLIT: usize = 2;
+// and leaves this big always are, even when the heuristic says it isn't worth it
+const MAX_LEAF: usize = 8;
+
+struct Node {
+    bbox: BoundingBox,
+    // the left child for inner nodes (the right is always next to it), the first primitive for leaves
+    first: usize,
+    // zero for inner nodes
+    count: usize,
+}
+
+pub struct Bvh {
+    nodes: Vec<Node>,
+    // primitive indices, leaves own consecutive runs of these
+    order: Vec<usize>,
+}
+
+fn empty_box() -> BoundingBox {
+    BoundingBox::new(
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+    )
+}
+
+impl Bvh {
+    pub fn new(bounds: &[BoundingBox]) -> Bvh {
+        let centroids: Vec<Vector3<f32>> = bounds.iter().map(|b| b.center()).collect();
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            order: (0..bounds.len()).collect(),
+        };
+        if bounds.is_empty() {
+            return bvh;
+        }
+
+        bvh.nodes.push(Node {
+            bbox: empty_box(),
+            first: 0,
+            count: bounds.len(),
+        });
+        bvh.subdivide(0, bounds, &centroids);
+        bvh
+    }
+
+    fn subdivide(&mut self, node: usize, bounds: &[BoundingBox], centroids: &[Vector3<f32>]) {
+        let (first, count) = (self.nodes[node].first, self.nodes[node].count);
+        let primitives = &self.order[first..first + count];
+
+        let bbox = primitives
+            .iter()
+            .fold(empty_box(), |acc, i| acc.union(&bounds[*i]));
+        self.nodes[node].bbox = bbox;
+        if count <= MIN_SPLIT {
+            return;
+        }
+
+        let Some((axis, split, cost)) = best_split(primitives, bounds, centroids) else {
+            return;
+        };
+        if cost >= count as f32 * bbox.surface_area() && count <= MAX_LEAF {
+            return;
+        }
+
+        // partition the run so everything left of the split comes first
+        let run = &mut self.order[first..first + count];
+        let mut left = 0;
+        for i in 0..run.len() {
+            if centroids[run[i]][axis] < split {
+                run.swap(i, left);
+                left += 1;
+            }
+        }
+        if left == 0 || left == count {
+            return;
+        }
+
+        let child = self.nodes.len();
+        self.nodes.push(Node {
+            bbox: empty_box(),
+            first,
+            count: left,
+        });
+        self.nodes.push(Node {
+            bbox: empty_box(),
+            first: first + left,
+            count: count - left,
+        });
+        self.nodes[node].first = child;
+        self.nodes[node].count = 0;
+
+        self.subdivide(child, bounds, centroids);
+        self.subdivide(child + 1, bounds, centroids);
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn depth(&self) -> usize {
+        fn depth(nodes: &[Node], node: usize) -> usize {
+            if nodes[node].count > 0 {
+                1
+            } else {
+                1 + depth(nodes, nodes[node].first).max(depth(nodes, nodes[node].first + 1))
+            }
+        }
+        if self.nodes.is_empty() {
+            0
+        } else {
+            depth(&self.nodes, 0)
+        }
+    }
+
+    // the nearest primitive hit, `hit` tests a primitive against the ray and returns its distance
+    // and whatever else the caller wants back. boxes further away than the best hit are skipped
+    pub fn closest<T>(
+        &self,
+        ray: &Ray,
+        mut hit: impl FnMut(usize) -> Option<(f32, T)>,
+    ) -> Option<(f32, T)> {
+        let mut best: Option<(f32, T)> = None;
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let Some((entry, _)) = ray.intersect_bounding_box(&node.bbox) else {
+                continue;
+            };
+            if best.as_ref().is_some_and(|(distance, _)| entry > *distance) {
+                continue;
+            }
+
+            if node.count > 0 {
+                for primitive in &self.order[node.first..node.first + node.count] {
+                    if let Some((distance, value)) = hit(*primitive) {
+                        if best.as_ref().is_none_or(|(d, _)| distance < *d) {
+                            best = Some((distance, value));
+                        }
+                    }
+                }
+            } else {
+                // visit the nearer child first so more of the far one gets skipped
+                let (left, right) = (node.first, node.first + 1);
+                let distance = |i: usize| {
+                    ray.intersect_bounding_box(&self.nodes[i].bbox)
+                        .map_or(f32::INFINITY, |(d, _)| d)
+                };
+                if distance(left) <= distance(right) {
+                    stack.push(right);
+                    stack.push(left);
+                } else {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+        best
+    }
+
+    // every primitive in the leaves the ray passes through, which may include a few it misses
+    pub fn candidates(&self, ray: &Ray) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if ray.intersect_bounding_box(&node.bbox).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                found.extend_from_slice(&self.order[node.first..node.first + node.count]);
+            } else {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+            }
+        }
+        found
+    }
+}
+
+// the cheapest split plane over all three axes, as (axis, position, cost)
+fn best_split(
+    primitives: &[usize],
+    bounds: &[BoundingBox],
+    centroids: &[Vector3<f32>],
+) -> Option<(usize, f32, f32)> {
+    let mut best: Option<(usize, f32, f32)> = None;
+
+    let (lows, highs) = primitives.iter().fold(
+        (
+            Vector3::repeat(f32::INFINITY),
+            Vector3::repeat(f32::NEG_INFINITY),
+        ),
+        |(lows, highs), i| (lows.inf(&centroids[*i]), highs.sup(&centroids[*i])),
+    );
+
+    for (axis, (min, max)) in lows.iter().copied().zip(highs.iter().copied()).enumerate() {
+        if max - min <= f32::EPSILON {
+            continue;
+        }
+
+        let mut bins = [(empty_box(), 0usize); BINS];
+        let scale = BINS as f32 / (max - min);
+        for i in primitives {
+            let bin = (((centroids[*i][axis] - min) * scale) as usize).min(BINS - 1);
+            bins[bin].0 = bins[bin].0.union(&bounds[*i]);
+            bins[bin].1 += 1;
+        }
+
+        // sweep from both ends so every plane's cost comes out in one pass each way
+        let mut left_area = [0.0; BINS - 1];
+        let mut left_count = [0; BINS - 1];
+        let (mut bbox, mut count) = (empty_box(), 0);
+        for plane in 0..BINS - 1 {
+            bbox = bbox.union(&bins[plane].0);
+            count += bins[plane].1;
+            left_area[plane] = bbox.surface_area();
+            left_count[plane] = count;
+        }
+
+        let (mut bbox, mut count) = (empty_box(), 0);
+        for plane in (0..BINS - 1).rev() {
+            bbox = bbox.union(&bins[plane + 1].0);
+            count += bins[plane + 1].1;
+            if left_count[plane] == 0 || count == 0 {
+                continue;
+            }
+
+            let cost =
+                left_count[plane] as f32 * left_area[plane] + count as f32 * bbox.surface_area();
+            if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                let position = min + (plane + 1) as f32 / scale;
+                best = Some((axis, position, cost));
+            }
+        }
+    }
+
+    best
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriangleHit {
+    pub distance: f32,
+    pub triangle: usize,
+    // weights of the triangle's three corners at the hit
+    pub barycentric: Vector3<f32>,
+    pub uv: Vector2<f32>,
+    // the geometric normal, turned to face back along the ray
+    pub normal: Vector3<f32>,
+}
+
+// a bvh over a triangle soup, three positions (and uvs) per triangle like Object keeps them. the
+// mesh isn't copied, queries borrow the same positions and uvs the bvh was built over
+pub struct MeshBvh {
+    bvh: Bvh,
+    triangles: usize,
+}
+
+impl MeshBvh {
+    pub fn new(positions: &[Vector3<f32>]) -> MeshBvh {
+        let bounds: Vec<BoundingBox> = positions
+            .chunks_exact(3)
+            .map(crate::collision::get_bounding_box)
+            .collect();
+
+        MeshBvh {
+            bvh: Bvh::new(&bounds),
+            triangles: bounds.len(),
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles
+    }
+
+    pub fn bvh(&self) -> &Bvh {
+        &self.bvh
+    }
+
+    pub fn intersect(
+        &self,
+        ray: &Ray,
+        positions: &[Vector3<f32>],
+        uvs: &[Vector2<f32>],
+    ) -> Option<TriangleHit> {
+        self.check_mesh(positions);
+        let (distance, (triangle, uv)) = self.bvh.closest(ray, |triangle| {
+            hit_triangle(ray, positions, triangle).map(|(distance, uv)| (distance, (triangle, uv)))
+        })?;
+        Some(describe(ray, positions, uvs, triangle, distance, uv))
+    }
+
+    // tests every triangle, for checking the bvh against
+    pub fn intersect_brute_force(
+        &self,
+        ray: &Ray,
+        positions: &[Vector3<f32>],
+        uvs: &[Vector2<f32>],
+    ) -> Option<TriangleHit> {
+        self.check_mesh(positions);
+        (0..self.triangles)
+            .filter_map(|triangle| {
+                hit_triangle(ray, positions, triangle)
+                    .map(|(distance, uv)| (distance, triangle, uv))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(distance, triangle, uv)| describe(ray, positions, uvs, triangle, distance, uv))
+    }
+
+    fn check_mesh(&self, positions: &[Vector3<f32>]) {
+        assert_eq!(
+            positions.len() / 3,
+            self.triangles,
+            "mesh bvh queried with a different mesh than it was built over"
+        );
+    }
+}
+
+fn hit_triangle(
+    ray: &Ray,
+    positions: &[Vector3<f32>],
+    triangle: usize,
+) -> Option<(f32, (f32, f32))> {
+    let corners = &positions[triangle * 3..triangle * 3 + 3];
+    let (distance, u, v) = ray.intersect_triangle(&corners[0], &corners[1], &corners[2])?;
+    Some((distance, (u, v)))
+}
+
+fn describe(
+    ray: &Ray,
+    positions: &[Vector3<f32>],
+    uvs: &[Vector2<f32>],
+    triangle: usize,
+    distance: f32,
+    (u, v): (f32, f32),
+) -> TriangleHit {
+    let corners = &positions[triangle * 3..triangle * 3 + 3];
+    let barycentric = Vector3::new(1.0 - u - v, u, v);
+    let uv = match uvs.get(triangle * 3..triangle * 3 + 3) {
+        Some(uvs) => uvs[0] * barycentric.x + uvs[1] * barycentric.y + uvs[2] * barycentric.z,
+        None => Vector2::zeros(),
+    };
+    let normal = (corners[1] - corners[0])
+        .cross(&(corners[2] - corners[0]))
+        .try_normalize(f32::EPSILON)
+        .unwrap_or(-ray.direction);
+    let normal = if normal.dot(&ray.direction) > 0.0 {
+        -normal
+    } else {
+        normal
+    };
+
+    TriangleHit {
+        distance,
+        triangle,
+        barycentric,
+        uv,
+        normal,
+    }
+}
+
+// world bounds of named things, rebuilt whenever they move
+pub struct SceneBvh {
+    bvh: Bvh,
+    names: Vec<String>,
+    bounds: Vec<BoundingBox>,
+}
+
+impl SceneBvh {
+    pub fn new(items: Vec<(String, BoundingBox)>) -> SceneBvh {
+        let bounds: Vec<BoundingBox> = items.iter().map(|(_, bbox)| *bbox).collect();
+        SceneBvh {
+            bvh: Bvh::new(&bounds),
+            names: items.into_iter().map(|(name, _)| name).collect(),
+            bounds,
+        }
+    }
+
+    // names of everything whose bounds the ray passes through
+    pub fn candidates(&self, ray: &Ray) -> Vec<&str> {
+        self.bvh
+            .candidates(ray)
+            .into_iter()
+            .filter(|i| ray.intersect_bounding_box(&self.bounds[*i]).is_some())
+            .map(|i| self.names[i].as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{obj, primitives};
+    use nalgebra::Point3;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray {
+        Ray {
+            origin: Point3::from(origin),
+            direction: Vector3::from(direction).normalize(),
+        }
+    }
+
+    fn triangle() -> Vec<Vector3<f32>> {
+        vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn triangle_hits_and_misses() {
+        let [a, b, c] = [triangle()[0], triangle()[1], triangle()[2]];
+
+        let (t, u, v) = ray([0.25, 0.25, 2.0], [0.0, 0.0, -1.0])
+            .intersect_triangle(&a, &b, &c)
+            .unwrap();
+        assert!((t - 2.0).abs() < 1e-6);
+        assert!((u - 0.25).abs() < 1e-6 && (v - 0.25).abs() < 1e-6);
+
+        // the back face counts too
+        assert!(ray([0.25, 0.25, -2.0], [0.0, 0.0, 1.0])
+            .intersect_triangle(&a, &b, &c)
+            .is_some());
+
+        // outside the edges, behind the ray, and along the triangle's plane
+        assert!(ray([0.75, 0.75, 2.0], [0.0, 0.0, -1.0])
+            .intersect_triangle(&a, &b, &c)
+            .is_none());
+        assert!(ray([0.25, 0.25, 2.0], [0.0, 0.0, 1.0])
+            .intersect_triangle(&a, &b, &c)
+            .is_none());
+        assert!(ray([-1.0, 0.25, 0.0], [1.0, 0.0, 0.0])
+            .intersect_triangle(&a, &b, &c)
+            .is_none());
+    }
+
+    #[test]
+    fn triangle_edges_vertices_and_degenerates() {
+        let [a, b, c] = [triangle()[0], triangle()[1], triangle()[2]];
+
+        // exactly on an edge and on a corner still hits
+        let (_, u, v) = ray([0.5, 0.0, 1.0], [0.0, 0.0, -1.0])
+            .intersect_triangle(&a, &b, &c)
+            .unwrap();
+        assert!((u - 0.5).abs() < 1e-6 && v.abs() < 1e-6);
+        assert!(ray([1.0, 0.0, 1.0], [0.0, 0.0, -1.0])
+            .intersect_triangle(&a, &b, &c)
+            .is_some());
+
+        // a ray starting on the surface doesn't hit it again
+        assert!(ray([0.25, 0.25, 0.0], [0.0, 0.0, 1.0])
+            .intersect_triangle(&a, &b, &c)
+            .is_none());
+
+        // triangles without area can't be hit
+        let line = Vector3::new(2.0, 0.0, 0.0);
+        assert!(ray([0.5, 0.0, 1.0], [0.0, 0.0, -1.0])
+            .intersect_triangle(&a, &b, &line)
+            .is_none());
+    }
+
+    #[test]
+    fn mesh_hits_carry_barycentrics_and_uvs() {
+        let uvs = vec![
+            Vector2::new(0.0, 0.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(0.0, 1.0),
+        ];
+        let mesh = MeshBvh::new(&triangle());
+        let hit = mesh
+            .intersect(&ray([0.2, 0.6, 5.0], [0.0, 0.0, -1.0]), &triangle(), &uvs)
+            .unwrap();
+
+        assert_eq!(hit.triangle, 0);
+        assert!((hit.distance - 5.0).abs() < 1e-5);
+        assert!((hit.barycentric - Vector3::new(0.2, 0.2, 0.6)).norm() < 1e-5);
+        assert!((hit.uv - Vector2::new(0.2, 0.6)).norm() < 1e-5);
+        assert_eq!(hit.normal, Vector3::new(0.0, 0.0, 1.0));
+
+        let empty = MeshBvh::new(&[]);
+        assert_eq!(empty.bvh().node_count(), 0);
+        assert!(empty
+            .intersect(&ray([0.0, 0.0, 1.0], [0.0, 0.0, -1.0]), &[], &[])
+            .is_none());
+    }
+
+    #[test]
+    fn sphere_hits_follow_the_surface_not_the_box() {
+        let sphere = primitives::uv_sphere(1.0, 32, 16);
+        let mesh = MeshBvh::new(&sphere.vertices);
+        let intersect = |ray: &Ray| mesh.intersect(ray, &sphere.vertices, &sphere.tex_coords);
+
+        // a ray past the sphere through its bounding box corner misses
+        let corner = ray([0.95, 0.95, 5.0], [0.0, 0.0, -1.0]);
+        assert!(corner
+            .intersect_bounding_box(&crate::collision::get_bounding_box(&sphere.vertices))
+            .is_some());
+        assert!(intersect(&corner).is_none());
+
+        let hit = intersect(&ray([0.0, 0.0, 5.0], [0.0, 0.0, -1.0])).unwrap();
+        assert!((hit.distance - 4.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn splits_keep_every_primitive_once() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let bounds: Vec<BoundingBox> = (0..500)
+            .map(|_| {
+                let (x, y, z) = (
+                    rng.gen_range(-50.0..50.0),
+                    rng.gen_range(-50.0..50.0),
+                    rng.gen_range(-50.0..50.0),
+                );
+                BoundingBox::new(x, x + 1.0, y, y + 1.0, z, z + 1.0)
+            })
+            .collect();
+        let bvh = Bvh::new(&bounds);
+
+        let mut order = bvh.order.clone();
+        order.sort();
+        assert_eq!(order, (0..500).collect::<Vec<_>>());
+        for node in bvh.nodes.iter().filter(|node| node.count > 0) {
+            assert!(node.count <= MAX_LEAF);
+            for i in &bvh.order[node.first..node.first + node.count] {
+                assert!(
+                    node.bbox.union(&bounds[*i]).surface_area() <= node.bbox.surface_area() + 1e-3
+                );
+            }
+        }
+        // far shallower than a list, far deeper than a single leaf
+        assert!(bvh.depth() > 4 && bvh.depth() < 40);
+
+        // identical boxes can't be split, they just end up in one leaf
+        let same = Bvh::new(&vec![BoundingBox::new(0.0, 1.0, 0.0, 1.0, 0.0, 1.0); 20]);
+        assert_eq!(same.node_count(), 1);
+    }
+
+    #[test]
+    fn teapot_bvh_agrees_with_brute_force() {
+        let teapot = obj::parse_obj("resources/teapot.obj").expect("unable to load teapot");
+        let mesh = MeshBvh::new(&teapot.vertices);
+        let bounds = crate::collision::get_bounding_box(&teapot.vertices);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let mut hits = 0;
+        for _ in 0..500 {
+            let target = bounds.center()
+                + Vector3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+                .component_mul(&bounds.half_extents());
+            let origin = bounds.center()
+                + Vector3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+                .normalize()
+                    * 200.0;
+            let ray = Ray {
+                origin: Point3::from(origin),
+                direction: (target - origin).normalize(),
+            };
+
+            let fast = mesh.intersect(&ray, &teapot.vertices, &teapot.tex_coords);
+            let slow = mesh.intersect_brute_force(&ray, &teapot.vertices, &teapot.tex_coords);
+            assert_eq!(fast.is_some(), slow.is_some());
+            if let (Some(fast), Some(slow)) = (fast, slow) {
+                assert!((fast.distance - slow.distance).abs() < 1e-3);
+                hits += 1;
+            }
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn scene_bvh_finds_the_boxes_on_the_ray() {
+        let items = (0..50)
+            .map(|i| {
+                let x = i as f32 * 10.0;
+                (
+                    format!("box{}", i),
+                    BoundingBox::new(x, x + 2.0, 0.0, 2.0, 0.0, 2.0),
+                )
+            })
+            .collect();
+        let scene = SceneBvh::new(items);
+
+        let mut found = scene.candidates(&ray([101.0, 1.0, 50.0], [0.0, 0.0, -1.0]));
+        assert_eq!(found, vec!["box10"]);
+
+        found = scene.candidates(&ray([-10.0, 1.0, 1.0], [1.0, 0.0, 0.0]));
+        assert_eq!(found.len(), 50);
+        assert!(scene
+            .candidates(&ray([0.0, 10.0, 1.0], [1.0, 0.0, 0.0]))
+            .is_empty());
+    }
+
+    #[test]
+    fn teapot_bvh_tests_a_fraction_of_the_triangles() {
+        let teapot = obj::parse_obj("resources/teapot.obj").expect("unable to load teapot");
+        let mesh = MeshBvh::new(&teapot.vertices);
+        let bounds = crate::collision::get_bounding_box(&teapot.vertices);
+        let mut rng = StdRng::seed_from_u64(2);
+
+        // rays from the front at points spread over the teapot's silhouette, counting the
+        // triangles the bvh has to test against the whole mesh brute force goes through
+        let rays = 1000;
+        let mut tested = 0;
+        let mut hits = 0;
+        for _ in 0..rays {
+            let origin = bounds.center() + Vector3::new(0.0, 0.0, 200.0);
+            let target = bounds.center()
+                + Vector3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0)
+                    .component_mul(&bounds.half_extents());
+            let ray = Ray {
+                origin: Point3::from(origin),
+                direction: (target - origin).normalize(),
+            };
+            let hit = mesh.bvh().closest(&ray, |triangle| {
+                tested += 1;
+                hit_triangle(&ray, &teapot.vertices, triangle)
+            });
+            hits += hit.is_some() as usize;
+        }
+
+        assert!(hits > rays / 4);
+        assert!(tested * 20 < rays * mesh.triangle_count());
+    }
+}
@@ -1,11 +1,111 @@
+use crate::raycast::Ray;
+use crate::render_target::RenderTarget;
 use crate::shader::get_shader_location;
-use nalgebra::{Const, Matrix4, Point, Point3, Vector3};
+use nalgebra::{Const, Matrix4, Point, Point3, Vector2, Vector3};
 use gl::types::*;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective { fovy: f32, near: f32, far: f32 },
+    // height is how many world units fit vertically, the width follows from the aspect
+    Orthographic { height: f32, near: f32, far: f32 },
+    // no far plane and depth going from 1 at the near plane to 0 at infinity, which spreads float
+    // depth precision evenly. needs a GREATER depth test and the depth buffer cleared to 0
+    InfiniteReversed { fovy: f32, near: f32 },
+}
+
+impl Projection {
+    pub fn matrix(&self, aspect: f32) -> Matrix4<f32> {
+        match *self {
+            Projection::Perspective { fovy, near, far } => {
+                Matrix4::new_perspective(aspect, fovy, near, far)
+            }
+            Projection::Orthographic { height, near, far } => {
+                let half_height = height * 0.5;
+                let half_width = half_height * aspect;
+                Matrix4::new_orthographic(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+            Projection::InfiniteReversed { fovy, near } => {
+                let f = 1.0 / (fovy * 0.5).tan();
+                Matrix4::new(
+                    f / aspect, 0.0, 0.0, 0.0,
+                    0.0, f, 0.0, 0.0,
+                    0.0, 0.0, 0.0, near,
+                    0.0, 0.0, -1.0, 0.0,
+                )
+            }
+        }
+    }
+
+    pub fn reversed_z(&self) -> bool {
+        matches!(self, Projection::InfiniteReversed { .. })
+    }
+
+    // the ndc depth of the near plane and of a point further along, to unproject rays between
+    pub fn ndc_depth_range(&self) -> (f32, f32) {
+        if self.reversed_z() {
+            (1.0, 0.5)
+        } else {
+            (-1.0, 1.0)
+        }
+    }
+}
+
+// the part of the window (or render target) a camera draws into, as fractions of its size with
+// the origin in the bottom left like gl
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Viewport {
+        Viewport {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn full() -> Viewport {
+        Viewport::new(0.0, 0.0, 1.0, 1.0)
+    }
+
+    // x, y, width and height in pixels of a surface of the given size
+    pub fn pixels(&self, width: i32, height: i32) -> (i32, i32, i32, i32) {
+        let x = (self.x * width as f32).round() as i32;
+        let y = (self.y * height as f32).round() as i32;
+        let right = ((self.x + self.width) * width as f32).round() as i32;
+        let top = ((self.y + self.height) * height as f32).round() as i32;
+        (x, y, (right - x).max(1), (top - y).max(1))
+    }
+}
+
 pub struct Camera {
     pub position: Point<f32, 3>,
     target: Point<f32, 3>,
     up: Vector3<f32>,
+    pub projection: Projection,
+    // width over height of whatever the camera renders to, kept up to date by the main loop
+    pub aspect: f32,
+    pub viewport: Viewport,
+    // draws into this instead of the window when set
+    pub render_target: Option<RenderTarget>,
+    // cameras are drawn from the lowest order up, so render targets should come before the
+    // cameras that see them and overlays like a minimap after the main view
+    pub order: i32,
+    pub enabled: bool,
 }
 
 impl Camera {
@@ -14,21 +114,32 @@ impl Camera {
             position,
             target,
             up,
+            projection: Projection::Perspective {
+                fovy: 60.0_f32.to_radians(),
+                near: 0.1,
+                far: 10000.0,
+            },
+            aspect: 1.0,
+            viewport: Viewport::full(),
+            render_target: None,
+            order: 0,
+            enabled: true,
         }
     }
 
     pub fn default() -> Camera {
-        Camera {
-            position: Point3::new(0.0, 0.0, 0.0),
-            target: Point3::new(0.0, 0.0, 0.5),
-            up: Vector3::new(0.0, 1.0, 0.0),
-        }
+        Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, 0.5),
+            Vector3::new(0.0, 1.0, 0.0),
+        )
     }
 
     pub fn link_shader(&self, program: u32) {
         // link the uniforms with the shader
         let position_loc = get_shader_location(program, "camera_position");
         let view_loc = get_shader_location(program, "view");
+        let projection_loc = get_shader_location(program, "projection");
 
         unsafe {
             gl::Uniform3f(
@@ -38,6 +149,32 @@ impl Camera {
                 self.position.z,
             );
             gl::UniformMatrix4fv(view_loc, 1, gl::FALSE, self.view_matrix().as_ptr());
+            gl::UniformMatrix4fv(
+                projection_loc,
+                1,
+                gl::FALSE,
+                self.projection_matrix(self.aspect).as_ptr(),
+            );
+        }
+    }
+
+    // reversed depth wants far cleared to 0 and nearer fragments to win with GREATER.
+    // clip control keeps the whole [0, 1] depth range for it where gl 4.5 is around
+    pub fn apply_depth_state(&self) {
+        unsafe {
+            if self.projection.reversed_z() {
+                if gl::ClipControl::is_loaded() {
+                    gl::ClipControl(gl::LOWER_LEFT, gl::ZERO_TO_ONE);
+                }
+                gl::ClearDepth(0.0);
+                gl::DepthFunc(gl::GREATER);
+            } else {
+                if gl::ClipControl::is_loaded() {
+                    gl::ClipControl(gl::LOWER_LEFT, gl::NEGATIVE_ONE_TO_ONE);
+                }
+                gl::ClearDepth(1.0);
+                gl::DepthFunc(gl::LESS);
+            }
         }
     }
 
@@ -45,7 +182,220 @@ impl Camera {
         self.target = t;
     }
 
+    pub fn target(&self) -> Point3<f32> {
+        self.target
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.up
+    }
+
     pub fn view_matrix(&self) -> Matrix4<f32> {
         return Matrix4::look_at_rh(&self.position, &self.target, &self.up);
     }
+
+    pub fn projection_matrix(&self, aspect: f32) -> Matrix4<f32> {
+        self.projection.matrix(aspect)
+    }
+
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        self.projection_matrix(self.aspect) * self.view_matrix()
+    }
+
+    // the pixel rectangle this camera draws to, given the window size
+    pub fn viewport_pixels(&self, window_width: i32, window_height: i32) -> (i32, i32, i32, i32) {
+        match &self.render_target {
+            Some(target) => self.viewport.pixels(target.width, target.height),
+            None => self.viewport.pixels(window_width, window_height),
+        }
+    }
+
+    // the ray under a window position in sdl's pixel coordinates (origin top left). nothing when
+    // the position is outside this camera's viewport or the camera draws to a render target
+    pub fn screen_point_to_ray(&self, x: i32, y: i32, window: (i32, i32)) -> Option<Ray> {
+        if self.render_target.is_some() {
+            return None;
+        }
+        let (left, bottom, width, height) = self.viewport_pixels(window.0, window.1);
+        let (x, y) = (x as f32 + 0.5, (window.1 - y) as f32 - 0.5);
+        if x < left as f32
+            || x > (left + width) as f32
+            || y < bottom as f32
+            || y > (bottom + height) as f32
+        {
+            return None;
+        }
+
+        let ndc_x = (x - left as f32) / width as f32 * 2.0 - 1.0;
+        let ndc_y = (y - bottom as f32) / height as f32 * 2.0 - 1.0;
+        let inverse = self.view_projection().try_inverse()?;
+
+        // unproject a point on the near plane and one further in, orthographic rays don't all
+        // start at the camera position
+        let (near_depth, far_depth) = self.projection.ndc_depth_range();
+        let near = inverse.transform_point(&Point3::new(ndc_x, ndc_y, near_depth));
+        let far = inverse.transform_point(&Point3::new(ndc_x, ndc_y, far_depth));
+
+        Some(Ray {
+            origin: near,
+            direction: (far - near).try_normalize(f32::EPSILON)?,
+        })
+    }
+
+    // where a world position lands in the window, in the same coordinates screen_point_to_ray
+    // takes. nothing for points behind the camera
+    pub fn world_to_screen(&self, point: &Point3<f32>, window: (i32, i32)) -> Option<Vector2<f32>> {
+        let clip = self.view_projection() * point.to_homogeneous();
+        if clip.w <= f32::EPSILON {
+            return None;
+        }
+        let (left, bottom, width, height) = self.viewport_pixels(window.0, window.1);
+        let x = left as f32 + (clip.x / clip.w + 1.0) * 0.5 * width as f32;
+        let y = bottom as f32 + (clip.y / clip.w + 1.0) * 0.5 * height as f32;
+        Some(Vector2::new(x - 0.5, window.1 as f32 - y - 0.5))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ndc_depth(projection: &Projection, distance: f32) -> f32 {
+        let clip = projection.matrix(1.0) * nalgebra::Vector4::new(0.0, 0.0, -distance, 1.0);
+        clip.z / clip.w
+    }
+
+    #[test]
+    fn perspective_and_orthographic_map_near_and_far() {
+        let perspective = Projection::Perspective {
+            fovy: 1.0,
+            near: 0.1,
+            far: 100.0,
+        };
+        assert!((ndc_depth(&perspective, 0.1) + 1.0).abs() < 1e-4);
+        assert!((ndc_depth(&perspective, 100.0) - 1.0).abs() < 1e-4);
+
+        let orthographic = Projection::Orthographic {
+            height: 20.0,
+            near: 1.0,
+            far: 50.0,
+        };
+        // the aspect widens the view, the height stays fixed
+        let edge = orthographic
+            .matrix(2.0)
+            .transform_point(&Point3::new(20.0, 10.0, -5.0));
+        assert!((edge.x - 1.0).abs() < 1e-5 && (edge.y - 1.0).abs() < 1e-5);
+        assert!((ndc_depth(&orthographic, 50.0) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn infinite_reversed_goes_from_one_to_zero() {
+        let projection = Projection::InfiniteReversed {
+            fovy: 1.0,
+            near: 0.5,
+        };
+        assert!(projection.reversed_z());
+        assert!((ndc_depth(&projection, 0.5) - 1.0).abs() < 1e-6);
+        assert!(ndc_depth(&projection, 1.0e6) < 1e-5);
+
+        // closer is always bigger
+        let near = ndc_depth(&projection, 10.0);
+        let far = ndc_depth(&projection, 11.0);
+        assert!(near > far && far > 0.0);
+    }
+
+    #[test]
+    fn viewports_cover_their_share_of_the_window() {
+        assert_eq!(Viewport::full().pixels(1200, 800), (0, 0, 1200, 800));
+
+        // right half of a split screen
+        assert_eq!(
+            Viewport::new(0.5, 0.0, 0.5, 1.0).pixels(1201, 800),
+            (601, 0, 600, 800)
+        );
+
+        // a corner inset never collapses to nothing
+        let (x, y, width, height) = Viewport::new(0.75, 0.7, 0.2, 0.3).pixels(1200, 800);
+        assert_eq!((x, y, width, height), (900, 560, 240, 240));
+        assert_eq!(Viewport::new(0.5, 0.5, 0.0, 0.0).pixels(10, 10).2, 1);
+    }
+
+    #[test]
+    fn screen_rays_go_through_what_is_under_the_cursor() {
+        let mut camera = Camera::new(
+            Point3::new(0.0, 0.0, 10.0),
+            Point3::origin(),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        let window = (800, 600);
+        camera.aspect = 800.0 / 600.0;
+
+        let ray = camera.screen_point_to_ray(400, 300, window).unwrap();
+        assert!((ray.direction - Vector3::new(0.0, 0.0, -1.0)).norm() < 1e-2);
+
+        // world to screen and back lands on the same point
+        let point = Point3::new(2.0, 1.5, -3.0);
+        let screen = camera.world_to_screen(&point, window).unwrap();
+        let ray = camera
+            .screen_point_to_ray(screen.x.round() as i32, screen.y.round() as i32, window)
+            .unwrap();
+        let along = (point - ray.origin).dot(&ray.direction);
+        assert!((ray.origin + ray.direction * along - point).norm() < 0.05);
+
+        // things up and to the right are up and to the right on screen, with y growing downwards
+        assert!(screen.x > 400.0 && screen.y < 300.0);
+        assert!(camera
+            .world_to_screen(&Point3::new(0.0, 0.0, 20.0), window)
+            .is_none());
+    }
+
+    #[test]
+    fn screen_rays_respect_viewports_and_projections() {
+        let mut camera = Camera::new(
+            Point3::new(0.0, 100.0, 0.0),
+            Point3::origin(),
+            Vector3::new(0.0, 0.0, -1.0),
+        );
+        camera.projection = Projection::Orthographic {
+            height: 100.0,
+            near: 1.0,
+            far: 200.0,
+        };
+        // the right half of the window
+        camera.viewport = Viewport::new(0.5, 0.0, 0.5, 1.0);
+        camera.aspect = 1.0;
+        let window = (200, 100);
+
+        assert!(camera.screen_point_to_ray(50, 50, window).is_none());
+
+        // orthographic rays are parallel, offset by where the cursor is
+        let centre = camera.screen_point_to_ray(150, 50, window).unwrap();
+        let corner = camera.screen_point_to_ray(199, 0, window).unwrap();
+        assert!((centre.direction - corner.direction).norm() < 1e-4);
+        assert!(centre.origin.x.abs() < 1.0 && centre.origin.z.abs() < 1.0);
+        assert!(corner.origin.x > 45.0 && corner.origin.z < -45.0);
+
+        camera.projection = Projection::InfiniteReversed {
+            fovy: 1.0,
+            near: 0.5,
+        };
+        let ray = camera.screen_point_to_ray(150, 50, window).unwrap();
+        assert!((ray.direction - Vector3::new(0.0, -1.0, 0.0)).norm() < 1e-2);
+    }
+
+    #[test]
+    fn view_projection_uses_the_camera_aspect() {
+        let mut camera = Camera::new(
+            Point3::new(0.0, 0.0, 10.0),
+            Point3::origin(),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        camera.aspect = 2.0;
+        assert_eq!(
+            camera.view_projection(),
+            camera.projection_matrix(2.0) * camera.view_matrix()
+        );
+        let center = camera.view_projection().transform_point(&Point3::origin());
+        assert!(center.x.abs() < 1e-6 && center.y.abs() < 1e-6);
+    }
 }
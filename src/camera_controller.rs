@@ -0,0 +1,540 @@
+// things that drive a camera from player input. the main loop hands every sdl event to the scene's
+// controller and then lets it move the active camera once per frame
+
+use std::collections::HashMap;
+
+use nalgebra::{Point3, Vector3};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+use crate::{camera::Camera, collision::BoundingBox, raycast::Ray};
+
+// sdl mouse button masks as they show up in MouseMotion's mousestate
+const MIDDLE_BUTTON: u32 = 1 << 1;
+const RIGHT_BUTTON: u32 = 1 << 2;
+
+// what a controller may need from the scene besides the camera
+pub struct CameraContext {
+    // where the followed object is, if there is one
+    pub target: Option<Point3<f32>>,
+    // world space boxes the camera shouldn't end up behind
+    pub obstacles: Vec<BoundingBox>,
+    // where every object in the scene is, by name
+    pub objects: HashMap<String, Point3<f32>>,
+}
+
+impl CameraContext {
+    pub fn new() -> CameraContext {
+        CameraContext {
+            target: None,
+            obstacles: Vec::new(),
+            objects: HashMap::new(),
+        }
+    }
+}
+
+pub trait CameraController {
+    fn name(&self) -> &str;
+    fn handle_event(&mut self, event: &Event);
+    fn update(&mut self, camera: &mut Camera, context: &CameraContext, delta_time: f32);
+}
+
+// spins around a focus point: right drag rotates, middle drag pans and the wheel zooms
+pub struct OrbitController {
+    pub focus: Point3<f32>,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    // radians per pixel dragged
+    pub rotate_speed: f32,
+    // fraction of the distance moved per pixel dragged
+    pub pan_speed: f32,
+}
+
+impl OrbitController {
+    pub fn new(focus: Point3<f32>, distance: f32) -> OrbitController {
+        OrbitController {
+            focus,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            min_distance: 1.0,
+            max_distance: 5000.0,
+            rotate_speed: 0.005,
+            pan_speed: 0.002,
+        }
+    }
+
+    // picks up wherever the camera is looking from now
+    pub fn from_camera(camera: &Camera) -> OrbitController {
+        let offset = camera.position - camera.target();
+        let distance = offset.norm().max(1.0);
+        let mut orbit = OrbitController::new(camera.target(), distance);
+        orbit.yaw = offset.x.atan2(offset.z);
+        orbit.pitch = (offset.y / distance).clamp(-1.0, 1.0).asin();
+        orbit
+    }
+
+    fn offset(&self) -> Vector3<f32> {
+        Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        ) * self.distance
+    }
+}
+
+impl CameraController for OrbitController {
+    fn name(&self) -> &str {
+        "orbit"
+    }
+
+    fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::MouseMotion {
+                mousestate,
+                xrel,
+                yrel,
+                ..
+            } => {
+                let buttons = mousestate.to_sdl_state();
+                if buttons & RIGHT_BUTTON != 0 {
+                    let limit = 89.0_f32.to_radians();
+                    self.yaw -= *xrel as f32 * self.rotate_speed;
+                    self.pitch =
+                        (self.pitch + *yrel as f32 * self.rotate_speed).clamp(-limit, limit);
+                } else if buttons & MIDDLE_BUTTON != 0 {
+                    // drag the focus along the view plane so the scene follows the mouse
+                    let back = self.offset().normalize();
+                    let right = Vector3::y().cross(&back).normalize();
+                    let up = back.cross(&right);
+                    let scale = self.distance * self.pan_speed;
+                    self.focus += (-right * *xrel as f32 + up * *yrel as f32) * scale;
+                }
+            }
+            Event::MouseWheel { y, .. } => {
+                self.distance =
+                    (self.distance * 0.9_f32.powi(*y)).clamp(self.min_distance, self.max_distance);
+            }
+            _ => {}
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, _context: &CameraContext, _delta_time: f32) {
+        camera.position = self.focus + self.offset();
+        camera.look_at_target(self.focus);
+    }
+}
+
+// wasd to move, space and left ctrl for up and down, shift to go faster, right drag to look around
+pub struct FlyController {
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    // world units per second
+    pub speed: f32,
+    pub boost: f32,
+    pub look_speed: f32,
+    forward: bool,
+    back: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    boosting: bool,
+}
+
+impl FlyController {
+    pub fn new(position: Point3<f32>) -> FlyController {
+        FlyController {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 100.0,
+            boost: 4.0,
+            look_speed: 0.003,
+            forward: false,
+            back: false,
+            left: false,
+            right: false,
+            up: false,
+            down: false,
+            boosting: false,
+        }
+    }
+
+    pub fn from_camera(camera: &Camera) -> FlyController {
+        let direction = (camera.target() - camera.position).normalize();
+        let mut fly = FlyController::new(camera.position);
+        fly.yaw = direction.x.atan2(-direction.z);
+        fly.pitch = direction.y.clamp(-1.0, 1.0).asin();
+        fly
+    }
+
+    // yaw 0 looks down -z like a fresh camera
+    fn direction(&self) -> Vector3<f32> {
+        Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            -self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    fn set_key(&mut self, keycode: Keycode, pressed: bool) {
+        match keycode {
+            Keycode::W => self.forward = pressed,
+            Keycode::S => self.back = pressed,
+            Keycode::A => self.left = pressed,
+            Keycode::D => self.right = pressed,
+            Keycode::SPACE => self.up = pressed,
+            Keycode::LCTRL => self.down = pressed,
+            Keycode::LSHIFT => self.boosting = pressed,
+            _ => {}
+        }
+    }
+}
+
+impl CameraController for FlyController {
+    fn name(&self) -> &str {
+        "fly"
+    }
+
+    fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => self.set_key(*keycode, true),
+            Event::KeyUp {
+                keycode: Some(keycode),
+                ..
+            } => self.set_key(*keycode, false),
+            Event::MouseMotion {
+                mousestate,
+                xrel,
+                yrel,
+                ..
+            } if mousestate.to_sdl_state() & RIGHT_BUTTON != 0 => {
+                let limit = 89.0_f32.to_radians();
+                self.yaw += *xrel as f32 * self.look_speed;
+                self.pitch = (self.pitch - *yrel as f32 * self.look_speed).clamp(-limit, limit);
+            }
+            _ => {}
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, _context: &CameraContext, delta_time: f32) {
+        let direction = self.direction();
+        let right = direction.cross(&Vector3::y()).normalize();
+        let axis = |positive: bool, negative: bool| positive as i32 as f32 - negative as i32 as f32;
+
+        let movement = direction * axis(self.forward, self.back)
+            + right * axis(self.right, self.left)
+            + Vector3::y() * axis(self.up, self.down);
+        if movement.norm_squared() > 0.0 {
+            let speed = if self.boosting {
+                self.speed * self.boost
+            } else {
+                self.speed
+            };
+            self.position += movement.normalize() * speed * delta_time;
+        }
+
+        camera.position = self.position;
+        camera.look_at_target(self.position + direction);
+    }
+}
+
+// trails the context's target at an offset, easing towards it and pulling in in front of
+// anything that would block the view. the wheel scales the offset
+pub struct FollowController {
+    pub offset: Vector3<f32>,
+    // how quickly the camera catches up, higher is stiffer
+    pub damping: f32,
+    // gap kept between the camera and whatever blocks it
+    pub margin: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    zoom: f32,
+    position: Option<Point3<f32>>,
+}
+
+impl FollowController {
+    pub fn new(offset: Vector3<f32>) -> FollowController {
+        FollowController {
+            offset,
+            damping: 4.0,
+            margin: 5.0,
+            min_zoom: 0.25,
+            max_zoom: 4.0,
+            zoom: 1.0,
+            position: None,
+        }
+    }
+
+    // where the camera would like to be, moved up to the first obstacle between it and the target
+    fn desired_position(
+        &self,
+        target: Point3<f32>,
+        obstacles: &[BoundingBox],
+    ) -> (Point3<f32>, bool) {
+        let offset = self.offset * self.zoom;
+        let length = offset.norm();
+        if length <= f32::EPSILON {
+            return (target + offset, false);
+        }
+
+        let ray = Ray {
+            origin: target,
+            direction: offset / length,
+        };
+        let closest = obstacles
+            .iter()
+            .filter_map(|bbox| ray.intersect_bounding_box(bbox))
+            .map(|(distance, _)| distance)
+            .filter(|t| *t > 0.0 && *t < length)
+            .fold(f32::INFINITY, f32::min);
+
+        if closest.is_finite() {
+            (
+                target + ray.direction * (closest - self.margin).max(0.0),
+                true,
+            )
+        } else {
+            (target + offset, false)
+        }
+    }
+}
+
+impl CameraController for FollowController {
+    fn name(&self) -> &str {
+        "follow"
+    }
+
+    fn handle_event(&mut self, event: &Event) {
+        if let Event::MouseWheel { y, .. } = event {
+            self.zoom = (self.zoom * 0.9_f32.powi(*y)).clamp(self.min_zoom, self.max_zoom);
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, context: &CameraContext, delta_time: f32) {
+        let Some(target) = context.target else {
+            return;
+        };
+        let (desired, blocked) = self.desired_position(target, &context.obstacles);
+
+        // frame rate independent easing, but never let the view stay blocked while catching up
+        let current = self.position.unwrap_or(camera.position);
+        let position = if blocked && (desired - target).norm() < (current - target).norm() {
+            desired
+        } else {
+            let t = 1.0 - (-self.damping * delta_time).exp();
+            current + (desired - current) * t
+        };
+
+        self.position = Some(position);
+        camera.position = position;
+        camera.look_at_target(target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::keyboard::Mod;
+    use sdl2::mouse::{MouseState, MouseWheelDirection};
+
+    fn drag(buttons: u32, xrel: i32, yrel: i32) -> Event {
+        Event::MouseMotion {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mousestate: MouseState::from_sdl_state(buttons),
+            x: 0,
+            y: 0,
+            xrel,
+            yrel,
+        }
+    }
+
+    fn wheel(y: i32) -> Event {
+        Event::MouseWheel {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            x: 0,
+            y,
+            direction: MouseWheelDirection::Normal,
+            precise_x: 0.0,
+            precise_y: y as f32,
+            mouse_x: 0,
+            mouse_y: 0,
+        }
+    }
+
+    fn key(keycode: Keycode, pressed: bool) -> Event {
+        if pressed {
+            Event::KeyDown {
+                timestamp: 0,
+                window_id: 0,
+                keycode: Some(keycode),
+                scancode: None,
+                keymod: Mod::NOMOD,
+                repeat: false,
+            }
+        } else {
+            Event::KeyUp {
+                timestamp: 0,
+                window_id: 0,
+                keycode: Some(keycode),
+                scancode: None,
+                keymod: Mod::NOMOD,
+                repeat: false,
+            }
+        }
+    }
+
+    fn camera_at(position: Point3<f32>, target: Point3<f32>) -> Camera {
+        Camera::new(position, target, Vector3::y())
+    }
+
+    fn close(a: Point3<f32>, b: Point3<f32>) -> bool {
+        (a - b).norm() < 1e-3
+    }
+
+    #[test]
+    fn orbit_picks_up_the_camera_pose() {
+        let mut camera = camera_at(Point3::new(0.0, 300.0, 300.0), Point3::origin());
+        let mut orbit = OrbitController::from_camera(&camera);
+        orbit.update(&mut camera, &CameraContext::new(), 0.016);
+        assert!(close(camera.position, Point3::new(0.0, 300.0, 300.0)));
+    }
+
+    #[test]
+    fn orbit_rotates_zooms_and_pans() {
+        let mut camera = camera_at(Point3::new(0.0, 0.0, 100.0), Point3::origin());
+        let mut orbit = OrbitController::from_camera(&camera);
+        let context = CameraContext::new();
+
+        // dragging without a button does nothing
+        orbit.handle_event(&drag(0, 200, 0));
+        orbit.update(&mut camera, &context, 0.016);
+        assert!(close(camera.position, Point3::new(0.0, 0.0, 100.0)));
+
+        orbit.handle_event(&drag(RIGHT_BUTTON, -100, 0));
+        orbit.update(&mut camera, &context, 0.016);
+        assert!((camera.position.coords.norm() - 100.0).abs() < 1e-3);
+        assert!(camera.position.x > 40.0);
+
+        orbit.handle_event(&wheel(2));
+        orbit.update(&mut camera, &context, 0.016);
+        assert!((camera.position.coords.norm() - 81.0).abs() < 1e-3);
+
+        // pitch stops short of the pole so look_at never degenerates
+        orbit.handle_event(&drag(RIGHT_BUTTON, 0, 100000));
+        assert!(orbit.pitch < std::f32::consts::FRAC_PI_2);
+
+        let focus = orbit.focus;
+        orbit.handle_event(&drag(MIDDLE_BUTTON, 50, 0));
+        orbit.update(&mut camera, &context, 0.016);
+        assert!(orbit.focus != focus);
+        assert!(close(camera.target(), orbit.focus));
+        assert!(((camera.position - orbit.focus).norm() - 81.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fly_moves_while_keys_are_held() {
+        let mut camera = camera_at(Point3::origin(), Point3::new(0.0, 0.0, -1.0));
+        let mut fly = FlyController::from_camera(&camera);
+        let context = CameraContext::new();
+
+        fly.handle_event(&key(Keycode::W, true));
+        fly.update(&mut camera, &context, 0.5);
+        assert!(close(camera.position, Point3::new(0.0, 0.0, -50.0)));
+
+        fly.handle_event(&key(Keycode::W, false));
+        fly.handle_event(&key(Keycode::D, true));
+        fly.handle_event(&key(Keycode::LSHIFT, true));
+        fly.update(&mut camera, &context, 0.5);
+        assert!(close(camera.position, Point3::new(200.0, 0.0, -50.0)));
+
+        fly.handle_event(&key(Keycode::D, false));
+        fly.update(&mut camera, &context, 0.5);
+        assert!(close(camera.position, Point3::new(200.0, 0.0, -50.0)));
+    }
+
+    #[test]
+    fn fly_only_looks_around_with_the_right_button() {
+        let mut camera = camera_at(Point3::origin(), Point3::new(0.0, 0.0, -1.0));
+        let mut fly = FlyController::from_camera(&camera);
+        let context = CameraContext::new();
+
+        fly.handle_event(&drag(0, 500, 0));
+        fly.update(&mut camera, &context, 0.0);
+        assert!(close(camera.target(), Point3::new(0.0, 0.0, -1.0)));
+
+        // a quarter turn to the right ends up looking down +x
+        let quarter = (std::f32::consts::FRAC_PI_2 / fly.look_speed) as i32;
+        fly.handle_event(&drag(RIGHT_BUTTON, quarter, 0));
+        fly.update(&mut camera, &context, 0.0);
+        assert!((camera.target() - Point3::new(1.0, 0.0, 0.0)).norm() < 1e-2);
+    }
+
+    #[test]
+    fn follow_eases_towards_the_target() {
+        let mut camera = camera_at(Point3::origin(), Point3::new(0.0, 0.0, -1.0));
+        let mut follow = FollowController::new(Vector3::new(0.0, 10.0, 20.0));
+        let mut context = CameraContext::new();
+
+        // nothing to follow yet
+        follow.update(&mut camera, &context, 0.1);
+        assert!(close(camera.position, Point3::origin()));
+
+        context.target = Some(Point3::new(100.0, 0.0, 0.0));
+        follow.update(&mut camera, &context, 0.1);
+        let desired = Point3::new(100.0, 10.0, 20.0);
+        let first = (camera.position - desired).norm();
+        assert!(first > 1.0);
+        assert!(close(camera.target(), Point3::new(100.0, 0.0, 0.0)));
+
+        for _ in 0..100 {
+            follow.update(&mut camera, &context, 0.1);
+        }
+        assert!(close(camera.position, desired));
+
+        follow.handle_event(&wheel(-1));
+        for _ in 0..100 {
+            follow.update(&mut camera, &context, 0.1);
+        }
+        assert!(
+            ((camera.position - Point3::new(100.0, 0.0, 0.0)).norm() - 22.36068 / 0.9).abs() < 1e-2
+        );
+    }
+
+    #[test]
+    fn follow_pulls_in_front_of_obstacles() {
+        let mut camera = camera_at(Point3::new(0.0, 0.0, 40.0), Point3::origin());
+        let mut follow = FollowController::new(Vector3::new(0.0, 0.0, 40.0));
+        let mut context = CameraContext::new();
+        context.target = Some(Point3::origin());
+        // a wall between the target and where the camera wants to be
+        context
+            .obstacles
+            .push(BoundingBox::new(-50.0, 50.0, -50.0, 50.0, 20.0, 25.0));
+
+        follow.update(&mut camera, &context, 0.016);
+        assert!(close(camera.position, Point3::new(0.0, 0.0, 15.0)));
+
+        // walls behind the camera or the target don't count
+        context.obstacles[0] = BoundingBox::new(-50.0, 50.0, -50.0, 50.0, 60.0, 70.0);
+        context
+            .obstacles
+            .push(BoundingBox::new(-50.0, 50.0, -50.0, 50.0, -30.0, -20.0));
+        assert_eq!(
+            follow.desired_position(Point3::origin(), &context.obstacles),
+            (Point3::new(0.0, 0.0, 40.0), false)
+        );
+    }
+}
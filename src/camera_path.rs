@@ -0,0 +1,234 @@
+// scripted camera moves: the camera travels along a spline over a set time while looking at a
+// second spline, a fixed point, a named object, the scene's camera target or just ahead along
+// the path.
+// space pauses and resumes, the arrow keys scrub, home jumps back to the start
+
+use nalgebra::{Point3, Vector3};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+use crate::{
+    camera::Camera,
+    camera_controller::{CameraContext, CameraController},
+    spline::{Easing, Spline},
+};
+
+pub enum LookAt {
+    Spline(Spline),
+    Point(Point3<f32>),
+    // an object in the scene by name, looks along the path while it doesn't exist
+    Object(String),
+    // whatever object the scene names as its camera target
+    Target,
+    AlongPath,
+}
+
+pub struct CameraPath {
+    pub position: Spline,
+    pub look_at: LookAt,
+    // seconds from start to end
+    pub duration: f32,
+    pub easing: Easing,
+    pub looping: bool,
+    pub playing: bool,
+    // seconds moved per scrub key press
+    pub scrub_step: f32,
+    time: f32,
+}
+
+impl CameraPath {
+    pub fn new(position: Spline, look_at: LookAt, duration: f32) -> CameraPath {
+        CameraPath {
+            position,
+            look_at,
+            duration: duration.max(f32::EPSILON),
+            easing: Easing::EaseInOut,
+            looping: false,
+            playing: true,
+            scrub_step: 0.5,
+            time: 0.0,
+        }
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = if self.looping {
+            time.rem_euclid(self.duration)
+        } else {
+            time.clamp(0.0, self.duration)
+        };
+    }
+
+    pub fn finished(&self) -> bool {
+        !self.looping && self.time >= self.duration
+    }
+
+    pub fn advance(&mut self, delta_time: f32) {
+        if self.playing {
+            self.seek(self.time + delta_time);
+        }
+    }
+
+    // where the camera is and what it looks at, at the current time
+    pub fn sample(&self, context: &CameraContext) -> (Point3<f32>, Point3<f32>) {
+        let t = self.easing.apply(self.time / self.duration);
+        let position = Point3::from(self.position.point(t));
+        let along = position
+            + self
+                .position
+                .tangent(t)
+                .try_normalize(f32::EPSILON)
+                .unwrap_or(-Vector3::z());
+
+        let look_at = match &self.look_at {
+            LookAt::Spline(spline) => Point3::from(spline.point(t)),
+            LookAt::Point(point) => *point,
+            LookAt::Object(name) => context.objects.get(name).copied().unwrap_or(along),
+            LookAt::Target => context.target.unwrap_or(along),
+            LookAt::AlongPath => along,
+        };
+        (position, look_at)
+    }
+}
+
+impl CameraController for CameraPath {
+    fn name(&self) -> &str {
+        "path"
+    }
+
+    fn handle_event(&mut self, event: &Event) {
+        if let Event::KeyDown {
+            keycode: Some(keycode),
+            ..
+        } = event
+        {
+            match *keycode {
+                Keycode::SPACE => {
+                    // play again from the top once a one shot path has run out
+                    if self.finished() {
+                        self.time = 0.0;
+                        self.playing = true;
+                    } else {
+                        self.playing = !self.playing;
+                    }
+                }
+                Keycode::LEFT => self.seek(self.time - self.scrub_step),
+                Keycode::RIGHT => self.seek(self.time + self.scrub_step),
+                Keycode::HOME => self.seek(0.0),
+                _ => {}
+            }
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, context: &CameraContext, delta_time: f32) {
+        self.advance(delta_time);
+        let (position, look_at) = self.sample(context);
+        camera.position = position;
+        camera.look_at_target(look_at);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::keyboard::Mod;
+
+    fn line() -> Spline {
+        Spline::catmull_rom(vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(100.0, 0.0, 0.0),
+        ])
+    }
+
+    fn press(keycode: Keycode) -> Event {
+        Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(keycode),
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat: false,
+        }
+    }
+
+    #[test]
+    fn plays_over_its_duration_and_stops() {
+        let mut path = CameraPath::new(line(), LookAt::AlongPath, 4.0);
+        path.easing = Easing::Linear;
+
+        path.advance(1.0);
+        let (position, look_at) = path.sample(&CameraContext::new());
+        assert!((position.x - 25.0).abs() < 1e-3);
+        assert!((look_at - position - Vector3::x()).norm() < 1e-3);
+
+        path.advance(10.0);
+        assert!(path.finished());
+        assert!((path.sample(&CameraContext::new()).0.x - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn looping_wraps_around() {
+        let mut path = CameraPath::new(line(), LookAt::Point(Point3::origin()), 4.0);
+        path.easing = Easing::Linear;
+        path.looping = true;
+
+        path.advance(5.0);
+        assert!(!path.finished());
+        assert!((path.time - 1.0).abs() < 1e-5);
+        path.seek(-1.0);
+        assert!((path.time - 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn scrubbing_works_while_paused() {
+        let mut path = CameraPath::new(line(), LookAt::Target, 4.0);
+        path.handle_event(&press(Keycode::SPACE));
+        assert!(!path.playing);
+
+        path.advance(1.0);
+        assert_eq!(path.time, 0.0);
+
+        path.handle_event(&press(Keycode::RIGHT));
+        path.handle_event(&press(Keycode::RIGHT));
+        assert_eq!(path.time, 1.0);
+        path.handle_event(&press(Keycode::LEFT));
+        path.handle_event(&press(Keycode::LEFT));
+        path.handle_event(&press(Keycode::LEFT));
+        assert_eq!(path.time, 0.0);
+
+        path.handle_event(&press(Keycode::SPACE));
+        assert!(path.playing);
+    }
+
+    #[test]
+    fn looks_at_the_scene_target_a_named_object_or_a_second_spline() {
+        let mut camera = Camera::default();
+        let mut context = CameraContext::new();
+        context.target = Some(Point3::new(0.0, -50.0, 0.0));
+
+        let mut path = CameraPath::new(line(), LookAt::Target, 2.0);
+        path.update(&mut camera, &context, 1.0);
+        assert_eq!(camera.target(), Point3::new(0.0, -50.0, 0.0));
+        assert!((camera.position.x - 50.0).abs() < 1e-3);
+
+        let targets = Spline::catmull_rom(vec![
+            Vector3::new(0.0, 10.0, 0.0),
+            Vector3::new(0.0, 30.0, 0.0),
+        ]);
+        let mut path = CameraPath::new(line(), LookAt::Spline(targets), 2.0);
+        path.update(&mut camera, &context, 1.0);
+        assert!((camera.target() - Point3::new(0.0, 20.0, 0.0)).norm() < 1e-3);
+
+        context
+            .objects
+            .insert("tower".to_string(), Point3::new(10.0, 80.0, 0.0));
+        let mut path = CameraPath::new(line(), LookAt::Object("tower".to_string()), 2.0);
+        path.update(&mut camera, &context, 1.0);
+        assert_eq!(camera.target(), Point3::new(10.0, 80.0, 0.0));
+
+        // an object that isn't there falls back to looking ahead
+        let mut path = CameraPath::new(line(), LookAt::Object("gone".to_string()), 2.0);
+        path.easing = Easing::Linear;
+        path.update(&mut camera, &context, 1.0);
+        assert!((camera.target() - Point3::new(51.0, 0.0, 0.0)).norm() < 1e-2);
+    }
+}
@@ -1,6 +1,8 @@
-use nalgebra::Vector3;
+use nalgebra::{Matrix4, Vector3};
 
-#[derive(Debug)]
+use crate::render::Model;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BoundingBox {
     pub x_min: f32,
     pub x_max: f32,
@@ -28,6 +30,58 @@ impl BoundingBox {
             z_max,
         }
     }
+
+    pub fn min(&self) -> Vector3<f32> {
+        Vector3::new(self.x_min, self.y_min, self.z_min)
+    }
+
+    pub fn max(&self) -> Vector3<f32> {
+        Vector3::new(self.x_max, self.y_max, self.z_max)
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min() + self.max()) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max() - self.min()) * 0.5
+    }
+
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox::new(
+            self.x_min.min(other.x_min),
+            self.x_max.max(other.x_max),
+            self.y_min.min(other.y_min),
+            self.y_max.max(other.y_max),
+            self.z_min.min(other.z_min),
+            self.z_max.max(other.z_max),
+        )
+    }
+
+    // zero for empty (inverted) boxes, which is what the bvh build wants
+    pub fn surface_area(&self) -> f32 {
+        let size = (self.max() - self.min()).map(|e| e.max(0.0));
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    // the box that encloses this one after it has been transformed, e.g. by a model matrix
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> BoundingBox {
+        let center = matrix.transform_point(&self.center().into());
+        let half = self.half_extents();
+
+        // each world axis extent is the sum of the absolute projections of the local half extents
+        let linear = matrix.fixed_view::<3, 3>(0, 0).abs();
+        let extent = linear * half;
+
+        BoundingBox::new(
+            center.x - extent.x,
+            center.x + extent.x,
+            center.y - extent.y,
+            center.y + extent.y,
+            center.z - extent.z,
+            center.z + extent.z,
+        )
+    }
 }
 
 pub fn get_bounding_box(vertices: &[Vector3<f32>]) -> BoundingBox {
@@ -67,3 +121,1082 @@ pub fn get_bounding_box(vertices: &[Vector3<f32>]) -> BoundingBox {
 
     BoundingBox::new(x_min, x_max, y_min, y_max, z_min, z_max)
 }
+
+// the rest of this file is the narrow phase: solid shapes, whether pairs of them overlap and the
+// closest point on each to a query point. all pure math, points are plain vectors like the box's.
+// touching counts as overlapping
+
+impl BoundingBox {
+    // the world box of a model space box, see transform
+    pub fn transform_by(&self, model: &Model) -> BoundingBox {
+        self.transform(&model.get_model_matrix())
+    }
+
+    pub fn contains(&self, point: &Vector3<f32>) -> bool {
+        let (min, max) = (self.min(), self.max());
+        (0..3).all(|axis| point[axis] >= min[axis] && point[axis] <= max[axis])
+    }
+
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.x_min <= other.x_max
+            && self.x_max >= other.x_min
+            && self.y_min <= other.y_max
+            && self.y_max >= other.y_min
+            && self.z_min <= other.z_max
+            && self.z_max >= other.z_min
+    }
+
+    pub fn closest_point(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        point.sup(&self.min()).inf(&self.max())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl Sphere {
+    pub fn new(center: Vector3<f32>, radius: f32) -> Sphere {
+        Sphere { center, radius }
+    }
+
+    pub fn closest_point(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        let offset = point - self.center;
+        if offset.norm() <= self.radius {
+            *point
+        } else {
+            self.center + offset.normalize() * self.radius
+        }
+    }
+}
+
+// a box turned to any orientation. axes are unit length and at right angles to each other
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obb {
+    pub center: Vector3<f32>,
+    pub axes: [Vector3<f32>; 3],
+    pub half_extents: Vector3<f32>,
+}
+
+impl Obb {
+    pub fn new(center: Vector3<f32>, axes: [Vector3<f32>; 3], half_extents: Vector3<f32>) -> Obb {
+        Obb {
+            center,
+            axes,
+            half_extents,
+        }
+    }
+
+    // a model space box carried by a matrix, which unlike BoundingBox::transform stays tight under
+    // rotation. any shear in the matrix is squared off
+    pub fn from_bounding_box(bbox: &BoundingBox, matrix: &Matrix4<f32>) -> Obb {
+        let center = matrix.transform_point(&bbox.center().into()).coords;
+        let linear = matrix.fixed_view::<3, 3>(0, 0);
+        let half = bbox.half_extents();
+
+        let mut axes = [Vector3::x(), Vector3::y(), Vector3::z()];
+        let mut half_extents = Vector3::zeros();
+        for i in 0..3 {
+            let column = linear.column(i).into_owned();
+            half_extents[i] = half[i] * column.norm();
+
+            // gram-schmidt against the axes already done, falling back to the unit axis when
+            // the column is gone (zero scale) or lines up with an earlier one
+            let mut axis = column;
+            for done in axes.iter().take(i) {
+                axis -= done * axis.dot(done);
+            }
+            axes[i] = axis.try_normalize(1e-6).unwrap_or_else(|| {
+                let fallback = match i {
+                    0 => Vector3::x(),
+                    1 => Vector3::y(),
+                    _ => axes[0].cross(&axes[1]),
+                };
+                let mut fallback = fallback;
+                for done in axes.iter().take(i) {
+                    fallback -= done * fallback.dot(done);
+                }
+                fallback.normalize()
+            });
+        }
+
+        Obb::new(center, axes, half_extents)
+    }
+
+    pub fn from_model(bbox: &BoundingBox, model: &Model) -> Obb {
+        Obb::from_bounding_box(bbox, &model.get_model_matrix())
+    }
+
+    pub fn corners(&self) -> [Vector3<f32>; 8] {
+        let mut corners = [self.center; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            for axis in 0..3 {
+                let sign = if i & (1 << axis) == 0 { -1.0 } else { 1.0 };
+                *corner += self.axes[axis] * self.half_extents[axis] * sign;
+            }
+        }
+        corners
+    }
+
+    pub fn closest_point(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        let offset = point - self.center;
+        let mut closest = self.center;
+        for axis in 0..3 {
+            let extent = self.half_extents[axis];
+            closest += self.axes[axis] * offset.dot(&self.axes[axis]).clamp(-extent, extent);
+        }
+        closest
+    }
+
+    // half the box's thickness measured along a direction
+    fn projected_radius(&self, direction: &Vector3<f32>) -> f32 {
+        (0..3)
+            .map(|axis| self.half_extents[axis] * self.axes[axis].dot(direction).abs())
+            .sum()
+    }
+}
+
+impl From<BoundingBox> for Obb {
+    fn from(bbox: BoundingBox) -> Obb {
+        Obb::new(
+            bbox.center(),
+            [Vector3::x(), Vector3::y(), Vector3::z()],
+            bbox.half_extents(),
+        )
+    }
+}
+
+// every point within radius of the segment from start to end
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capsule {
+    pub start: Vector3<f32>,
+    pub end: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl Capsule {
+    pub fn new(start: Vector3<f32>, end: Vector3<f32>, radius: f32) -> Capsule {
+        Capsule { start, end, radius }
+    }
+
+    pub fn closest_point(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        let (on_segment, _) = closest_point_on_segment(point, &self.start, &self.end);
+        Sphere::new(on_segment, self.radius).closest_point(point)
+    }
+}
+
+// a half space: everything on the far side of the normal, up to distance along it from the origin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    pub fn new(normal: Vector3<f32>, distance: f32) -> Plane {
+        let length = normal.norm();
+        Plane {
+            normal: normal / length,
+            distance: distance / length,
+        }
+    }
+
+    pub fn from_point_normal(point: &Vector3<f32>, normal: &Vector3<f32>) -> Plane {
+        let normal = normal.normalize();
+        Plane {
+            normal,
+            distance: normal.dot(point),
+        }
+    }
+
+    // positive in front of the plane, negative inside the half space
+    pub fn signed_distance(&self, point: &Vector3<f32>) -> f32 {
+        self.normal.dot(point) - self.distance
+    }
+
+    pub fn closest_point(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        let distance = self.signed_distance(point);
+        if distance > 0.0 {
+            point - self.normal * distance
+        } else {
+            *point
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Triangle {
+    pub a: Vector3<f32>,
+    pub b: Vector3<f32>,
+    pub c: Vector3<f32>,
+}
+
+impl Triangle {
+    pub fn new(a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> Triangle {
+        Triangle { a, b, c }
+    }
+
+    // follows the winding, zero for triangles without area
+    pub fn normal(&self) -> Vector3<f32> {
+        (self.b - self.a)
+            .cross(&(self.c - self.a))
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::zeros)
+    }
+
+    fn points(&self) -> [Vector3<f32>; 3] {
+        [self.a, self.b, self.c]
+    }
+
+    fn edges(&self) -> [Vector3<f32>; 3] {
+        [self.b - self.a, self.c - self.b, self.a - self.c]
+    }
+
+    // works through the voronoi regions of the corners, then the edges, then the face
+    pub fn closest_point(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        let (a, b, c) = (self.a, self.b, self.c);
+        let ab = b - a;
+        let ac = c - a;
+
+        let ap = point - a;
+        let d1 = ab.dot(&ap);
+        let d2 = ac.dot(&ap);
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return a;
+        }
+
+        let bp = point - b;
+        let d3 = ab.dot(&bp);
+        let d4 = ac.dot(&bp);
+        if d3 >= 0.0 && d4 <= d3 {
+            return b;
+        }
+
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            return a + ab * (d1 / (d1 - d3));
+        }
+
+        let cp = point - c;
+        let d5 = ab.dot(&cp);
+        let d6 = ac.dot(&cp);
+        if d6 >= 0.0 && d5 <= d6 {
+            return c;
+        }
+
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            return a + ac * (d2 / (d2 - d6));
+        }
+
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+            return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+        }
+
+        let denominator = va + vb + vc;
+        if denominator.abs() < f32::EPSILON {
+            // no area, the closest of the edges will do
+            return [(a, b), (b, c), (c, a)]
+                .iter()
+                .map(|(start, end)| closest_point_on_segment(point, start, end).0)
+                .min_by(|x, y| {
+                    (x - point)
+                        .norm_squared()
+                        .total_cmp(&(y - point).norm_squared())
+                })
+                .unwrap();
+        }
+        a + ab * (vb / denominator) + ac * (vc / denominator)
+    }
+}
+
+// the closest point to point on the segment, and how far along the segment it is from 0 to 1
+pub fn closest_point_on_segment(
+    point: &Vector3<f32>,
+    start: &Vector3<f32>,
+    end: &Vector3<f32>,
+) -> (Vector3<f32>, f32) {
+    let direction = end - start;
+    let length_squared = direction.norm_squared();
+    if length_squared < f32::EPSILON {
+        return (*start, 0.0);
+    }
+
+    let t = ((point - start).dot(&direction) / length_squared).clamp(0.0, 1.0);
+    (start + direction * t, t)
+}
+
+// the closest pair of points between two segments, one on each
+pub fn closest_points_between_segments(
+    (p1, q1): (&Vector3<f32>, &Vector3<f32>),
+    (p2, q2): (&Vector3<f32>, &Vector3<f32>),
+) -> (Vector3<f32>, Vector3<f32>) {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.norm_squared();
+    let e = d2.norm_squared();
+    let f = d2.dot(&r);
+
+    // either or both segments may be a single point
+    if a < f32::EPSILON && e < f32::EPSILON {
+        return (*p1, *p2);
+    }
+    if a < f32::EPSILON {
+        return (*p1, closest_point_on_segment(p1, p2, q2).0);
+    }
+    let c = d1.dot(&r);
+    if e < f32::EPSILON {
+        return (closest_point_on_segment(p2, p1, q1).0, *p2);
+    }
+
+    let b = d1.dot(&d2);
+    let denominator = a * e - b * b;
+    // parallel segments get an arbitrary s, the clamping below sorts out t
+    let mut s = if denominator > f32::EPSILON {
+        ((b * f - c * e) / denominator).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let mut t = (b * s + f) / e;
+    if t < 0.0 {
+        t = 0.0;
+        s = (-c / a).clamp(0.0, 1.0);
+    } else if t > 1.0 {
+        t = 1.0;
+        s = ((b - c) / a).clamp(0.0, 1.0);
+    }
+
+    (p1 + d1 * s, p2 + d2 * t)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Sphere(Sphere),
+    Aabb(BoundingBox),
+    Obb(Obb),
+    Capsule(Capsule),
+    Plane(Plane),
+    Triangle(Triangle),
+}
+
+impl Shape {
+    // the point in or on the shape nearest to point, which is point itself when it's inside
+    pub fn closest_point(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        match self {
+            Shape::Sphere(sphere) => sphere.closest_point(point),
+            Shape::Aabb(bbox) => bbox.closest_point(point),
+            Shape::Obb(obb) => obb.closest_point(point),
+            Shape::Capsule(capsule) => capsule.closest_point(point),
+            Shape::Plane(plane) => plane.closest_point(point),
+            Shape::Triangle(triangle) => triangle.closest_point(point),
+        }
+    }
+
+    // zero inside the shape
+    pub fn distance(&self, point: &Vector3<f32>) -> f32 {
+        (self.closest_point(point) - point).norm()
+    }
+
+    // planes go on forever, their bounds are infinite
+    pub fn bounding_box(&self) -> BoundingBox {
+        let (min, max) = match self {
+            Shape::Sphere(sphere) => (
+                sphere.center.add_scalar(-sphere.radius),
+                sphere.center.add_scalar(sphere.radius),
+            ),
+            Shape::Aabb(bbox) => (bbox.min(), bbox.max()),
+            Shape::Obb(obb) => {
+                let extent =
+                    Vector3::from_fn(|axis, _| obb.projected_radius(&Vector3::ith(axis, 1.0)));
+                (obb.center - extent, obb.center + extent)
+            }
+            Shape::Capsule(capsule) => (
+                capsule.start.inf(&capsule.end).add_scalar(-capsule.radius),
+                capsule.start.sup(&capsule.end).add_scalar(capsule.radius),
+            ),
+            Shape::Plane(_) => (
+                Vector3::repeat(f32::NEG_INFINITY),
+                Vector3::repeat(f32::INFINITY),
+            ),
+            Shape::Triangle(triangle) => (
+                triangle.a.inf(&triangle.b).inf(&triangle.c),
+                triangle.a.sup(&triangle.b).sup(&triangle.c),
+            ),
+        };
+        BoundingBox::new(min.x, max.x, min.y, max.y, min.z, max.z)
+    }
+
+    pub fn intersects(&self, other: &Shape) -> bool {
+        match (self, other) {
+            // anything against a sphere or a capsule is a distance check against its centre
+            (Shape::Sphere(sphere), other) | (other, Shape::Sphere(sphere)) => {
+                other.distance(&sphere.center) <= sphere.radius + TOLERANCE
+            }
+            (Shape::Capsule(a), Shape::Capsule(b)) => {
+                let (on_a, on_b) =
+                    closest_points_between_segments((&a.start, &a.end), (&b.start, &b.end));
+                (on_a - on_b).norm() <= a.radius + b.radius + TOLERANCE
+            }
+            (Shape::Capsule(capsule), Shape::Plane(plane))
+            | (Shape::Plane(plane), Shape::Capsule(capsule)) => {
+                plane
+                    .signed_distance(&capsule.start)
+                    .min(plane.signed_distance(&capsule.end))
+                    <= capsule.radius + TOLERANCE
+            }
+            (Shape::Capsule(capsule), other) | (other, Shape::Capsule(capsule)) => {
+                segment_distance(other, &capsule.start, &capsule.end) <= capsule.radius + TOLERANCE
+            }
+
+            (Shape::Plane(a), Shape::Plane(b)) => {
+                // two half spaces only miss when they face away from each other
+                a.normal.dot(&b.normal) > -1.0 + 1e-6 || a.distance + b.distance >= -TOLERANCE
+            }
+            (Shape::Plane(plane), Shape::Triangle(triangle))
+            | (Shape::Triangle(triangle), Shape::Plane(plane)) => triangle
+                .points()
+                .iter()
+                .any(|point| plane.signed_distance(point) <= TOLERANCE),
+            (Shape::Plane(plane), Shape::Aabb(bbox)) | (Shape::Aabb(bbox), Shape::Plane(plane)) => {
+                let obb = Obb::from(*bbox);
+                plane.signed_distance(&obb.center)
+                    <= obb.projected_radius(&plane.normal) + TOLERANCE
+            }
+            (Shape::Plane(plane), Shape::Obb(obb)) | (Shape::Obb(obb), Shape::Plane(plane)) => {
+                plane.signed_distance(&obb.center)
+                    <= obb.projected_radius(&plane.normal) + TOLERANCE
+            }
+
+            (Shape::Aabb(a), Shape::Aabb(b)) => a.intersects(b),
+            (Shape::Aabb(a), Shape::Obb(b)) | (Shape::Obb(b), Shape::Aabb(a)) => {
+                obbs_intersect(&Obb::from(*a), b)
+            }
+            (Shape::Obb(a), Shape::Obb(b)) => obbs_intersect(a, b),
+            (Shape::Aabb(bbox), Shape::Triangle(triangle))
+            | (Shape::Triangle(triangle), Shape::Aabb(bbox)) => {
+                obb_intersects_triangle(&Obb::from(*bbox), triangle)
+            }
+            (Shape::Obb(obb), Shape::Triangle(triangle))
+            | (Shape::Triangle(triangle), Shape::Obb(obb)) => {
+                obb_intersects_triangle(obb, triangle)
+            }
+            (Shape::Triangle(a), Shape::Triangle(b)) => triangles_intersect(a, b),
+        }
+    }
+}
+
+// slack for float error, so shapes resting exactly on each other still count as touching
+const TOLERANCE: f32 = 1e-5;
+
+// how close the segment gets to a convex shape. the distance to a convex shape along a line is
+// convex itself, so a golden section search finds the minimum
+fn segment_distance(shape: &Shape, start: &Vector3<f32>, end: &Vector3<f32>) -> f32 {
+    let ratio = (5.0_f32.sqrt() - 1.0) * 0.5;
+    let at = |t: f32| shape.distance(&(start + (end - start) * t));
+
+    let (mut low, mut high) = (0.0_f32, 1.0_f32);
+    let mut left = high - (high - low) * ratio;
+    let mut right = low + (high - low) * ratio;
+    let (mut left_distance, mut right_distance) = (at(left), at(right));
+    for _ in 0..40 {
+        if left_distance <= right_distance {
+            high = right;
+            right = left;
+            right_distance = left_distance;
+            left = high - (high - low) * ratio;
+            left_distance = at(left);
+        } else {
+            low = left;
+            left = right;
+            left_distance = right_distance;
+            right = low + (high - low) * ratio;
+            right_distance = at(right);
+        }
+    }
+
+    left_distance.min(right_distance).min(at(0.0)).min(at(1.0))
+}
+
+// the separating axis test over point sets. axes too short to have a direction are skipped
+fn separated_on_any(
+    a: &[Vector3<f32>],
+    b: &[Vector3<f32>],
+    axes: impl IntoIterator<Item = Vector3<f32>>,
+) -> bool {
+    let project = |points: &[Vector3<f32>], axis: &Vector3<f32>| {
+        points
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), point| {
+                let distance = point.dot(axis);
+                (min.min(distance), max.max(distance))
+            })
+    };
+
+    axes.into_iter()
+        .filter_map(|axis| axis.try_normalize(1e-6))
+        .any(|axis| {
+            let (a_min, a_max) = project(a, &axis);
+            let (b_min, b_max) = project(b, &axis);
+            a_max < b_min - TOLERANCE || b_max < a_min - TOLERANCE
+        })
+}
+
+fn cross_axes(a: &[Vector3<f32>], b: &[Vector3<f32>]) -> Vec<Vector3<f32>> {
+    a.iter()
+        .flat_map(|a| b.iter().map(move |b| a.cross(b)))
+        .collect()
+}
+
+fn obbs_intersect(a: &Obb, b: &Obb) -> bool {
+    let axes = a
+        .axes
+        .iter()
+        .chain(b.axes.iter())
+        .copied()
+        .chain(cross_axes(&a.axes, &b.axes));
+    !separated_on_any(&a.corners(), &b.corners(), axes)
+}
+
+fn obb_intersects_triangle(obb: &Obb, triangle: &Triangle) -> bool {
+    let axes = obb
+        .axes
+        .iter()
+        .copied()
+        .chain([triangle.normal()])
+        .chain(cross_axes(&obb.axes, &triangle.edges()));
+    !separated_on_any(&obb.corners(), &triangle.points(), axes)
+}
+
+fn triangles_intersect(a: &Triangle, b: &Triangle) -> bool {
+    let (a_normal, b_normal) = (a.normal(), b.normal());
+    // the in plane edge normals only matter when the two are coplanar
+    let axes = [a_normal, b_normal]
+        .into_iter()
+        .chain(cross_axes(&a.edges(), &b.edges()))
+        .chain(a.edges().map(|edge| a_normal.cross(&edge)))
+        .chain(b.edges().map(|edge| b_normal.cross(&edge)));
+    !separated_on_any(&a.points(), &b.points(), axes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::UnitQuaternion;
+
+    #[test]
+    fn transform_translates_and_scales() {
+        let bb = BoundingBox::new(-1.0, 1.0, -2.0, 2.0, -3.0, 3.0);
+        let matrix = Matrix4::new_translation(&Vector3::new(10.0, 0.0, -5.0))
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(2.0, 1.0, 0.5));
+
+        let world = bb.transform(&matrix);
+        assert_eq!(world.min(), Vector3::new(8.0, -2.0, -6.5));
+        assert_eq!(world.max(), Vector3::new(12.0, 2.0, -3.5));
+    }
+
+    #[test]
+    fn transform_rotation_grows_to_enclose() {
+        let bb = BoundingBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+        let rotation =
+            UnitQuaternion::from_axis_angle(&Vector3::y_axis(), std::f32::consts::FRAC_PI_4);
+
+        let world = bb.transform(&rotation.to_homogeneous());
+        let expected = 2.0_f32.sqrt();
+        assert!((world.x_max - expected).abs() < 1e-5);
+        assert!((world.z_min + expected).abs() < 1e-5);
+        assert!((world.y_max - 1.0).abs() < 1e-5);
+    }
+
+    fn v(x: f32, y: f32, z: f32) -> Vector3<f32> {
+        Vector3::new(x, y, z)
+    }
+
+    fn close(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+        (a - b).norm() < 1e-4
+    }
+
+    fn unit_box() -> BoundingBox {
+        BoundingBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0)
+    }
+
+    fn unit_sphere() -> Shape {
+        Shape::Sphere(Sphere::new(Vector3::zeros(), 1.0))
+    }
+
+    // a unit cube turned 45 degrees about an axis
+    fn turned_box(center: Vector3<f32>, axis: nalgebra::Unit<Vector3<f32>>) -> Shape {
+        let rotation = UnitQuaternion::from_axis_angle(&axis, std::f32::consts::FRAC_PI_4);
+        let matrix = Matrix4::new_translation(&center) * rotation.to_homogeneous();
+        Shape::Obb(Obb::from_bounding_box(&unit_box(), &matrix))
+    }
+
+    fn big_triangle(z: f32) -> Shape {
+        Shape::Triangle(Triangle::new(
+            v(-10.0, -10.0, z),
+            v(10.0, -10.0, z),
+            v(0.0, 10.0, z),
+        ))
+    }
+
+    fn ground(height: f32) -> Shape {
+        Shape::Plane(Plane::from_point_normal(
+            &v(0.0, height, 0.0),
+            &Vector3::y(),
+        ))
+    }
+
+    // both orders have to agree
+    fn overlap(a: &Shape, b: &Shape, expected: bool) {
+        assert_eq!(a.intersects(b), expected, "{:?} against {:?}", a, b);
+        assert_eq!(b.intersects(a), expected, "{:?} against {:?}", b, a);
+    }
+
+    #[test]
+    fn boxes_contain_overlap_and_clamp() {
+        let bb = unit_box();
+        assert!(bb.contains(&v(1.0, 0.0, -1.0)));
+        assert!(!bb.contains(&v(1.01, 0.0, 0.0)));
+
+        assert!(bb.intersects(&BoundingBox::new(1.0, 2.0, 0.0, 1.0, 0.0, 1.0)));
+        assert!(!bb.intersects(&BoundingBox::new(1.1, 2.0, 0.0, 1.0, 0.0, 1.0)));
+        assert!(!bb.intersects(&BoundingBox::new(0.0, 1.0, 0.0, 1.0, 1.5, 2.0)));
+
+        assert_eq!(bb.closest_point(&v(0.5, 0.5, 0.5)), v(0.5, 0.5, 0.5));
+        assert_eq!(bb.closest_point(&v(3.0, 0.5, -4.0)), v(1.0, 0.5, -1.0));
+    }
+
+    #[test]
+    fn boxes_follow_models() {
+        let mut model = Model::new();
+        model.translate(v(5.0, 0.0, 0.0));
+        model.scale(v(2.0, 1.0, 1.0));
+
+        // the model matrix scales its translation too
+        let world = unit_box().transform_by(&model);
+        assert_eq!(world.min(), v(8.0, -1.0, -1.0));
+        assert_eq!(world.max(), v(12.0, 1.0, 1.0));
+
+        let obb = Obb::from_model(&unit_box(), &model);
+        assert!(close(obb.center, v(10.0, 0.0, 0.0)));
+        assert!(close(obb.half_extents, v(2.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn oriented_boxes_stay_tight_under_rotation() {
+        let Shape::Obb(obb) = turned_box(Vector3::zeros(), Vector3::z_axis()) else {
+            unreachable!();
+        };
+        assert!(close(obb.half_extents, v(1.0, 1.0, 1.0)));
+        for (i, a) in obb.axes.iter().enumerate() {
+            assert!((a.norm() - 1.0).abs() < 1e-5);
+            for b in &obb.axes[i + 1..] {
+                assert!(a.dot(b).abs() < 1e-5);
+            }
+        }
+
+        // a corner points along x, the sides don't
+        let corners = obb.corners();
+        let reach = corners
+            .iter()
+            .map(|c| c.x)
+            .fold(f32::NEG_INFINITY, f32::max);
+        assert!((reach - 2.0_f32.sqrt()).abs() < 1e-5);
+        assert!(close(
+            obb.closest_point(&v(5.0, 0.0, 0.0)),
+            v(2.0_f32.sqrt(), 0.0, 0.0)
+        ));
+        assert!(close(
+            obb.closest_point(&v(0.2, 0.1, 0.0)),
+            v(0.2, 0.1, 0.0)
+        ));
+
+        // flattening an axis to nothing still leaves three right angled axes
+        let flat = Obb::from_bounding_box(
+            &unit_box(),
+            &Matrix4::new_nonuniform_scaling(&v(1.0, 0.0, 1.0)),
+        );
+        assert_eq!(flat.half_extents.y, 0.0);
+        assert!(close(flat.axes[0].cross(&flat.axes[1]), flat.axes[2]));
+    }
+
+    #[test]
+    fn closest_points_on_spheres_capsules_and_planes() {
+        let sphere = Sphere::new(v(0.0, 0.0, 0.0), 2.0);
+        assert!(close(
+            sphere.closest_point(&v(0.0, 10.0, 0.0)),
+            v(0.0, 2.0, 0.0)
+        ));
+        assert_eq!(sphere.closest_point(&v(0.5, 0.0, 0.0)), v(0.5, 0.0, 0.0));
+
+        let capsule = Capsule::new(v(0.0, 0.0, 0.0), v(0.0, 4.0, 0.0), 1.0);
+        assert!(close(
+            capsule.closest_point(&v(3.0, 2.0, 0.0)),
+            v(1.0, 2.0, 0.0)
+        ));
+        assert!(close(
+            capsule.closest_point(&v(0.0, 9.0, 0.0)),
+            v(0.0, 5.0, 0.0)
+        ));
+        // with no length a capsule is a sphere
+        let ball = Capsule::new(v(1.0, 1.0, 1.0), v(1.0, 1.0, 1.0), 1.0);
+        assert!(close(
+            ball.closest_point(&v(1.0, 1.0, 5.0)),
+            v(1.0, 1.0, 2.0)
+        ));
+
+        let plane = Plane::new(v(0.0, 2.0, 0.0), 2.0);
+        assert_eq!(plane.normal, Vector3::y());
+        assert_eq!(plane.distance, 1.0);
+        assert_eq!(plane.signed_distance(&v(4.0, 3.0, 0.0)), 2.0);
+        assert_eq!(plane.closest_point(&v(4.0, 3.0, 0.0)), v(4.0, 1.0, 0.0));
+        // below the surface is inside
+        assert_eq!(plane.closest_point(&v(4.0, -3.0, 0.0)), v(4.0, -3.0, 0.0));
+    }
+
+    #[test]
+    fn closest_points_on_triangles_cover_every_region() {
+        let triangle = Triangle::new(v(0.0, 0.0, 0.0), v(4.0, 0.0, 0.0), v(0.0, 4.0, 0.0));
+        assert_eq!(triangle.normal(), Vector3::z());
+
+        // the face, the three corners and the three edges
+        assert!(close(
+            triangle.closest_point(&v(1.0, 1.0, 5.0)),
+            v(1.0, 1.0, 0.0)
+        ));
+        assert!(close(
+            triangle.closest_point(&v(-1.0, -1.0, 1.0)),
+            v(0.0, 0.0, 0.0)
+        ));
+        assert!(close(
+            triangle.closest_point(&v(6.0, -1.0, 0.0)),
+            v(4.0, 0.0, 0.0)
+        ));
+        assert!(close(
+            triangle.closest_point(&v(-1.0, 6.0, 0.0)),
+            v(0.0, 4.0, 0.0)
+        ));
+        assert!(close(
+            triangle.closest_point(&v(2.0, -3.0, 0.0)),
+            v(2.0, 0.0, 0.0)
+        ));
+        assert!(close(
+            triangle.closest_point(&v(-3.0, 2.0, 0.0)),
+            v(0.0, 2.0, 0.0)
+        ));
+        assert!(close(
+            triangle.closest_point(&v(3.0, 3.0, 0.0)),
+            v(2.0, 2.0, 0.0)
+        ));
+
+        // a triangle folded onto a line has no normal but still has closest points
+        let line = Triangle::new(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0), v(2.0, 0.0, 0.0));
+        assert_eq!(line.normal(), Vector3::zeros());
+        assert!(close(
+            line.closest_point(&v(1.5, 1.0, 0.0)),
+            v(1.5, 0.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn closest_points_between_segments_and_points() {
+        let (p, t) =
+            closest_point_on_segment(&v(5.0, 1.0, 0.0), &v(0.0, 0.0, 0.0), &v(2.0, 0.0, 0.0));
+        assert_eq!((p, t), (v(2.0, 0.0, 0.0), 1.0));
+
+        // skew lines crossing over each other
+        let (a, b) = closest_points_between_segments(
+            (&v(-1.0, 0.0, 0.0), &v(1.0, 0.0, 0.0)),
+            (&v(0.0, 1.0, -1.0), &v(0.0, 1.0, 1.0)),
+        );
+        assert!(close(a, v(0.0, 0.0, 0.0)) && close(b, v(0.0, 1.0, 0.0)));
+
+        // past each other's ends
+        let (a, b) = closest_points_between_segments(
+            (&v(0.0, 0.0, 0.0), &v(1.0, 0.0, 0.0)),
+            (&v(3.0, 1.0, 0.0), &v(3.0, 5.0, 0.0)),
+        );
+        assert!(close(a, v(1.0, 0.0, 0.0)) && close(b, v(3.0, 1.0, 0.0)));
+
+        // parallel, any pair along the overlap is as close as it gets
+        let (a, b) = closest_points_between_segments(
+            (&v(0.0, 0.0, 0.0), &v(4.0, 0.0, 0.0)),
+            (&v(2.0, 3.0, 0.0), &v(6.0, 3.0, 0.0)),
+        );
+        assert!(((a - b).norm() - 3.0).abs() < 1e-5);
+
+        // points for segments
+        let (a, b) = closest_points_between_segments(
+            (&v(1.0, 1.0, 0.0), &v(1.0, 1.0, 0.0)),
+            (&v(0.0, 0.0, 0.0), &v(4.0, 0.0, 0.0)),
+        );
+        assert!(close(a, v(1.0, 1.0, 0.0)) && close(b, v(1.0, 0.0, 0.0)));
+        let (a, b) = closest_points_between_segments(
+            (&v(1.0, 1.0, 0.0), &v(1.0, 1.0, 0.0)),
+            (&v(2.0, 2.0, 0.0), &v(2.0, 2.0, 0.0)),
+        );
+        assert!(close(a, v(1.0, 1.0, 0.0)) && close(b, v(2.0, 2.0, 0.0)));
+    }
+
+    #[test]
+    fn spheres_against_everything() {
+        let sphere = unit_sphere();
+        overlap(
+            &sphere,
+            &Shape::Sphere(Sphere::new(v(1.9, 0.0, 0.0), 1.0)),
+            true,
+        );
+        overlap(
+            &sphere,
+            &Shape::Sphere(Sphere::new(v(2.1, 0.0, 0.0), 1.0)),
+            false,
+        );
+
+        overlap(
+            &sphere,
+            &Shape::Aabb(BoundingBox::new(0.9, 3.0, -1.0, 1.0, -1.0, 1.0)),
+            true,
+        );
+        overlap(
+            &sphere,
+            &Shape::Aabb(BoundingBox::new(1.5, 3.0, -1.0, 1.0, -1.0, 1.0)),
+            false,
+        );
+        // every axis overlaps on its own but the corner is out of reach
+        overlap(
+            &sphere,
+            &Shape::Aabb(BoundingBox::new(0.8, 2.0, 0.8, 2.0, 0.8, 2.0)),
+            false,
+        );
+
+        overlap(
+            &sphere,
+            &turned_box(v(2.3, 0.0, 0.0), Vector3::z_axis()),
+            true,
+        );
+        overlap(
+            &sphere,
+            &turned_box(v(2.5, 0.0, 0.0), Vector3::z_axis()),
+            false,
+        );
+        // the turned box's world bounds would reach the sphere, the box itself doesn't
+        let diagonal = turned_box(v(1.9, 1.9, 0.0), Vector3::z_axis());
+        overlap(&sphere, &Shape::Aabb(diagonal.bounding_box()), true);
+        overlap(&sphere, &diagonal, false);
+
+        let pole =
+            |radius| Shape::Capsule(Capsule::new(v(2.0, -5.0, 0.0), v(2.0, 5.0, 0.0), radius));
+        overlap(&sphere, &pole(1.1), true);
+        overlap(&sphere, &pole(0.5), false);
+
+        overlap(&sphere, &ground(-0.5), true);
+        overlap(&sphere, &ground(-2.0), false);
+        // entirely under the surface is still inside the half space
+        overlap(&sphere, &ground(5.0), true);
+
+        overlap(&sphere, &big_triangle(0.9), true);
+        overlap(&sphere, &big_triangle(1.1), false);
+    }
+
+    #[test]
+    fn axis_aligned_boxes_against_everything() {
+        let bb = Shape::Aabb(unit_box());
+        overlap(
+            &bb,
+            &Shape::Aabb(BoundingBox::new(1.0, 2.0, 1.0, 2.0, 1.0, 2.0)),
+            true,
+        );
+        overlap(
+            &bb,
+            &Shape::Aabb(BoundingBox::new(1.0, 2.0, 1.1, 2.0, 1.0, 2.0)),
+            false,
+        );
+
+        overlap(&bb, &turned_box(v(2.3, 0.0, 0.0), Vector3::z_axis()), true);
+        overlap(&bb, &turned_box(v(2.5, 0.0, 0.0), Vector3::z_axis()), false);
+        // only the turned box's own face normal separates these
+        overlap(&bb, &turned_box(v(1.6, 1.6, 0.0), Vector3::z_axis()), true);
+        overlap(&bb, &turned_box(v(1.9, 1.9, 0.0), Vector3::z_axis()), false);
+
+        let post =
+            |radius| Shape::Capsule(Capsule::new(v(2.0, 2.0, -5.0), v(2.0, 2.0, 5.0), radius));
+        overlap(&bb, &post(1.5), true);
+        overlap(&bb, &post(1.0), false);
+        let through = Shape::Capsule(Capsule::new(v(-5.0, 0.0, 0.0), v(5.0, 0.0, 0.0), 0.1));
+        overlap(&bb, &through, true);
+
+        overlap(&bb, &ground(-0.8), true);
+        overlap(&bb, &ground(-1.2), false);
+        let tilted =
+            |at: f32| Shape::Plane(Plane::from_point_normal(&v(at, at, 0.0), &v(1.0, 1.0, 0.0)));
+        overlap(&bb, &tilted(-0.9), true);
+        overlap(&bb, &tilted(-1.2), false);
+
+        let across_corner = |d: f32| {
+            Shape::Triangle(Triangle::new(
+                v(d, 0.0, 0.0),
+                v(0.0, d, 0.0),
+                v(0.0, 0.0, d),
+            ))
+        };
+        overlap(&bb, &across_corner(2.8), true);
+        overlap(&bb, &across_corner(3.2), false);
+        // in a plane through the box, but off to the side
+        let beside = Shape::Triangle(Triangle::new(
+            v(5.0, 5.0, 0.0),
+            v(6.0, 5.0, 0.0),
+            v(5.0, 6.0, 0.0),
+        ));
+        overlap(&bb, &beside, false);
+    }
+
+    #[test]
+    fn oriented_boxes_against_everything() {
+        let obb = turned_box(Vector3::zeros(), Vector3::z_axis());
+        // corners meet at twice the half diagonal
+        overlap(&obb, &turned_box(v(2.7, 0.0, 0.0), Vector3::z_axis()), true);
+        overlap(
+            &obb,
+            &turned_box(v(2.9, 0.0, 0.0), Vector3::z_axis()),
+            false,
+        );
+        // turned about different axes
+        overlap(&obb, &turned_box(v(2.4, 0.0, 2.4), Vector3::x_axis()), true);
+        overlap(
+            &obb,
+            &turned_box(v(2.4, 0.0, 2.6), Vector3::x_axis()),
+            false,
+        );
+
+        let post = |x| Shape::Capsule(Capsule::new(v(x, 0.0, -5.0), v(x, 0.0, 5.0), 0.2));
+        overlap(&obb, &post(1.3), true);
+        overlap(&obb, &post(1.7), false);
+
+        overlap(&obb, &ground(-1.3), true);
+        overlap(&obb, &ground(-1.5), false);
+
+        let tipped = |z| turned_box(v(0.0, 0.0, z), Vector3::x_axis());
+        overlap(&tipped(1.3), &big_triangle(0.0), true);
+        overlap(&tipped(1.6), &big_triangle(0.0), false);
+    }
+
+    #[test]
+    fn capsules_against_capsules_planes_and_triangles() {
+        let rod =
+            |radius| Shape::Capsule(Capsule::new(v(-5.0, 0.0, 0.0), v(5.0, 0.0, 0.0), radius));
+        let skew =
+            |radius| Shape::Capsule(Capsule::new(v(0.0, 1.5, -5.0), v(0.0, 1.5, 5.0), radius));
+        overlap(&rod(0.8), &skew(0.8), true);
+        overlap(&rod(0.7), &skew(0.7), false);
+        let parallel =
+            |radius| Shape::Capsule(Capsule::new(v(-2.0, 1.0, 0.0), v(8.0, 1.0, 0.0), radius));
+        overlap(&rod(0.6), &parallel(0.6), true);
+        overlap(&rod(0.3), &parallel(0.3), false);
+
+        let dangling =
+            |bottom| Shape::Capsule(Capsule::new(v(0.0, 2.0, 0.0), v(0.0, bottom, 0.0), 0.5));
+        overlap(&dangling(0.3), &ground(0.0), true);
+        overlap(&dangling(0.6), &ground(0.0), false);
+
+        let hovering =
+            |radius| Shape::Capsule(Capsule::new(v(-1.0, 0.0, 0.4), v(1.0, 0.0, 0.4), radius));
+        overlap(&hovering(0.5), &big_triangle(0.0), true);
+        overlap(&hovering(0.3), &big_triangle(0.0), false);
+        // straight through the face with barely any radius
+        let spear = Shape::Capsule(Capsule::new(v(0.0, 0.0, -3.0), v(0.0, 0.0, 3.0), 0.01));
+        overlap(&spear, &big_triangle(0.0), true);
+        // alongside an edge
+        let alongside = |radius| {
+            Shape::Capsule(Capsule::new(
+                v(-20.0, -11.0, 0.0),
+                v(20.0, -11.0, 0.0),
+                radius,
+            ))
+        };
+        overlap(&alongside(1.2), &big_triangle(0.0), true);
+        overlap(&alongside(0.8), &big_triangle(0.0), false);
+    }
+
+    #[test]
+    fn planes_and_triangles() {
+        let floor = ground(0.0);
+        // half spaces facing away only meet if they overlap
+        let ceiling = |height: f32| Shape::Plane(Plane::new(-Vector3::y(), -height));
+        overlap(&floor, &ceiling(1.0), false);
+        overlap(&floor, &ceiling(-1.0), true);
+        overlap(
+            &floor,
+            &Shape::Plane(Plane::new(Vector3::x(), -100.0)),
+            true,
+        );
+
+        let tilted = |low: f32| {
+            Shape::Triangle(Triangle::new(
+                v(0.0, low, 0.0),
+                v(1.0, low + 2.0, 0.0),
+                v(0.0, low + 2.0, 1.0),
+            ))
+        };
+        overlap(&floor, &tilted(-0.5), true);
+        overlap(&floor, &tilted(0.5), false);
+
+        let flat = |z| {
+            Shape::Triangle(Triangle::new(
+                v(0.0, 0.0, z),
+                v(2.0, 0.0, z),
+                v(0.0, 2.0, z),
+            ))
+        };
+        let standing = Shape::Triangle(Triangle::new(
+            v(0.5, 0.5, -1.0),
+            v(0.5, 0.5, 1.0),
+            v(0.5, 3.0, 0.0),
+        ));
+        overlap(&flat(0.0), &standing, true);
+        overlap(&flat(0.0), &flat(0.5), false);
+        // the planes cross but the triangles pass each other by
+        let aside = Shape::Triangle(Triangle::new(
+            v(5.0, 0.5, -1.0),
+            v(5.0, 0.5, 1.0),
+            v(5.0, 3.0, 0.0),
+        ));
+        overlap(&flat(0.0), &aside, false);
+
+        // in the same plane, only the edge normals can tell these apart
+        let shifted = |dx| {
+            Shape::Triangle(Triangle::new(
+                v(dx, 0.0, 0.0),
+                v(dx + 2.0, 0.0, 0.0),
+                v(dx, 2.0, 0.0),
+            ))
+        };
+        overlap(&flat(0.0), &shifted(1.5), true);
+        let flipped = Shape::Triangle(Triangle::new(
+            v(2.0, 2.0, 0.0),
+            v(1.2, 2.0, 0.0),
+            v(2.0, 1.2, 0.0),
+        ));
+        overlap(&flat(0.0), &flipped, false);
+    }
+
+    #[test]
+    fn shape_bounds_and_distances() {
+        assert_eq!(unit_sphere().bounding_box(), unit_box());
+        let capsule = Shape::Capsule(Capsule::new(v(0.0, 0.0, 0.0), v(0.0, 4.0, 0.0), 1.0));
+        assert_eq!(
+            capsule.bounding_box(),
+            BoundingBox::new(-1.0, 1.0, -1.0, 5.0, -1.0, 1.0)
+        );
+        let bounds = turned_box(Vector3::zeros(), Vector3::z_axis()).bounding_box();
+        assert!((bounds.x_max - 2.0_f32.sqrt()).abs() < 1e-5);
+        assert!((bounds.z_max - 1.0).abs() < 1e-5);
+        assert_eq!(ground(0.0).bounding_box().y_max, f32::INFINITY);
+
+        assert_eq!(capsule.distance(&v(3.0, 2.0, 0.0)), 2.0);
+        assert_eq!(capsule.distance(&v(0.5, 2.0, 0.0)), 0.0);
+        assert_eq!(big_triangle(0.0).distance(&v(0.0, 0.0, -3.0)), 3.0);
+    }
+}
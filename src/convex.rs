@@ -0,0 +1,1018 @@
+// general convex collision. anything with a support function (the furthest point in a direction)
+// can be tested with gjk, and when two shapes overlap epa finds how deep and in which direction.
+// hulls built from mesh vertices with quickhull also get a manifold of up to four contact points,
+// which is what a physics step needs to rest one box on another without rocking
+
+use nalgebra::{Matrix4, Vector3};
+
+use crate::{
+    collision::{BoundingBox, Capsule, Obb, Sphere, Triangle},
+    obj::ObjData,
+};
+
+pub trait Support {
+    // the point of the shape furthest along direction, which doesn't have to be normalized
+    fn support(&self, direction: &Vector3<f32>) -> Vector3<f32>;
+}
+
+impl Support for Sphere {
+    fn support(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        self.center
+            + direction
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::x)
+                * self.radius
+    }
+}
+
+impl Support for BoundingBox {
+    fn support(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        let (min, max) = (self.min(), self.max());
+        Vector3::from_fn(|axis, _| {
+            if direction[axis] >= 0.0 {
+                max[axis]
+            } else {
+                min[axis]
+            }
+        })
+    }
+}
+
+impl Support for Obb {
+    fn support(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        let mut point = self.center;
+        for axis in 0..3 {
+            let sign = self.axes[axis].dot(direction).signum();
+            point += self.axes[axis] * self.half_extents[axis] * sign;
+        }
+        point
+    }
+}
+
+impl Support for Capsule {
+    fn support(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        let end = if self.end.dot(direction) > self.start.dot(direction) {
+            self.end
+        } else {
+            self.start
+        };
+        Sphere::new(end, self.radius).support(direction)
+    }
+}
+
+impl Support for Triangle {
+    fn support(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        furthest(&[self.a, self.b, self.c], direction)
+    }
+}
+
+fn furthest(points: &[Vector3<f32>], direction: &Vector3<f32>) -> Vector3<f32> {
+    *points
+        .iter()
+        .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+        .expect("no points to support")
+}
+
+#[derive(Debug, Clone)]
+pub struct ConvexHull {
+    pub vertices: Vec<Vector3<f32>>,
+    // counter clockwise seen from outside
+    pub faces: Vec<[usize; 3]>,
+}
+
+impl Support for ConvexHull {
+    fn support(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        furthest(&self.vertices, direction)
+    }
+}
+
+// a face still being built, with the points that are in front of it and so not yet inside
+struct HullFace {
+    indices: [usize; 3],
+    normal: Vector3<f32>,
+    offset: f32,
+    outside: Vec<usize>,
+}
+
+impl HullFace {
+    fn new(points: &[Vector3<f32>], indices: [usize; 3]) -> HullFace {
+        let [a, b, c] = indices.map(|i| points[i]);
+        let normal = (b - a).cross(&(c - a)).normalize();
+        HullFace {
+            indices,
+            normal,
+            offset: normal.dot(&a),
+            outside: Vec::new(),
+        }
+    }
+
+    fn distance(&self, point: &Vector3<f32>) -> f32 {
+        self.normal.dot(point) - self.offset
+    }
+}
+
+impl ConvexHull {
+    // quickhull. None when the points don't span a volume: fewer than four of them, or all on one
+    // plane or line
+    pub fn from_points(points: &[Vector3<f32>]) -> Option<ConvexHull> {
+        let scale = points.iter().map(|p| p.abs().max()).fold(1.0_f32, f32::max);
+        let epsilon = scale * 1e-5;
+
+        // the starting tetrahedron from extreme points, each one as far as possible from the last
+        let extremes: Vec<usize> = (0..3)
+            .flat_map(|axis| {
+                let by_axis = |a: &usize, b: &usize| points[*a][axis].total_cmp(&points[*b][axis]);
+                [
+                    (0..points.len()).min_by(by_axis),
+                    (0..points.len()).max_by(by_axis),
+                ]
+            })
+            .flatten()
+            .collect();
+        let (a, b) = extremes
+            .iter()
+            .flat_map(|a| extremes.iter().map(move |b| (*a, *b)))
+            .max_by(|x, y| {
+                let length = |(a, b): &(usize, usize)| (points[*a] - points[*b]).norm_squared();
+                length(x).total_cmp(&length(y))
+            })?;
+        let line = points[b] - points[a];
+        if line.norm() < epsilon {
+            return None;
+        }
+
+        let c = (0..points.len()).max_by(|x, y| {
+            let distance = |i: &usize| line.cross(&(points[*i] - points[a])).norm_squared();
+            distance(x).total_cmp(&distance(y))
+        })?;
+        let normal = line.cross(&(points[c] - points[a]));
+        if normal.norm() < epsilon * line.norm() {
+            return None;
+        }
+        let normal = normal.normalize();
+
+        let d = (0..points.len()).max_by(|x, y| {
+            let distance = |i: &usize| normal.dot(&(points[*i] - points[a])).abs();
+            distance(x).total_cmp(&distance(y))
+        })?;
+        if normal.dot(&(points[d] - points[a])).abs() < epsilon {
+            return None;
+        }
+
+        // wind the first faces so they face away from the middle of the tetrahedron
+        let center = (points[a] + points[b] + points[c] + points[d]) / 4.0;
+        let mut faces: Vec<HullFace> = [[a, b, c], [a, c, d], [a, d, b], [b, d, c]]
+            .into_iter()
+            .map(|[x, y, z]| {
+                let face = HullFace::new(points, [x, y, z]);
+                if face.distance(&center) > 0.0 {
+                    HullFace::new(points, [x, z, y])
+                } else {
+                    face
+                }
+            })
+            .collect();
+
+        for (i, point) in points.iter().enumerate() {
+            if let Some(face) = faces.iter_mut().find(|f| f.distance(point) > epsilon) {
+                face.outside.push(i);
+            }
+        }
+
+        while let Some(current) = faces.iter().position(|f| !f.outside.is_empty()) {
+            let eye = *faces[current]
+                .outside
+                .iter()
+                .max_by(|x, y| {
+                    let face = &faces[current];
+                    face.distance(&points[**x])
+                        .total_cmp(&face.distance(&points[**y]))
+                })
+                .unwrap();
+
+            let (visible, kept): (Vec<HullFace>, Vec<HullFace>) = faces
+                .into_iter()
+                .partition(|f| f.distance(&points[eye]) > epsilon);
+            faces = kept;
+
+            // the horizon is every edge of the visible faces that only one of them has, its
+            // direction taken from the visible face keeps the new faces wound outwards
+            let edges: Vec<(usize, usize)> = visible
+                .iter()
+                .flat_map(|f| {
+                    let [x, y, z] = f.indices;
+                    [(x, y), (y, z), (z, x)]
+                })
+                .collect();
+            let horizon = edges
+                .iter()
+                .filter(|(x, y)| !edges.contains(&(*y, *x)))
+                .copied();
+
+            let first_new = faces.len();
+            for (x, y) in horizon {
+                faces.push(HullFace::new(points, [x, y, eye]));
+            }
+
+            // points that were outside the removed faces either sit outside a new one or are
+            // now inside the hull
+            for i in visible.into_iter().flat_map(|f| f.outside) {
+                if i == eye {
+                    continue;
+                }
+                if let Some(face) = faces[first_new..]
+                    .iter_mut()
+                    .find(|f| f.distance(&points[i]) > epsilon)
+                {
+                    face.outside.push(i);
+                }
+            }
+        }
+
+        // keep only the points the faces use
+        let mut remap = vec![usize::MAX; points.len()];
+        let mut vertices = Vec::new();
+        let faces = faces
+            .iter()
+            .map(|face| {
+                face.indices.map(|i| {
+                    if remap[i] == usize::MAX {
+                        remap[i] = vertices.len();
+                        vertices.push(points[i]);
+                    }
+                    remap[i]
+                })
+            })
+            .collect();
+
+        Some(ConvexHull { vertices, faces })
+    }
+
+    pub fn from_obj(data: &ObjData) -> Option<ConvexHull> {
+        ConvexHull::from_points(&data.vertices)
+    }
+
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> ConvexHull {
+        ConvexHull {
+            vertices: self
+                .vertices
+                .iter()
+                .map(|v| matrix.transform_point(&(*v).into()).coords)
+                .collect(),
+            faces: self.faces.clone(),
+        }
+    }
+
+    pub fn face_normal(&self, face: usize) -> Vector3<f32> {
+        let [a, b, c] = self.faces[face].map(|i| self.vertices[i]);
+        (b - a).cross(&(c - a)).normalize()
+    }
+
+    // the whole flat side of the hull facing most along direction, which may be several
+    // triangles, as a polygon wound counter clockwise around its normal
+    fn face_polygon(&self, direction: &Vector3<f32>) -> (Vector3<f32>, Vec<Vector3<f32>>) {
+        let normal = (0..self.faces.len())
+            .map(|face| self.face_normal(face))
+            .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+            .expect("hull without faces");
+
+        let top = self
+            .vertices
+            .iter()
+            .map(|v| v.dot(&normal))
+            .fold(f32::NEG_INFINITY, f32::max);
+        let epsilon = 1e-4 * (1.0 + top.abs());
+        let polygon: Vec<Vector3<f32>> = self
+            .vertices
+            .iter()
+            .filter(|v| v.dot(&normal) >= top - epsilon)
+            .copied()
+            .collect();
+
+        (normal, wind(polygon, &normal))
+    }
+}
+
+// sorts the points of a flat convex polygon counter clockwise around normal
+fn wind(mut polygon: Vec<Vector3<f32>>, normal: &Vector3<f32>) -> Vec<Vector3<f32>> {
+    let center = polygon.iter().sum::<Vector3<f32>>() / polygon.len() as f32;
+    let u = normal
+        .cross(&Vector3::x())
+        .try_normalize(1e-3)
+        .unwrap_or_else(|| normal.cross(&Vector3::y()).normalize());
+    let w = normal.cross(&u);
+    let angle = |p: &Vector3<f32>| (p - center).dot(&w).atan2((p - center).dot(&u));
+    polygon.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+    polygon
+}
+
+// a point of the minkowski difference a - b, remembering where on each shape it came from
+#[derive(Debug, Clone, Copy)]
+struct SupportPoint {
+    point: Vector3<f32>,
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+}
+
+fn support(a: &impl Support, b: &impl Support, direction: &Vector3<f32>) -> SupportPoint {
+    let on_a = a.support(direction);
+    let on_b = b.support(&-direction);
+    SupportPoint {
+        point: on_a - on_b,
+        a: on_a,
+        b: on_b,
+    }
+}
+
+const MAX_ITERATIONS: usize = 64;
+// below this a simplex is taken to be flat
+const MIN_VOLUME: f32 = 1e-9;
+
+// the simplex gjk found around the origin when the shapes overlap
+pub struct Simplex {
+    points: Vec<SupportPoint>,
+}
+
+// gjk. the shapes overlap when their minkowski difference holds the origin, which is searched
+// for by growing a simplex towards it
+pub fn gjk(a: &impl Support, b: &impl Support) -> Option<Simplex> {
+    let mut direction = Vector3::x();
+    let mut points = vec![support(a, b, &direction)];
+    direction = -points[0].point;
+
+    for _ in 0..MAX_ITERATIONS {
+        if direction.norm_squared() < 1e-12 {
+            // the origin is right on the simplex, the shapes are touching
+            return Some(Simplex { points });
+        }
+
+        let next = support(a, b, &direction);
+        if next.point.dot(&direction) < 0.0 {
+            return None;
+        }
+        points.push(next);
+
+        if let Some(towards) = nearest_simplex(&mut points) {
+            direction = towards;
+        } else {
+            return Some(Simplex { points });
+        }
+    }
+
+    // out of iterations only happens for shapes grazing each other, call that touching
+    Some(Simplex { points })
+}
+
+pub fn intersects(a: &impl Support, b: &impl Support) -> bool {
+    gjk(a, b).is_some()
+}
+
+// cuts the simplex down to the part nearest the origin and returns the direction to search in
+// next, or None when the simplex holds the origin. the newest point is always last
+fn nearest_simplex(points: &mut Vec<SupportPoint>) -> Option<Vector3<f32>> {
+    let same = |a: &Vector3<f32>, b: &Vector3<f32>| a.dot(b) > 0.0;
+
+    match points.len() {
+        2 => {
+            let (a, b) = (points[1].point, points[0].point);
+            let (ab, ao) = (b - a, -a);
+            if same(&ab, &ao) {
+                Some(ab.cross(&ao).cross(&ab))
+            } else {
+                *points = vec![points[1]];
+                Some(ao)
+            }
+        }
+        3 => {
+            let (a, b, c) = (points[2].point, points[1].point, points[0].point);
+            let (ab, ac, ao) = (b - a, c - a, -a);
+            let abc = ab.cross(&ac);
+
+            if same(&abc.cross(&ac), &ao) {
+                if same(&ac, &ao) {
+                    *points = vec![points[0], points[2]];
+                    Some(ac.cross(&ao).cross(&ac))
+                } else {
+                    *points = vec![points[1], points[2]];
+                    nearest_simplex(points)
+                }
+            } else if same(&ab.cross(&abc), &ao) {
+                *points = vec![points[1], points[2]];
+                nearest_simplex(points)
+            } else if same(&abc, &ao) {
+                Some(abc)
+            } else {
+                // keep the triangle wound so its normal faces the origin
+                *points = vec![points[1], points[0], points[2]];
+                Some(-abc)
+            }
+        }
+        _ => {
+            let (a, b, c, d) = (
+                points[3].point,
+                points[2].point,
+                points[1].point,
+                points[0].point,
+            );
+            let (ab, ac, ad, ao) = (b - a, c - a, d - a, -a);
+            let abc = ab.cross(&ac);
+            let acd = ac.cross(&ad);
+            let adb = ad.cross(&ab);
+
+            // the triangle sides that face the origin, the base bcd can't as a was found past it
+            if same(&abc, &ao) {
+                *points = vec![points[1], points[2], points[3]];
+                nearest_simplex(points)
+            } else if same(&acd, &ao) {
+                *points = vec![points[0], points[1], points[3]];
+                nearest_simplex(points)
+            } else if same(&adb, &ao) {
+                *points = vec![points[2], points[0], points[3]];
+                nearest_simplex(points)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Penetration {
+    // unit length, pointing from a into b. moving b along it by depth pulls them apart
+    pub normal: Vector3<f32>,
+    pub depth: f32,
+    // the deepest points of each shape inside the other
+    pub point_a: Vector3<f32>,
+    pub point_b: Vector3<f32>,
+}
+
+// epa. blows the gjk simplex up into a polytope inside the minkowski difference until the face
+// nearest the origin is on its surface. that face gives the shortest way out. nothing when the
+// difference is flat, like two triangles in the same plane, and there is no way out to find
+pub fn epa(a: &impl Support, b: &impl Support, simplex: Simplex) -> Option<Penetration> {
+    let mut vertices = simplex.points;
+    fill_simplex(a, b, &mut vertices)?;
+
+    let winding = |vertices: &[SupportPoint], [i, j, k]: [usize; 3]| {
+        let normal =
+            (vertices[j].point - vertices[i].point).cross(&(vertices[k].point - vertices[i].point));
+        normal.try_normalize(1e-12).unwrap_or_else(Vector3::zeros)
+    };
+
+    // a tetrahedron wound outwards
+    let center = vertices.iter().map(|v| v.point).sum::<Vector3<f32>>() / 4.0;
+    let mut faces: Vec<[usize; 3]> = [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]]
+        .into_iter()
+        .map(|[i, j, k]| {
+            if winding(&vertices, [i, j, k]).dot(&(vertices[i].point - center)) < 0.0 {
+                [i, k, j]
+            } else {
+                [i, j, k]
+            }
+        })
+        .collect();
+
+    let mut closest = (Vector3::x(), 0.0, faces[0]);
+    for _ in 0..MAX_ITERATIONS {
+        closest = faces
+            .iter()
+            .map(|face| {
+                let normal = winding(&vertices, *face);
+                (normal, normal.dot(&vertices[face[0]].point), *face)
+            })
+            .filter(|(normal, _, _)| *normal != Vector3::zeros())
+            .min_by(|x, y| x.1.total_cmp(&y.1))?;
+        let (normal, distance, _) = closest;
+
+        let next = support(a, b, &normal);
+        if next.point.dot(&normal) - distance < 1e-4 {
+            break;
+        }
+
+        // replace every face the new point can see with a fan from the horizon to it
+        let new = vertices.len();
+        vertices.push(next);
+        let (visible, kept): (Vec<[usize; 3]>, Vec<[usize; 3]>) =
+            faces.into_iter().partition(|face| {
+                winding(&vertices, *face).dot(&(next.point - vertices[face[0]].point)) > 0.0
+            });
+        let edges: Vec<(usize, usize)> = visible
+            .iter()
+            .flat_map(|[i, j, k]| [(*i, *j), (*j, *k), (*k, *i)])
+            .collect();
+        faces = kept;
+        for (i, j) in edges.iter().filter(|(i, j)| !edges.contains(&(*j, *i))) {
+            faces.push([*i, *j, new]);
+        }
+    }
+
+    // where the origin projects onto the closest face, in that face's barycentrics, says where on
+    // each shape the contact is
+    let (normal, depth, [i, j, k]) = closest;
+    let weights = barycentric(
+        &(normal * depth),
+        &vertices[i].point,
+        &vertices[j].point,
+        &vertices[k].point,
+    );
+    let blend = |pick: fn(&SupportPoint) -> Vector3<f32>| {
+        pick(&vertices[i]) * weights.x
+            + pick(&vertices[j]) * weights.y
+            + pick(&vertices[k]) * weights.z
+    };
+
+    Some(Penetration {
+        normal,
+        depth: depth.max(0.0),
+        point_a: blend(|v| v.a),
+        point_b: blend(|v| v.b),
+    })
+}
+
+// gjk can finish on a point, line or triangle when the shapes only touch, epa wants a tetrahedron.
+// nothing when every direction leaves it flat
+fn fill_simplex(a: &impl Support, b: &impl Support, points: &mut Vec<SupportPoint>) -> Option<()> {
+    let directions = [
+        Vector3::x(),
+        -Vector3::x(),
+        Vector3::y(),
+        -Vector3::y(),
+        Vector3::z(),
+        -Vector3::z(),
+    ];
+
+    // gjk also stops on a flat tetrahedron when the origin lies in its plane, that one is grown
+    // again from its first three points
+    if points.len() == 4 && simplex_volume(&points[..3], &points[3].point) < MIN_VOLUME {
+        points.pop();
+    }
+
+    while points.len() < 4 {
+        let candidates: Vec<Vector3<f32>> = match points.len() {
+            1 => directions.to_vec(),
+            2 => {
+                let line = points[1].point - points[0].point;
+                directions
+                    .iter()
+                    .map(|d| line.cross(d))
+                    .flat_map(|d| [d, -d])
+                    .collect()
+            }
+            _ => {
+                let normal =
+                    (points[1].point - points[0].point).cross(&(points[2].point - points[0].point));
+                vec![normal, -normal]
+            }
+        };
+
+        let next = candidates
+            .iter()
+            .filter(|d| d.norm_squared() > 1e-12)
+            .map(|d| support(a, b, d))
+            .max_by(|x, y| {
+                simplex_volume(points, &x.point).total_cmp(&simplex_volume(points, &y.point))
+            })?;
+        if simplex_volume(points, &next.point) < MIN_VOLUME {
+            return None;
+        }
+        points.push(next);
+    }
+    Some(())
+}
+
+// length, area or volume (times a constant) the simplex would have with next added
+fn simplex_volume(points: &[SupportPoint], next: &Vector3<f32>) -> f32 {
+    match points.len() {
+        1 => (next - points[0].point).norm(),
+        2 => (points[1].point - points[0].point)
+            .cross(&(next - points[0].point))
+            .norm(),
+        _ => (points[1].point - points[0].point)
+            .cross(&(points[2].point - points[0].point))
+            .dot(&(next - points[0].point))
+            .abs(),
+    }
+}
+
+// weights of a, b and c for a point on their plane
+fn barycentric(
+    point: &Vector3<f32>,
+    a: &Vector3<f32>,
+    b: &Vector3<f32>,
+    c: &Vector3<f32>,
+) -> Vector3<f32> {
+    let (v0, v1, v2) = (b - a, c - a, point - a);
+    let (d00, d01, d11) = (v0.dot(&v0), v0.dot(&v1), v1.dot(&v1));
+    let (d20, d21) = (v2.dot(&v0), v2.dot(&v1));
+    let denominator = d00 * d11 - d01 * d01;
+    if denominator.abs() < 1e-12 {
+        return Vector3::new(1.0, 0.0, 0.0);
+    }
+
+    let v = (d11 * d20 - d01 * d21) / denominator;
+    let w = (d00 * d21 - d01 * d20) / denominator;
+    Vector3::new(1.0 - v - w, v, w)
+}
+
+// the overlap of two shapes if they overlap at all
+pub fn penetration(a: &impl Support, b: &impl Support) -> Option<Penetration> {
+    gjk(a, b).and_then(|simplex| epa(a, b, simplex))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactPoint {
+    // on the surface of whichever hull's face was clipped
+    pub position: Vector3<f32>,
+    pub depth: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactManifold {
+    // from a into b, the same as Penetration::normal
+    pub normal: Vector3<f32>,
+    // at most four
+    pub points: Vec<ContactPoint>,
+}
+
+// the contact points between two overlapping hulls. the face of one hull that best matches the
+// epa normal is the reference, the other hull's face turned most against it is clipped to the
+// reference face's sides, and whatever of it is under the reference face touches
+pub fn contact_manifold(a: &ConvexHull, b: &ConvexHull) -> Option<ContactManifold> {
+    let penetration = penetration(a, b)?;
+    let normal = penetration.normal;
+
+    let (a_normal, a_face) = a.face_polygon(&normal);
+    let (b_normal, b_face) = b.face_polygon(&-normal);
+    let (reference_normal, reference, incident) =
+        if b_normal.dot(&-normal) > a_normal.dot(&normal) + 1e-3 {
+            (b_normal, b_face, a_face)
+        } else {
+            (a_normal, a_face, b_face)
+        };
+
+    let mut clipped = incident;
+    for i in 0..reference.len() {
+        let start = reference[i];
+        let end = reference[(i + 1) % reference.len()];
+        let outward = (end - start).cross(&reference_normal);
+        clipped = clip(&clipped, &start, &outward);
+        if clipped.is_empty() {
+            break;
+        }
+    }
+
+    let mut points: Vec<ContactPoint> = clipped
+        .into_iter()
+        .map(|position| ContactPoint {
+            position,
+            depth: -(position - reference[0]).dot(&reference_normal),
+        })
+        .filter(|contact| contact.depth >= -1e-4)
+        .collect();
+
+    // edges and corners meeting at an angle can clip to nothing, epa's point still stands
+    if points.is_empty() {
+        points.push(ContactPoint {
+            position: (penetration.point_a + penetration.point_b) * 0.5,
+            depth: penetration.depth,
+        });
+    }
+
+    Some(ContactManifold {
+        normal,
+        points: reduce(points),
+    })
+}
+
+// sutherland-hodgman against one plane, keeping what is behind outward
+fn clip(
+    polygon: &[Vector3<f32>],
+    point: &Vector3<f32>,
+    outward: &Vector3<f32>,
+) -> Vec<Vector3<f32>> {
+    let mut result = Vec::new();
+    for i in 0..polygon.len() {
+        let current = polygon[i];
+        let next = polygon[(i + 1) % polygon.len()];
+        let (d_current, d_next) = ((current - point).dot(outward), (next - point).dot(outward));
+
+        if d_current <= 0.0 {
+            result.push(current);
+        }
+        if (d_current < 0.0 && d_next > 0.0) || (d_current > 0.0 && d_next < 0.0) {
+            result.push(current + (next - current) * (d_current / (d_current - d_next)));
+        }
+    }
+    result
+}
+
+// keeps the deepest point and the three that spread the manifold out the most
+pub(crate) fn reduce(mut points: Vec<ContactPoint>) -> Vec<ContactPoint> {
+    if points.len() <= 4 {
+        return points;
+    }
+
+    let take = |points: &mut Vec<ContactPoint>, score: &dyn Fn(&ContactPoint) -> f32| {
+        let best = (0..points.len())
+            .max_by(|x, y| score(&points[*x]).total_cmp(&score(&points[*y])))
+            .unwrap();
+        points.swap_remove(best)
+    };
+
+    let first = take(&mut points, &|p| p.depth);
+    let second = take(&mut points, &|p| {
+        (p.position - first.position).norm_squared()
+    });
+    let third = take(&mut points, &|p| {
+        (second.position - first.position)
+            .cross(&(p.position - first.position))
+            .norm_squared()
+    });
+    // the fourth as far outside the triangle as possible, on either side
+    let fourth = take(&mut points, &|p| {
+        [(first, second), (second, third), (third, first)]
+            .iter()
+            .map(|(x, y)| {
+                (y.position - x.position)
+                    .cross(&(p.position - x.position))
+                    .norm()
+            })
+            .sum()
+    });
+
+    vec![first, second, third, fourth]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{collision::Shape, obj, primitives};
+    use nalgebra::UnitQuaternion;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn v(x: f32, y: f32, z: f32) -> Vector3<f32> {
+        Vector3::new(x, y, z)
+    }
+
+    fn cube_hull(center: Vector3<f32>, half: f32) -> ConvexHull {
+        let data = primitives::cube(half * 2.0);
+        ConvexHull::from_obj(&data)
+            .unwrap()
+            .transform(&Matrix4::new_translation(&center))
+    }
+
+    // every point is inside or on every face
+    fn contains_all(hull: &ConvexHull, points: &[Vector3<f32>]) -> bool {
+        (0..hull.faces.len()).all(|face| {
+            let normal = hull.face_normal(face);
+            let offset = normal.dot(&hull.vertices[hull.faces[face][0]]);
+            points.iter().all(|p| normal.dot(p) - offset < 1e-3)
+        })
+    }
+
+    // a closed triangulated surface with V vertices has 2V - 4 faces
+    fn is_closed(hull: &ConvexHull) -> bool {
+        hull.faces.len() == 2 * hull.vertices.len() - 4
+    }
+
+    #[test]
+    fn hull_of_a_cube_with_points_inside() {
+        let mut points = primitives::cube(2.0).vertices;
+        points.extend([v(0.0, 0.0, 0.0), v(0.5, -0.5, 0.2), v(-0.9, 0.9, 0.9)]);
+        let hull = ConvexHull::from_points(&points).unwrap();
+
+        assert_eq!(hull.vertices.len(), 8);
+        assert_eq!(hull.faces.len(), 12);
+        assert!(contains_all(&hull, &points));
+
+        // faces are wound to look away from the middle
+        for face in 0..hull.faces.len() {
+            let corner = hull.vertices[hull.faces[face][0]];
+            assert!(hull.face_normal(face).dot(&corner) > 0.0);
+        }
+    }
+
+    #[test]
+    fn hulls_of_random_clouds_and_meshes_enclose_them() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let cloud: Vec<Vector3<f32>> = (0..400)
+            .map(|_| {
+                v(
+                    rng.gen_range(-5.0..5.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-3.0..3.0),
+                )
+            })
+            .collect();
+        let hull = ConvexHull::from_points(&cloud).unwrap();
+        assert!(contains_all(&hull, &cloud));
+        assert!(is_closed(&hull));
+        assert!(hull.vertices.len() < cloud.len());
+
+        let sphere = primitives::uv_sphere(2.0, 16, 8);
+        let hull = ConvexHull::from_obj(&sphere).unwrap();
+        assert!(contains_all(&hull, &sphere.vertices));
+        assert!(is_closed(&hull));
+
+        let teapot = obj::parse_obj("resources/teapot.obj").expect("unable to load teapot");
+        let hull = ConvexHull::from_obj(&teapot).unwrap();
+        assert!(contains_all(&hull, &teapot.vertices));
+        assert!(is_closed(&hull));
+    }
+
+    #[test]
+    fn flat_point_sets_have_no_hull() {
+        assert!(ConvexHull::from_points(&[]).is_none());
+        assert!(ConvexHull::from_points(&[v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)]).is_none());
+        let line: Vec<Vector3<f32>> = (0..10).map(|i| v(i as f32, i as f32, 0.0)).collect();
+        assert!(ConvexHull::from_points(&line).is_none());
+        assert!(ConvexHull::from_obj(&primitives::plane(4.0, 4.0, 3)).is_none());
+    }
+
+    #[test]
+    fn supports_are_the_furthest_points() {
+        let sphere = Sphere::new(v(1.0, 0.0, 0.0), 2.0);
+        assert_eq!(sphere.support(&v(0.0, 5.0, 0.0)), v(1.0, 2.0, 0.0));
+
+        let bbox = BoundingBox::new(-1.0, 1.0, -2.0, 2.0, -3.0, 3.0);
+        assert_eq!(bbox.support(&v(1.0, -1.0, 1.0)), v(1.0, -2.0, 3.0));
+
+        let obb = Obb::from(bbox);
+        assert_eq!(obb.support(&v(-1.0, 1.0, -1.0)), v(-1.0, 2.0, -3.0));
+
+        let capsule = Capsule::new(v(0.0, 0.0, 0.0), v(0.0, 4.0, 0.0), 1.0);
+        assert_eq!(capsule.support(&v(0.0, 1.0, 0.0)), v(0.0, 5.0, 0.0));
+        assert_eq!(capsule.support(&v(0.0, -1.0, 0.0)), v(0.0, -1.0, 0.0));
+
+        let triangle = Triangle::new(v(0.0, 0.0, 0.0), v(3.0, 0.0, 0.0), v(0.0, 2.0, 0.0));
+        assert_eq!(triangle.support(&v(0.0, 1.0, 0.0)), v(0.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn gjk_agrees_with_the_primitive_tests() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let point = |rng: &mut StdRng| {
+            v(
+                rng.gen_range(-3.0..3.0),
+                rng.gen_range(-3.0..3.0),
+                rng.gen_range(-3.0..3.0),
+            )
+        };
+
+        let (mut hits, mut misses) = (0, 0);
+        for _ in 0..300 {
+            let sphere = Sphere::new(point(&mut rng), rng.gen_range(0.2..1.5));
+            let rotation = UnitQuaternion::from_euler_angles(
+                rng.gen_range(0.0..3.0),
+                rng.gen_range(0.0..3.0),
+                rng.gen_range(0.0..3.0),
+            );
+            let matrix = Matrix4::new_translation(&point(&mut rng)) * rotation.to_homogeneous();
+            let obb =
+                Obb::from_bounding_box(&BoundingBox::new(-1.0, 1.0, -0.5, 0.5, -0.7, 0.7), &matrix);
+            let capsule = Capsule::new(point(&mut rng), point(&mut rng), rng.gen_range(0.1..0.8));
+
+            // well clear of touching, where either answer is fine
+            let clear = |a: &Shape, b: &Shape| {
+                let grown = |shape: &Shape, by: f32| match shape {
+                    Shape::Sphere(s) => Shape::Sphere(Sphere::new(s.center, s.radius + by)),
+                    Shape::Capsule(c) => {
+                        Shape::Capsule(Capsule::new(c.start, c.end, c.radius + by))
+                    }
+                    other => *other,
+                };
+                grown(a, 0.01).intersects(b) == grown(a, -0.01).intersects(b)
+            };
+
+            let pairs = [
+                (
+                    Shape::Sphere(sphere),
+                    Shape::Obb(obb),
+                    intersects(&sphere, &obb),
+                ),
+                (
+                    Shape::Capsule(capsule),
+                    Shape::Obb(obb),
+                    intersects(&capsule, &obb),
+                ),
+                (
+                    Shape::Sphere(sphere),
+                    Shape::Capsule(capsule),
+                    intersects(&sphere, &capsule),
+                ),
+            ];
+            for (a, b, gjk_says) in pairs {
+                if !clear(&a, &b) {
+                    continue;
+                }
+                assert_eq!(a.intersects(&b), gjk_says, "{:?} against {:?}", a, b);
+                if gjk_says {
+                    hits += 1;
+                } else {
+                    misses += 1;
+                }
+            }
+        }
+        assert!(hits > 50 && misses > 50);
+    }
+
+    #[test]
+    fn epa_finds_depth_and_direction() {
+        let a = cube_hull(v(0.0, 0.0, 0.0), 1.0);
+        let b = cube_hull(v(1.8, 0.3, -0.2), 1.0);
+        let found = penetration(&a, &b).unwrap();
+        assert!((found.normal - Vector3::x()).norm() < 1e-3);
+        assert!((found.depth - 0.2).abs() < 1e-3);
+
+        let a = Sphere::new(v(0.0, 0.0, 0.0), 1.0);
+        let b = Sphere::new(v(0.0, 1.5, 0.0), 1.0);
+        let found = penetration(&a, &b).unwrap();
+        assert!((found.normal - Vector3::y()).norm() < 0.05);
+        assert!((found.depth - 0.5).abs() < 0.01);
+        assert!((found.point_a - v(0.0, 1.0, 0.0)).norm() < 0.05);
+        assert!((found.point_b - v(0.0, 0.5, 0.0)).norm() < 0.05);
+
+        // shapes that only just touch still give a tetrahedron to work from
+        let b = cube_hull(v(2.0, 0.0, 0.0), 1.0);
+        let found = penetration(&a_cube(), &b).expect("touching cubes overlap");
+        assert!(found.depth < 1e-3);
+        assert!(penetration(&a_cube(), &cube_hull(v(2.1, 0.0, 0.0), 1.0)).is_none());
+    }
+
+    #[test]
+    fn flat_differences_have_no_penetration() {
+        // two triangles in one plane overlap, but their difference has no inside to push out of
+        let a = Triangle::new(v(0.0, 0.0, 0.0), v(2.0, 0.0, 0.0), v(0.0, 2.0, 0.0));
+        let b = Triangle::new(v(0.5, 0.5, 0.0), v(2.5, 0.5, 0.0), v(0.5, 2.5, 0.0));
+        assert!(penetration(&a, &b).is_none());
+
+        // a triangle through a cube still has depth
+        let through = Triangle::new(v(-2.0, -2.0, 0.5), v(2.0, -2.0, 0.5), v(0.0, 2.0, 0.5));
+        let found = penetration(&a_cube(), &through).expect("the triangle cuts the cube");
+        assert!(found.depth > 0.0);
+    }
+
+    fn a_cube() -> ConvexHull {
+        cube_hull(v(0.0, 0.0, 0.0), 1.0)
+    }
+
+    #[test]
+    fn resting_boxes_touch_at_four_corners() {
+        let ground = cube_hull(v(0.0, 0.0, 0.0), 2.0);
+        let resting = cube_hull(v(0.5, 2.9, -0.3), 1.0);
+        let manifold = contact_manifold(&ground, &resting).unwrap();
+
+        assert!((manifold.normal - Vector3::y()).norm() < 1e-3);
+        assert_eq!(manifold.points.len(), 4);
+        for contact in &manifold.points {
+            assert!((contact.depth - 0.1).abs() < 1e-3);
+            assert!(
+                (contact.position.y - 1.9).abs() < 1e-3 || (contact.position.y - 2.0).abs() < 1e-3
+            );
+        }
+
+        // hanging over the edge, only the part over the ground touches
+        let overhanging = cube_hull(v(2.5, 2.9, 0.0), 1.0);
+        let manifold = contact_manifold(&ground, &overhanging).unwrap();
+        assert_eq!(manifold.points.len(), 4);
+        assert!(manifold.points.iter().all(|c| c.position.x <= 2.0 + 1e-3));
+    }
+
+    #[test]
+    fn a_box_on_its_edge_touches_along_a_line() {
+        let ground = cube_hull(v(0.0, 0.0, 0.0), 2.0);
+        let rotation =
+            UnitQuaternion::from_axis_angle(&Vector3::z_axis(), std::f32::consts::FRAC_PI_4);
+        let tipped = a_cube().transform(
+            &(Matrix4::new_translation(&v(0.0, 2.0 + 2.0_f32.sqrt() - 0.05, 0.0))
+                * rotation.to_homogeneous()),
+        );
+        let manifold = contact_manifold(&ground, &tipped).unwrap();
+
+        assert!((manifold.normal - Vector3::y()).norm() < 1e-2);
+        assert_eq!(manifold.points.len(), 2);
+        for contact in &manifold.points {
+            assert!((contact.depth - 0.05).abs() < 1e-3);
+            assert!(contact.position.x.abs() < 1e-3);
+        }
+        assert!(contact_manifold(&ground, &cube_hull(v(0.0, 5.0, 0.0), 1.0)).is_none());
+    }
+
+    #[test]
+    fn manifolds_keep_at_most_four_points() {
+        let points: Vec<ContactPoint> = (0..8)
+            .map(|i| {
+                let angle = i as f32 / 8.0 * std::f32::consts::TAU;
+                ContactPoint {
+                    position: v(angle.cos(), 0.0, angle.sin()),
+                    depth: if i == 3 { 0.5 } else { 0.1 },
+                }
+            })
+            .collect();
+        let kept = reduce(points);
+        assert_eq!(kept.len(), 4);
+        assert_eq!(kept[0].depth, 0.5);
+        // the deepest and the one opposite it are both kept
+        assert!((kept[1].position + kept[0].position).norm() < 1e-5);
+    }
+}
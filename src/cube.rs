@@ -1,103 +0,0 @@
-pub struct Cube {
-    vertices: Vec<Vector3<f32>>,
-    normals: Vec<Vector3<f32>>,
-}
-
-impl Cube {
-    pub fn new() -> Cube {
-        let VERTICES: Vec<Vector3<f32>> = vec![
-            // Front face
-            Vector3::new(-0.5, -0.5, 0.5),
-            Vector3::new(0.5, -0.5, 0.5),
-            Vector3::new(0.5, 0.5, 0.5),
-            Vector3::new(-0.5, -0.5, 0.5),
-            Vector3::new(0.5, 0.5, 0.5),
-            Vector3::new(-0.5, 0.5, 0.5),
-            // Back face
-            Vector3::new(-0.5, -0.5, -0.5),
-            Vector3::new(-0.5, 0.5, -0.5),
-            Vector3::new(0.5, 0.5, -0.5),
-            Vector3::new(-0.5, -0.5, -0.5),
-            Vector3::new(0.5, 0.5, -0.5),
-            Vector3::new(0.5, -0.5, -0.5),
-            // Left face
-            Vector3::new(-0.5, -0.5, -0.5),
-            Vector3::new(-0.5, -0.5, 0.5),
-            Vector3::new(-0.5, 0.5, 0.5),
-            Vector3::new(-0.5, -0.5, -0.5),
-            Vector3::new(-0.5, 0.5, 0.5),
-            Vector3::new(-0.5, 0.5, -0.5),
-            // Right face
-            Vector3::new(0.5, -0.5, -0.5),
-            Vector3::new(0.5, 0.5, -0.5),
-            Vector3::new(0.5, 0.5, 0.5),
-            Vector3::new(0.5, -0.5, -0.5),
-            Vector3::new(0.5, 0.5, 0.5),
-            Vector3::new(0.5, -0.5, 0.5),
-            // Top face
-            Vector3::new(-0.5, 0.5, -0.5),
-            Vector3::new(-0.5, 0.5, 0.5),
-            Vector3::new(0.5, 0.5, 0.5),
-            Vector3::new(-0.5, 0.5, -0.5),
-            Vector3::new(0.5, 0.5, 0.5),
-            Vector3::new(0.5, 0.5, -0.5),
-            // Bottom face
-            Vector3::new(-0.5, -0.5, -0.5),
-            Vector3::new(0.5, -0.5, -0.5),
-            Vector3::new(0.5, -0.5, 0.5),
-            Vector3::new(-0.5, -0.5, -0.5),
-            Vector3::new(0.5, -0.5, 0.5),
-            Vector3::new(-0.5, -0.5, 0.5),
-        ];
-
-        let NORMALS: Vec<Vector3<f32>> = vec![
-            // Front face (0, 0, 1)
-            Vector3::new(0.0, 0.0, 1.0),
-            Vector3::new(0.0, 0.0, 1.0),
-            Vector3::new(0.0, 0.0, 1.0),
-            Vector3::new(0.0, 0.0, 1.0),
-            Vector3::new(0.0, 0.0, 1.0),
-            Vector3::new(0.0, 0.0, 1.0),
-            // Back face (0, 0, -1)
-            Vector3::new(0.0, 0.0, -1.0),
-            Vector3::new(0.0, 0.0, -1.0),
-            Vector3::new(0.0, 0.0, -1.0),
-            Vector3::new(0.0, 0.0, -1.0),
-            Vector3::new(0.0, 0.0, -1.0),
-            Vector3::new(0.0, 0.0, -1.0),
-            // Left face (-1, 0, 0)
-            Vector3::new(-1.0, 0.0, 0.0),
-            Vector3::new(-1.0, 0.0, 0.0),
-            Vector3::new(-1.0, 0.0, 0.0),
-            Vector3::new(-1.0, 0.0, 0.0),
-            Vector3::new(-1.0, 0.0, 0.0),
-            Vector3::new(-1.0, 0.0, 0.0),
-            // Right face (1, 0, 0)
-            Vector3::new(1.0, 0.0, 0.0),
-            Vector3::new(1.0, 0.0, 0.0),
-            Vector3::new(1.0, 0.0, 0.0),
-            Vector3::new(1.0, 0.0, 0.0),
-            Vector3::new(1.0, 0.0, 0.0),
-            Vector3::new(1.0, 0.0, 0.0),
-            // Top face (0, 1, 0)
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector3::new(0.0, 1.0, 0.0),
-            // Bottom face (0, -1, 0)
-            Vector3::new(0.0, -1.0, 0.0),
-            Vector3::new(0.0, -1.0, 0.0),
-            Vector3::new(0.0, -1.0, 0.0),
-            Vector3::new(0.0, -1.0, 0.0),
-            Vector3::new(0.0, -1.0, 0.0),
-            Vector3::new(0.0, -1.0, 0.0),
-        ];
-
-        Cube {
-            vertices: VERTICES,
-            normals: NORMALS,
-        }
-    }
-}
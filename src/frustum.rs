@@ -0,0 +1,165 @@
+// view frustum planes pulled straight out of a projection * view matrix (gribb & hartmann),
+// used to skip objects whose world bounds are entirely off screen
+
+use nalgebra::{Matrix4, Vector3, Vector4};
+
+use crate::collision::BoundingBox;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrustumPlane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl FrustumPlane {
+    fn from_row(row: Vector4<f32>) -> FrustumPlane {
+        let normal = Vector3::new(row.x, row.y, row.z);
+        let length = normal.norm();
+        FrustumPlane {
+            normal: normal / length,
+            distance: row.w / length,
+        }
+    }
+
+    // positive in front of the plane, which is the inside of the frustum
+    pub fn signed_distance(&self, point: &Vector3<f32>) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+pub struct Frustum {
+    // left, right, bottom, top, near, far, all facing inward
+    pub planes: [FrustumPlane; 6],
+}
+
+impl Frustum {
+    // expects an opengl style clip space with z in [-1, 1]
+    pub fn from_matrix(view_projection: &Matrix4<f32>) -> Frustum {
+        let row = |i: usize| view_projection.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        Frustum {
+            planes: [
+                FrustumPlane::from_row(w + x),
+                FrustumPlane::from_row(w - x),
+                FrustumPlane::from_row(w + y),
+                FrustumPlane::from_row(w - y),
+                FrustumPlane::from_row(w + z),
+                FrustumPlane::from_row(w - z),
+            ],
+        }
+    }
+
+    pub fn contains_point(&self, point: &Vector3<f32>) -> bool {
+        self.planes.iter().all(|p| p.signed_distance(point) >= 0.0)
+    }
+
+    // conservative, boxes near the corners of the frustum can pass without being visible
+    pub fn intersects_aabb(&self, bbox: &BoundingBox) -> bool {
+        let center = bbox.center();
+        let half = bbox.half_extents();
+
+        self.planes.iter().all(|plane| {
+            let radius = half.dot(&plane.normal.abs());
+            plane.signed_distance(&center) >= -radius
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Projection;
+    use nalgebra::Point3;
+
+    fn camera_frustum() -> Frustum {
+        // looking down -z from the origin, 90 degree fov so the side planes are at 45 degrees
+        let projection = Matrix4::new_perspective(1.0, std::f32::consts::FRAC_PI_2, 1.0, 100.0);
+        let view = Matrix4::look_at_rh(
+            &Point3::origin(),
+            &Point3::new(0.0, 0.0, -1.0),
+            &Vector3::y(),
+        );
+        Frustum::from_matrix(&(projection * view))
+    }
+
+    #[test]
+    fn extracts_normalized_planes() {
+        let frustum = camera_frustum();
+        let [left, right, bottom, top, near, far] = frustum.planes;
+        let diagonal = 1.0 / 2.0_f32.sqrt();
+
+        let close = |a: Vector3<f32>, b: Vector3<f32>| (a - b).norm() < 1e-5;
+        assert!(close(left.normal, Vector3::new(diagonal, 0.0, -diagonal)));
+        assert!(close(right.normal, Vector3::new(-diagonal, 0.0, -diagonal)));
+        assert!(close(bottom.normal, Vector3::new(0.0, diagonal, -diagonal)));
+        assert!(close(top.normal, Vector3::new(0.0, -diagonal, -diagonal)));
+        assert!(close(near.normal, Vector3::new(0.0, 0.0, -1.0)));
+        assert!(close(far.normal, Vector3::new(0.0, 0.0, 1.0)));
+        assert!((near.distance + 1.0).abs() < 1e-3);
+        assert!((far.distance - 100.0).abs() < 1e-2);
+        for plane in frustum.planes {
+            assert!((plane.normal.norm() - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn orthographic_planes() {
+        let projection = Matrix4::new_orthographic(-10.0, 10.0, -5.0, 5.0, 0.5, 50.0);
+        let frustum = Frustum::from_matrix(&projection);
+
+        assert!(frustum.contains_point(&Vector3::new(9.9, 4.9, -49.0)));
+        assert!(!frustum.contains_point(&Vector3::new(10.1, 0.0, -10.0)));
+        assert!(!frustum.contains_point(&Vector3::new(0.0, 5.1, -10.0)));
+        assert!(!frustum.contains_point(&Vector3::new(0.0, 0.0, -0.4)));
+        assert!(!frustum.contains_point(&Vector3::new(0.0, 0.0, -50.1)));
+    }
+
+    #[test]
+    fn aabb_inside_outside_and_straddling() {
+        let frustum = camera_frustum();
+        let cube = |x: f32, y: f32, z: f32, half: f32| {
+            BoundingBox::new(x - half, x + half, y - half, y + half, z - half, z + half)
+        };
+
+        // fully inside
+        assert!(frustum.intersects_aabb(&cube(0.0, 0.0, -10.0, 1.0)));
+        // behind the camera
+        assert!(!frustum.intersects_aabb(&cube(0.0, 0.0, 10.0, 1.0)));
+        // past the far plane, and straddling it
+        assert!(!frustum.intersects_aabb(&cube(0.0, 0.0, -110.0, 5.0)));
+        assert!(frustum.intersects_aabb(&cube(0.0, 0.0, -102.0, 5.0)));
+        // off to the side at z = -10 the frustum is 10 wide either way
+        assert!(!frustum.intersects_aabb(&cube(15.0, 0.0, -10.0, 1.0)));
+        assert!(frustum.intersects_aabb(&cube(10.5, 0.0, -10.0, 1.0)));
+        assert!(!frustum.intersects_aabb(&cube(0.0, -15.0, -10.0, 1.0)));
+        // bigger than the whole frustum
+        assert!(frustum.intersects_aabb(&cube(0.0, 0.0, 0.0, 500.0)));
+    }
+
+    #[test]
+    fn transformed_box_is_culled_in_world_space() {
+        let frustum = camera_frustum();
+        let local = BoundingBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+
+        let in_view = Matrix4::new_translation(&Vector3::new(0.0, 0.0, -20.0));
+        let off_screen = Matrix4::new_translation(&Vector3::new(0.0, 40.0, -20.0));
+        assert!(frustum.intersects_aabb(&local.transform(&in_view)));
+        assert!(!frustum.intersects_aabb(&local.transform(&off_screen)));
+    }
+
+    #[test]
+    fn infinite_reversed_keeps_everything_in_front() {
+        let projection = Projection::InfiniteReversed {
+            fovy: std::f32::consts::FRAC_PI_2,
+            near: 0.5,
+        };
+        let frustum = Frustum::from_matrix(&projection.matrix(1.0));
+
+        assert!(frustum.contains_point(&Vector3::new(0.0, 0.0, -1.0)));
+        assert!(frustum.contains_point(&Vector3::new(0.0, 0.0, -1.0e6)));
+        assert!(!frustum.contains_point(&Vector3::new(0.0, 0.0, -0.4)));
+        assert!(!frustum.contains_point(&Vector3::new(0.0, 0.0, 10.0)));
+        assert!(!frustum.contains_point(&Vector3::new(20.0, 0.0, -10.0)));
+    }
+}
@@ -0,0 +1,428 @@
+// one mesh drawn many times in a single call. each instance gets its own model matrix and
+// material parameters through a per-instance vertex buffer (glVertexAttribDivisor), the same way
+// ParticleGenerator feeds its positions. only the instances that changed are re-uploaded
+
+use std::ops::Range;
+
+use nalgebra::{Matrix4, Vector2, Vector3};
+
+use crate::{
+    buffers::RenderBuffers,
+    collision::{self, BoundingBox},
+    material::{self, Material, FRACTION},
+    obj::ObjData,
+    render::Model,
+};
+
+// attribute locations 3-6 hold the model matrix columns, 7 the albedo, 8 metallic/roughness/ao
+pub const INSTANCE_ATTRIBUTE_START: u32 = 3;
+
+pub struct Instance {
+    pub model: Model,
+    pub albedo: Vector3<f32>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub ao: f32,
+}
+
+impl Instance {
+    pub fn new(
+        model: Model,
+        albedo: Vector3<f32>,
+        metallic: f32,
+        roughness: f32,
+        ao: f32,
+    ) -> Instance {
+        Instance {
+            model,
+            albedo,
+            metallic,
+            roughness,
+            ao,
+        }
+    }
+}
+
+// layout of one instance in the gpu buffer
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct InstanceData {
+    model: Matrix4<f32>,
+    albedo: Vector3<f32>,
+    material: Vector3<f32>,
+}
+
+impl InstanceData {
+    // the instance's fields are set directly, so they are clamped here like Physical's are
+    fn from_instance(instance: &Instance) -> InstanceData {
+        let clamp = |value| material::clamp_parameter(value, &FRACTION);
+        InstanceData {
+            model: instance.model.get_model_matrix(),
+            albedo: instance.albedo.map(clamp),
+            material: Vector3::new(
+                clamp(instance.metallic),
+                clamp(instance.roughness),
+                clamp(instance.ao),
+            ),
+        }
+    }
+}
+
+pub struct InstancedObject {
+    pub buffers: RenderBuffers,
+    pub material: Box<dyn Material>,
+    pub vertices: Vec<Vector3<f32>>,
+    pub normals: Vec<Vector3<f32>>,
+    pub uvs: Vec<Vector2<f32>>,
+    pub tangents: Vec<Vector3<f32>>,
+    pub bounding_box: BoundingBox,
+    pub instance_buffer: u32,
+
+    instances: Vec<Instance>,
+    // instances waiting to be uploaded, sorted and with touching ranges merged
+    dirty: Vec<Range<usize>>,
+    // number of instances the gpu buffer has room for
+    capacity: usize,
+    world_bounding_box: BoundingBox,
+}
+
+impl InstancedObject {
+    pub fn new(data: &ObjData, material: Box<dyn Material>) -> InstancedObject {
+        InstancedObject {
+            buffers: RenderBuffers::new(),
+            material,
+            vertices: data.vertices.clone(),
+            normals: data.normals.clone(),
+            uvs: data.tex_coords.clone(),
+            tangents: data.tangents.clone(),
+            bounding_box: collision::get_bounding_box(&data.vertices),
+            instance_buffer: 0,
+            instances: Vec::new(),
+            dirty: Vec::new(),
+            capacity: 0,
+            world_bounding_box: BoundingBox::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn init(&mut self) {
+        self.buffers
+            .init(&self.vertices, &self.normals, &self.uvs, &self.tangents);
+
+        unsafe {
+            gl::BindVertexArray(self.buffers.vao);
+            gl::GenBuffers(1, &mut self.instance_buffer);
+            assert_ne!(self.instance_buffer, 0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.instance_buffer);
+
+            let stride = size_of::<InstanceData>() as i32;
+            let vec4_size = size_of::<[f32; 4]>();
+            for column in 0..4 {
+                let location = INSTANCE_ATTRIBUTE_START + column;
+                gl::VertexAttribPointer(
+                    location,
+                    4,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    (column as usize * vec4_size) as *const _,
+                );
+                gl::EnableVertexAttribArray(location);
+                gl::VertexAttribDivisor(location, 1);
+            }
+
+            let albedo_offset = size_of::<Matrix4<f32>>();
+            let material_offset = albedo_offset + size_of::<Vector3<f32>>();
+            for (location, offset) in [
+                (INSTANCE_ATTRIBUTE_START + 4, albedo_offset),
+                (INSTANCE_ATTRIBUTE_START + 5, material_offset),
+            ] {
+                gl::VertexAttribPointer(
+                    location,
+                    3,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    offset as *const _,
+                );
+                gl::EnableVertexAttribArray(location);
+                gl::VertexAttribDivisor(location, 1);
+            }
+
+            gl::BindVertexArray(0);
+        }
+
+        self.reallocate();
+    }
+
+    pub fn add_instance(&mut self, instance: Instance) -> usize {
+        self.instances.push(instance);
+        let index = self.instances.len() - 1;
+        self.mark_dirty(index);
+        index
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    // changes to an instance are picked up by the next upload
+    pub fn instance_mut(&mut self, index: usize) -> &mut Instance {
+        self.mark_dirty(index);
+        &mut self.instances[index]
+    }
+
+    fn mark_dirty(&mut self, index: usize) {
+        mark_dirty(&mut self.dirty, index);
+    }
+
+    fn reallocate(&mut self) {
+        self.capacity = grown_capacity(self.instances.len());
+        let data: Vec<InstanceData> = self
+            .instances
+            .iter()
+            .map(InstanceData::from_instance)
+            .collect();
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.instance_buffer);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (self.capacity * size_of::<InstanceData>()) as isize,
+                std::ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                (data.len() * size_of::<InstanceData>()) as isize,
+                data.as_ptr().cast(),
+            );
+        }
+
+        self.dirty.clear();
+        self.update_world_bounds();
+    }
+
+    // sends the changed instances to the gpu, see plan_upload for when everything goes instead
+    pub fn upload(&mut self) {
+        let ranges = match plan_upload(&self.dirty, self.instances.len(), self.capacity) {
+            Upload::Nothing => return,
+            Upload::Everything => {
+                self.reallocate();
+                return;
+            }
+            Upload::Ranges(ranges) => ranges,
+        };
+        self.dirty.clear();
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.instance_buffer);
+        }
+        for range in ranges {
+            let data: Vec<InstanceData> = self.instances[range.clone()]
+                .iter()
+                .map(InstanceData::from_instance)
+                .collect();
+
+            unsafe {
+                gl::BufferSubData(
+                    gl::ARRAY_BUFFER,
+                    (range.start * size_of::<InstanceData>()) as isize,
+                    (data.len() * size_of::<InstanceData>()) as isize,
+                    data.as_ptr().cast(),
+                );
+            }
+        }
+
+        self.update_world_bounds();
+    }
+
+    fn update_world_bounds(&mut self) {
+        let corners: Vec<Vector3<f32>> = self
+            .instances
+            .iter()
+            .flat_map(|instance| {
+                let world = self
+                    .bounding_box
+                    .transform(&instance.model.get_model_matrix());
+                [world.min(), world.max()]
+            })
+            .collect();
+        self.world_bounding_box = collision::get_bounding_box(&corners);
+    }
+
+    // encloses every instance as of the last upload
+    pub fn world_bounding_box(&self) -> BoundingBox {
+        self.world_bounding_box
+    }
+
+    pub fn draw(&self) {
+        unsafe {
+            gl::DrawArraysInstanced(
+                gl::TRIANGLES,
+                0,
+                self.buffers.size,
+                self.instances.len() as i32,
+            );
+        }
+    }
+}
+
+// past this many separate ranges one upload of the whole buffer beats a call per range
+const MAX_DIRTY_RANGES: usize = 8;
+
+enum Upload {
+    Nothing,
+    Ranges(Vec<Range<usize>>),
+    // the buffer is reallocated and filled in one go
+    Everything,
+}
+
+fn plan_upload(dirty: &[Range<usize>], count: usize, capacity: usize) -> Upload {
+    if dirty.is_empty() {
+        Upload::Nothing
+    } else if count > capacity || dirty.len() > MAX_DIRTY_RANGES {
+        Upload::Everything
+    } else {
+        Upload::Ranges(dirty.to_vec())
+    }
+}
+
+// adds one index to sorted ranges, joining any it touches so neighbours go up in one call
+fn mark_dirty(ranges: &mut Vec<Range<usize>>, index: usize) {
+    let at = ranges.partition_point(|range| range.end < index);
+    if at < ranges.len() && ranges[at].start <= index + 1 {
+        let range = &mut ranges[at];
+        range.start = range.start.min(index);
+        range.end = range.end.max(index + 1);
+        // growing the end can reach the next range
+        if at + 1 < ranges.len() && ranges[at + 1].start <= ranges[at].end {
+            let next = ranges.remove(at + 1);
+            ranges[at].end = ranges[at].end.max(next.end);
+        }
+    } else {
+        ranges.insert(at, index..index + 1);
+    }
+}
+
+// doubles so adding instances one at a time only reallocates now and then
+fn grown_capacity(count: usize) -> usize {
+    count.max(1).next_power_of_two()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Physical, primitives};
+
+    // (start, end) pairs read better in asserts than ranges
+    fn spans(ranges: &[Range<usize>]) -> Vec<(usize, usize)> {
+        ranges
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    fn dirty(indices: &[usize]) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        for index in indices {
+            mark_dirty(&mut ranges, *index);
+        }
+        ranges
+    }
+
+    fn planned(
+        dirty: &[Range<usize>],
+        count: usize,
+        capacity: usize,
+    ) -> Option<Vec<(usize, usize)>> {
+        match plan_upload(dirty, count, capacity) {
+            Upload::Nothing => Some(Vec::new()),
+            Upload::Ranges(ranges) => Some(spans(&ranges)),
+            Upload::Everything => None,
+        }
+    }
+
+    #[test]
+    fn distant_edits_stay_separate() {
+        assert_eq!(spans(&dirty(&[0, 999])), vec![(0, 1), (999, 1000)]);
+        assert_eq!(spans(&dirty(&[999, 0])), vec![(0, 1), (999, 1000)]);
+        assert_eq!(spans(&dirty(&[5, 1, 9])), vec![(1, 2), (5, 6), (9, 10)]);
+    }
+
+    #[test]
+    fn touching_edits_merge() {
+        assert_eq!(spans(&dirty(&[3, 3, 3])), vec![(3, 4)]);
+        assert_eq!(spans(&dirty(&[3, 4, 2])), vec![(2, 5)]);
+        // 6 joins the two ranges either side of it
+        assert_eq!(spans(&dirty(&[4, 5, 7, 8, 6])), vec![(4, 9)]);
+        assert_eq!(spans(&dirty(&[0, 2, 1])), vec![(0, 3)]);
+    }
+
+    #[test]
+    fn uploads_only_what_changed_until_it_is_cheaper_not_to() {
+        assert_eq!(planned(&[], 10, 16), Some(Vec::new()));
+        assert_eq!(
+            planned(&dirty(&[0, 9]), 10, 16),
+            Some(vec![(0, 1), (9, 10)])
+        );
+
+        let scattered: Vec<usize> = (0..=MAX_DIRTY_RANGES).map(|i| i * 2).collect();
+        assert_eq!(planned(&dirty(&scattered), 100, 128), None);
+        let many_but_few_ranges: Vec<usize> = (0..50).collect();
+        assert_eq!(
+            planned(&dirty(&many_but_few_ranges), 100, 128),
+            Some(vec![(0, 50)])
+        );
+    }
+
+    #[test]
+    fn growing_past_capacity_reallocates_in_powers_of_two() {
+        assert_eq!(grown_capacity(0), 1);
+        assert_eq!(grown_capacity(1), 1);
+        assert_eq!(grown_capacity(5), 8);
+        assert_eq!(grown_capacity(8), 8);
+        assert_eq!(grown_capacity(9), 16);
+
+        assert_eq!(planned(&dirty(&[8]), 9, 8), None);
+        assert_eq!(planned(&dirty(&[7]), 8, 8), Some(vec![(7, 8)]));
+    }
+
+    #[test]
+    fn instances_are_tracked_until_uploaded() {
+        let mut orbs = InstancedObject::new(&primitives::cube(1.0), Box::new(Physical::default()));
+        for _ in 0..4 {
+            orbs.add_instance(Instance::new(Model::new(), Vector3::zeros(), 0.0, 0.5, 1.0));
+        }
+        assert_eq!(spans(&orbs.dirty), vec![(0, 4)]);
+        assert_eq!(orbs.len(), 4);
+
+        orbs.dirty.clear();
+        orbs.instance_mut(3).metallic = 1.0;
+        orbs.instance_mut(0).roughness = 0.1;
+        assert_eq!(spans(&orbs.dirty), vec![(0, 1), (3, 4)]);
+        assert_eq!(orbs.instances()[3].metallic, 1.0);
+    }
+
+    #[test]
+    fn instance_parameters_are_clamped_on_upload() {
+        let instance = Instance::new(
+            Model::new(),
+            Vector3::new(1.5, 0.5, -0.5),
+            2.0,
+            f32::NAN,
+            0.5,
+        );
+        let data = InstanceData::from_instance(&instance);
+        assert_eq!(data.albedo, Vector3::new(1.0, 0.5, 0.0));
+        assert_eq!(data.material, Vector3::new(1.0, 0.0, 0.5));
+    }
+}
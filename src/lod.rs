@@ -0,0 +1,248 @@
+// level of detail meshes for an object. level 0 is always the object's own buffers, the levels
+// stored here are the progressively coarser meshes that replace it further away
+
+use nalgebra::{Vector2, Vector3};
+
+use crate::{buffers::RenderBuffers, camera::Projection, obj::ObjData};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LodMetric {
+    // world space distance from the camera to the object's bounds
+    Distance,
+    // fraction of the screen height covered by the object's bounding sphere
+    ScreenSize,
+}
+
+pub struct LodLevel {
+    pub buffers: RenderBuffers,
+    pub vertices: Vec<Vector3<f32>>,
+    pub normals: Vec<Vector3<f32>>,
+    pub uvs: Vec<Vector2<f32>>,
+    pub tangents: Vec<Vector3<f32>>,
+    // for Distance the level is used at or beyond this distance,
+    // for ScreenSize it is used once the object covers less than this much of the screen
+    pub threshold: f32,
+}
+
+impl LodLevel {
+    pub fn new(data: &ObjData, threshold: f32) -> LodLevel {
+        LodLevel {
+            buffers: RenderBuffers::new(),
+            vertices: data.vertices.clone(),
+            normals: data.normals.clone(),
+            uvs: data.tex_coords.clone(),
+            tangents: data.tangents.clone(),
+            threshold,
+        }
+    }
+
+    pub fn init(&mut self) {
+        self.buffers
+            .init(&self.vertices, &self.normals, &self.uvs, &self.tangents);
+    }
+}
+
+pub struct LodGroup {
+    pub metric: LodMetric,
+    pub levels: Vec<LodLevel>,
+    // seconds spent dithering between two levels when the selection changes, 0 pops instantly
+    pub fade_duration: f32,
+
+    current: usize,
+    previous: Option<usize>,
+    fade: f32,
+}
+
+impl LodGroup {
+    pub fn new(metric: LodMetric) -> LodGroup {
+        LodGroup {
+            metric,
+            levels: Vec::new(),
+            fade_duration: 0.0,
+            current: 0,
+            previous: None,
+            fade: 1.0,
+        }
+    }
+
+    pub fn add_level(&mut self, level: LodLevel) {
+        self.levels.push(level);
+        match self.metric {
+            LodMetric::Distance => self
+                .levels
+                .sort_by(|a, b| a.threshold.total_cmp(&b.threshold)),
+            LodMetric::ScreenSize => self
+                .levels
+                .sort_by(|a, b| b.threshold.total_cmp(&a.threshold)),
+        }
+    }
+
+    pub fn current_level(&self) -> usize {
+        self.current
+    }
+
+    // picks the level for this frame given the metric value measured against the active camera
+    pub fn select(&mut self, value: f32, delta_time: f32) {
+        let target = select_level(&self.metric, &self.levels, value);
+
+        if target != self.current {
+            self.previous = if self.fade_duration > 0.0 {
+                Some(self.current)
+            } else {
+                None
+            };
+            self.current = target;
+            self.fade = 0.0;
+        }
+
+        if self.previous.is_some() {
+            self.fade += delta_time / self.fade_duration;
+            if self.fade >= 1.0 {
+                self.previous = None;
+            }
+        }
+        if self.previous.is_none() {
+            self.fade = 1.0;
+        }
+    }
+
+    // the levels to draw this frame with their dither fade: 0 draws the level fully,
+    // (0, 1) fades a level in and (-1, 0) fades the level it replaces out with the inverse pattern
+    pub fn draws(&self) -> Vec<(usize, f32)> {
+        match self.previous {
+            Some(previous) => vec![(self.current, self.fade.max(f32::EPSILON)), (previous, -self.fade.max(f32::EPSILON))],
+            None => vec![(self.current, 0.0)],
+        }
+    }
+}
+
+pub fn select_level(metric: &LodMetric, levels: &[LodLevel], value: f32) -> usize {
+    let mut selected = 0;
+    for (i, level) in levels.iter().enumerate() {
+        let passes = match metric {
+            LodMetric::Distance => value >= level.threshold,
+            LodMetric::ScreenSize => value < level.threshold,
+        };
+        if passes {
+            selected = i + 1;
+        }
+    }
+    selected
+}
+
+// how much of the screen height a sphere covers, orthographic cameras don't shrink things with
+// distance so only their view height matters
+pub fn screen_size(radius: f32, distance: f32, projection: &Projection) -> f32 {
+    match *projection {
+        Projection::Perspective { fovy, .. } | Projection::InfiniteReversed { fovy, .. } => {
+            if distance <= radius {
+                return 1.0;
+            }
+            radius / (distance * (fovy * 0.5).tan())
+        }
+        Projection::Orthographic { height, .. } => (radius / (height * 0.5)).min(1.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    fn level(threshold: f32) -> LodLevel {
+        LodLevel::new(&primitives::cube(1.0), threshold)
+    }
+
+    fn group(metric: LodMetric, thresholds: &[f32]) -> LodGroup {
+        let mut group = LodGroup::new(metric);
+        for threshold in thresholds {
+            group.add_level(level(*threshold));
+        }
+        group
+    }
+
+    #[test]
+    fn distance_levels_switch_at_their_threshold() {
+        let levels = [level(100.0), level(200.0)];
+        assert_eq!(select_level(&LodMetric::Distance, &levels, 0.0), 0);
+        assert_eq!(select_level(&LodMetric::Distance, &levels, 99.9), 0);
+        assert_eq!(select_level(&LodMetric::Distance, &levels, 100.0), 1);
+        assert_eq!(select_level(&LodMetric::Distance, &levels, 199.9), 1);
+        assert_eq!(select_level(&LodMetric::Distance, &levels, 200.0), 2);
+        assert_eq!(select_level(&LodMetric::Distance, &levels, 1e6), 2);
+    }
+
+    #[test]
+    fn screen_size_levels_switch_once_the_object_is_smaller() {
+        let levels = [level(0.2), level(0.05)];
+        assert_eq!(select_level(&LodMetric::ScreenSize, &levels, 1.0), 0);
+        assert_eq!(select_level(&LodMetric::ScreenSize, &levels, 0.2), 0);
+        assert_eq!(select_level(&LodMetric::ScreenSize, &levels, 0.19), 1);
+        assert_eq!(select_level(&LodMetric::ScreenSize, &levels, 0.05), 1);
+        assert_eq!(select_level(&LodMetric::ScreenSize, &levels, 0.01), 2);
+    }
+
+    #[test]
+    fn no_levels_always_draws_the_full_mesh() {
+        assert_eq!(select_level(&LodMetric::Distance, &[], 1e6), 0);
+        assert_eq!(select_level(&LodMetric::ScreenSize, &[], 0.0), 0);
+
+        let mut group = group(LodMetric::Distance, &[]);
+        group.select(1e6, 0.1);
+        assert_eq!(group.draws(), vec![(0, 0.0)]);
+    }
+
+    #[test]
+    fn levels_added_out_of_order_are_sorted() {
+        let mut far = group(LodMetric::Distance, &[800.0, 100.0, 400.0]);
+        let thresholds: Vec<f32> = far.levels.iter().map(|l| l.threshold).collect();
+        assert_eq!(thresholds, vec![100.0, 400.0, 800.0]);
+        far.select(500.0, 0.0);
+        assert_eq!(far.current_level(), 2);
+
+        let mut small = group(LodMetric::ScreenSize, &[0.05, 0.3, 0.1]);
+        let thresholds: Vec<f32> = small.levels.iter().map(|l| l.threshold).collect();
+        assert_eq!(thresholds, vec![0.3, 0.1, 0.05]);
+        small.select(0.08, 0.0);
+        assert_eq!(small.current_level(), 2);
+    }
+
+    #[test]
+    fn switches_pop_or_cross_fade() {
+        let mut popping = group(LodMetric::Distance, &[100.0]);
+        popping.select(150.0, 0.016);
+        assert_eq!(popping.draws(), vec![(1, 0.0)]);
+
+        let mut fading = group(LodMetric::Distance, &[100.0]);
+        fading.fade_duration = 0.5;
+        fading.select(150.0, 0.25);
+        let draws = fading.draws();
+        assert_eq!(draws.len(), 2);
+        assert_eq!((draws[0].0, draws[1].0), (1, 0));
+        assert!((draws[0].1 - 0.5).abs() < 1e-6);
+        assert!((draws[1].1 + 0.5).abs() < 1e-6);
+
+        fading.select(150.0, 0.25);
+        assert_eq!(fading.draws(), vec![(1, 0.0)]);
+    }
+
+    #[test]
+    fn screen_size_shrinks_with_distance_only_in_perspective() {
+        let perspective = Projection::Perspective {
+            fovy: std::f32::consts::FRAC_PI_2,
+            near: 0.1,
+            far: 1000.0,
+        };
+        assert_eq!(screen_size(1.0, 0.5, &perspective), 1.0);
+        assert!((screen_size(1.0, 10.0, &perspective) - 0.1).abs() < 1e-5);
+        assert!(screen_size(1.0, 20.0, &perspective) < screen_size(1.0, 10.0, &perspective));
+
+        let orthographic = Projection::Orthographic {
+            height: 10.0,
+            near: 0.1,
+            far: 1000.0,
+        };
+        assert_eq!(screen_size(1.0, 10.0, &orthographic), 0.2);
+        assert_eq!(screen_size(1.0, 100.0, &orthographic), 0.2);
+    }
+}
@@ -4,19 +4,36 @@
 #![allow(clippy::zero_ptr)]
 
 mod buffers;
+mod bvh;
 mod camera;
+mod camera_controller;
+mod camera_path;
 mod collision;
+mod convex;
 mod directional_light;
+mod frustum;
+mod instancing;
+mod lod;
 mod material;
+mod material_library;
+mod mesh;
 mod obj;
 mod particle;
+mod physics;
+mod picking;
 mod point_light;
+mod primitives;
 mod raycast;
 mod render;
+mod render_queue;
+mod render_target;
 mod scene;
+mod scene_material_preview;
 mod scene_one;
 mod shader;
+mod spline;
 mod texture;
+mod texture_container;
 mod vertex;
 mod window;
 
@@ -30,7 +47,10 @@ use point_light::PointLight;
 use scene::{Scene, Settings};
 
 use gl::types::*;
-use render::{Model, Object};
+use frustum::Frustum;
+use camera::Camera;
+use render::{Model, Object, RenderStats};
+use render_queue::RenderQueue;
 use sdl2::event::WindowEvent;
 use std::{collections::HashMap, ffi::CString, time::Instant};
 
@@ -44,14 +64,19 @@ fn main() {
     let gl_win = gw.window.expect("failed to get opengl:: window");
     let video_subsys = gw.video_subsystem.expect("unable to get video subsys");
     let sdl = gw.ctx.expect("failed to get window context");
-    let active_scene: String = "scene-01".to_string();
+    // the first argument picks the scene, scene-01 when there isn't one
+    let active_scene: String = std::env::args().nth(1).unwrap_or("scene-01".to_string());
     let mut scenes: HashMap<String, Scene> = HashMap::new();
-    let mut scene_settings = Settings::new(WINDOW_WIDTH, WINDOW_HEIGHT, 60.0_f32.to_radians());
+    let mut scene_settings = Settings::new(WINDOW_WIDTH, WINDOW_HEIGHT);
 
     let _gl_context = gl_win.gl_create_context().unwrap();
     let _gl = gl::load_with(|s| video_subsys.gl_get_proc_address(s) as *const std::os::raw::c_void);
 
-    scenes.insert("scene-01".to_string(), scene_one::scene_one(scene_settings));
+    scenes.insert("scene-01".to_string(), scene_one::scene_one(scene_settings.clone()));
+    scenes.insert(
+        "material-preview".to_string(),
+        scene_material_preview::material_preview(scene_settings),
+    );
 
     let scene = scenes
         .get_mut(&active_scene)
@@ -60,15 +85,19 @@ fn main() {
     scene.start();
 
     let mut particle_gen = ParticleGenerator::new(1000);
+    let mut last_frame = Instant::now();
 
     'main_loop: loop {
-        let aspect: f32 = scene.settings.screen_width as f32 / scene.settings.screen_height as f32;
-        let projection = Matrix4::new_perspective(aspect, scene.settings.fovy, 0.1, 10000.0);
+        let delta_time = last_frame.elapsed().as_secs_f32();
+        last_frame = Instant::now();
 
         // handle events this frame
         let mut event_pump = sdl.event_pump().unwrap();
         for event in event_pump.poll_iter() {
             (scene.on_event)(scene, event.clone());
+            if let Some(controller) = scene.camera_controller.as_mut() {
+                controller.handle_event(&event);
+            }
             match event {
                 sdl2::event::Event::Quit { .. } => break 'main_loop,
                 sdl2::event::Event::Window {
@@ -88,77 +117,91 @@ fn main() {
 
         // now the events are clear, update our scene
         (scene.on_update)(scene);
+        scene.update_physics(delta_time);
+        for (name, err) in scene.reload_materials() {
+            println!("failed to reload material {}: {}", name, err);
+        }
+        scene.update_camera(delta_time);
+        scene.rebuild_bvh();
+
+        // each camera's aspect follows the shape of its viewport
+        let window = (scene.settings.screen_width, scene.settings.screen_height);
+        for (_, camera) in scene.cameras.iter_mut() {
+            let (_, _, width, height) = camera.viewport_pixels(window.0, window.1);
+            camera.aspect = width as f32 / height as f32;
+        }
 
-        // and then draw!
-        unsafe {
-            // Clear the screen
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-            gl::Enable(gl::DEPTH_TEST);
-            gl::Enable(gl::MULTISAMPLE);
-            gl::Viewport(
-                0,
-                0,
-                scene.settings.screen_width,
-                scene.settings.screen_height,
-            );
-
-            // now loop over the objects and get specific uniform fields for the object
-            for (_, object) in &mut scene.object_map.iter_mut() {
-                gl::UseProgram(object.shader_program);
-
-                // Get uniform locations for projection, view, and model matrices
-                let projection_loc =
-                    shader::get_shader_location(object.shader_program, "projection");
-                let model_loc = shader::get_shader_location(object.shader_program, "model");
-
-                gl::UniformMatrix4fv(projection_loc, 1, gl::FALSE, projection.as_ptr());
-                scene
-                    .cameras
-                    .get_mut(&scene.active_camera)
-                    .unwrap()
-                    .link_shader(object.shader_program);
-                for (index, pl) in scene.point_lights.iter_mut().enumerate() {
-                    pl.link_shader(object.shader_program, index.try_into().unwrap());
-                }
-
-                // link the dir light if it is present
-                if let Some(dir_light) = &scene.directional_light {
-                    dir_light.link_shader(object.shader_program);
-                }
-
-                gl::Uniform1i(
-                    shader::get_shader_location(object.shader_program, "numPointLights"),
-                    scene.point_lights.len().try_into().unwrap(),
-                );
-
-                gl::Uniform2f(
-                    shader::get_shader_location(object.shader_program, "resolution"),
-                    scene.settings.screen_width as f32,
-                    scene.settings.screen_height as f32,
-                );
-
-                let model = object.model.get_model_matrix();
-                gl::UniformMatrix4fv(model_loc, 1, gl::FALSE, model.as_ptr());
+        // pick the level of detail for each object against the active camera
+        let active_camera = scene.cameras.get(&scene.active_camera).unwrap();
+        for (_, object) in scene.object_map.iter_mut() {
+            object.update_lod(active_camera, delta_time);
+        }
 
-                // link the material here
-                object.material.link_shader(object.shader_program);
+        // send any instances that moved this frame to the gpu
+        for (_, instanced) in scene.instanced_objects.iter_mut() {
+            instanced.upload();
+        }
 
-                // Bind buffers and draw
-                object.buffers.bind();
-                gl::DrawArrays(gl::TRIANGLES, 0, object.buffers.size);
-                object.buffers.unbind();
+        // and then draw every camera, in order
+        let mut cameras: Vec<(&String, &Camera)> = scene
+            .cameras
+            .iter()
+            .filter(|(_, camera)| camera.enabled)
+            .collect();
+        cameras.sort_by_key(|(name, camera)| (camera.order, name.as_str()));
+
+        let mut render_stats = None;
+        for (name, camera) in cameras {
+            let stats = unsafe { draw_camera(scene, camera, window) };
+
+            // particles and the stats printed with I only belong to the main view
+            if *name == scene.active_camera {
+                particle_gen.update();
+                particle_gen.render(
+                    &camera.view_matrix(),
+                    &camera.projection_matrix(camera.aspect),
+                    0.0,
+                );
+                render_stats = Some(stats);
             }
-            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        if let Some(stats) = render_stats {
+            scene.render_stats = stats;
         }
 
-        let cam = scene.cameras.get_mut(&scene.active_camera).unwrap();
-
-        // render particles
-        // let ident = nalgebra::Matrix4::identity();
-
-        particle_gen.update();
-        particle_gen.render(&cam.view_matrix(), &projection, 0.0);
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
 
         gl_win.gl_swap_window();
     }
 }
+
+// renders the scene from one camera into its viewport of the window or its render target
+unsafe fn draw_camera(scene: &Scene, camera: &Camera, window: (i32, i32)) -> RenderStats {
+    match &camera.render_target {
+        Some(target) => target.bind(),
+        None => gl::BindFramebuffer(gl::FRAMEBUFFER, 0),
+    }
+    let (x, y, width, height) = camera.viewport_pixels(window.0, window.1);
+
+    camera.apply_depth_state();
+
+    // only clear this camera's rectangle so split screens and insets don't wipe each other
+    gl::Viewport(x, y, width, height);
+    gl::Enable(gl::SCISSOR_TEST);
+    gl::Scissor(x, y, width, height);
+    gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+    gl::Disable(gl::SCISSOR_TEST);
+    gl::Enable(gl::DEPTH_TEST);
+    gl::Enable(gl::MULTISAMPLE);
+
+    // queue up what is visible, sorted to keep state changes down, and draw it
+    let view = camera.view_matrix();
+    let frustum = Frustum::from_matrix(&camera.view_projection());
+    let mut queue = RenderQueue::new();
+    queue.collect(scene, &view, &frustum);
+    queue.sort();
+    queue.execute(scene, camera, (width, height))
+}
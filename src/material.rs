@@ -1,23 +1,171 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+
 use gl::types::*;
-use nalgebra::Vector3;
+use nalgebra::{Vector2, Vector3, Vector4};
 
-use crate::shader::get_shader_location;
+use crate::obj::{MtlData, ObjData};
+use crate::shader::{self, get_shader_location};
 
 pub trait Material {
-    fn link_shader(&self, program: u32);
-    fn toggle_map(&mut self, t: TextureType);
+    // vertex and fragment stage names under shaders/, see shader::get_or_load
+    fn shader(&self) -> (&str, &str);
+
+    // the material.<name> uniforms this material sets, textures are declared by texture_slots
+    fn parameters(&self) -> Vec<(&str, MaterialParam)>;
+
+    // uploads the material's own uniforms, textures are bound from texture_slots by link_material
+    fn link_shader(&self, program: u32) {
+        for (name, value) in self.parameters() {
+            value.link(get_shader_location(program, &format!("material.{}", name)));
+        }
+    }
+
+    // textures the shader samples as material.<name>
+    fn texture_slots(&self) -> Vec<(&str, &Texture)> {
+        Vec::new()
+    }
+
+    fn texture_slot_mut(&mut self, _name: &str) -> Option<&mut Texture> {
+        None
+    }
+
+    // flips a declared slot on or off, false if the material has no slot with that name
+    fn toggle_map(&mut self, name: &str) -> bool {
+        match self.texture_slot_mut(name) {
+            Some(texture) => {
+                texture.enabled = !texture.enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Opaque
+    }
+
+    // double sided materials are drawn without back face culling
+    fn double_sided(&self) -> bool {
+        false
+    }
+
+    // transparent materials are drawn after everything else, back to front
+    fn is_transparent(&self) -> bool {
+        matches!(self.alpha_mode(), AlphaMode::Blend | AlphaMode::Additive)
+    }
+}
+
+// the program a material draws with
+pub fn program(material: &dyn Material) -> u32 {
+    let (vert, frag) = material.shader();
+    shader::get_or_load(vert, frag)
+}
+
+// identifies a material by everything link_material uploads and the render state it needs, so
+// objects that each own an equal material still batch together in the render queue
+pub fn batch_key(material: &dyn Material) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    material.shader().hash(&mut hasher);
+    for (name, value) in material.parameters() {
+        name.hash(&mut hasher);
+        value.hash_bits(&mut hasher);
+    }
+    for (name, texture) in material.texture_slots() {
+        name.hash(&mut hasher);
+        (texture.tex, texture.enabled, texture.scale.to_bits(), texture.target).hash(&mut hasher);
+    }
+    let alpha_mode = material.alpha_mode();
+    (alpha_mode.shader_value(), alpha_mode.cutoff().to_bits()).hash(&mut hasher);
+    material.double_sided().hash(&mut hasher);
+    hasher.finish()
+}
+
+// uploads a material to its program: its own uniforms, then each texture slot on the next free unit
+pub fn link_material(material: &dyn Material, program: u32) {
+    material.link_shader(program);
+    for (unit, (name, texture)) in material.texture_slots().into_iter().enumerate() {
+        texture.link(program, &format!("material.{}", name), unit as u32);
+    }
 }
 
-pub enum TextureType {
-    DIFFUSE,
-    NORMAL,
-    ARM,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    // fragments with alpha below the cutoff are discarded, everything else is opaque
+    Mask(f32),
+    Blend,
+    Additive,
+}
+
+impl AlphaMode {
+    // matches the ALPHA_* constants in pbr.frag.glsl
+    pub fn shader_value(&self) -> i32 {
+        match self {
+            AlphaMode::Opaque => 0,
+            AlphaMode::Mask(_) => 1,
+            AlphaMode::Blend => 2,
+            AlphaMode::Additive => 3,
+        }
+    }
+
+    pub fn cutoff(&self) -> f32 {
+        match self {
+            AlphaMode::Mask(cutoff) => *cutoff,
+            _ => 0.0,
+        }
+    }
+}
+
+// shows a single input of the lighting instead of the lit result, cycled at runtime to check
+// what a material's maps actually contain
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugView {
+    Lit,
+    Albedo,
+    Metallic,
+    Roughness,
+    AmbientOcclusion,
+    Normal,
+    Emissive,
+}
+
+impl DebugView {
+    pub fn next(&self) -> DebugView {
+        match self {
+            DebugView::Lit => DebugView::Albedo,
+            DebugView::Albedo => DebugView::Metallic,
+            DebugView::Metallic => DebugView::Roughness,
+            DebugView::Roughness => DebugView::AmbientOcclusion,
+            DebugView::AmbientOcclusion => DebugView::Normal,
+            DebugView::Normal => DebugView::Emissive,
+            DebugView::Emissive => DebugView::Lit,
+        }
+    }
+
+    // matches the DEBUG_* constants in pbr.frag.glsl
+    pub fn shader_value(&self) -> i32 {
+        match self {
+            DebugView::Lit => 0,
+            DebugView::Albedo => 1,
+            DebugView::Metallic => 2,
+            DebugView::Roughness => 3,
+            DebugView::AmbientOcclusion => 4,
+            DebugView::Normal => 5,
+            DebugView::Emissive => 6,
+        }
+    }
 }
 
 pub struct Texture {
     pub tex: Option<u32>,
     pub enabled: bool,
     pub scale: f32,
+    // TEXTURE_2D, TEXTURE_2D_ARRAY or TEXTURE_CUBE_MAP, has to match the sampler type in the shader
+    pub target: u32,
 }
 
 impl Texture {
@@ -26,30 +174,73 @@ impl Texture {
             tex: None,
             enabled: false,
             scale: 1.0,
+            target: gl::TEXTURE_2D,
         };
     }
+
+    // uploads the enabled flag, uv scale and sampler of a MaterialTexture uniform and binds the
+    // texture to the given unit
+    fn link(&self, program: u32, name: &str, unit: u32) {
+        unsafe {
+            gl::Uniform1i(
+                get_shader_location(program, &format!("{}.enabled", name)),
+                self.enabled as i32,
+            );
+            gl::Uniform1f(
+                get_shader_location(program, &format!("{}.scale", name)),
+                self.scale,
+            );
+
+            if let Some(tex) = self.tex {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+                gl::BindTexture(self.target, tex);
+                gl::Uniform1i(
+                    get_shader_location(program, &format!("{}.tex", name)),
+                    unit as i32,
+                );
+            }
+        }
+    }
 }
 
+// the fields stay public so a material can be tweaked after it is built, values edited out of
+// range are clamped when the material is linked, the same way build_clamped does
 pub struct Physical {
     pub albedo: Vector3<f32>,
     pub metallic: f32,
     pub roughness: f32,
     pub ao: f32,
+    // multiplied with the alpha of the diffuse texture when it is enabled
+    pub opacity: f32,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+    // light given off by the surface itself, emissive_texture multiplies the color when enabled
+    pub emissive: Vector3<f32>,
+    pub emissive_strength: f32,
     pub diffuse_texture: Texture,
     pub normal_texture: Texture,
+    // ao in red, roughness in green and metallic in blue
     pub arm_texture: Texture,
+    pub emissive_texture: Texture,
+    // single channel maps (red is read), these take priority over the matching arm channel
+    pub ao_texture: Texture,
+    pub roughness_texture: Texture,
+    pub metallic_texture: Texture,
+    // parallax occlusion mapping, white in the height map is the top of the surface
+    pub height_texture: Texture,
+    // how deep the darkest parts of the height map sit, in uvs of the height map
+    pub height_scale: f32,
+    // most steps taken through the height field, fewer are used looking straight down
+    pub parallax_steps: i32,
+    // marches toward each light through the height field so bumps shadow their surroundings
+    pub parallax_shadows: bool,
 }
 
 impl Physical {
-    pub fn new(a: Vector3<f32>, m: f32, r: f32, ao: f32) -> Physical {
-        Physical {
-            albedo: a,
-            metallic: m,
-            roughness: r,
-            ao: ao,
-            diffuse_texture: Texture::new(),
-            normal_texture: Texture::new(),
-            arm_texture: Texture::new(),
+    // values are checked when the builder finishes, see PhysicalBuilder::build
+    pub fn builder() -> PhysicalBuilder {
+        PhysicalBuilder {
+            physical: Physical::default(),
         }
     }
 
@@ -59,81 +250,754 @@ impl Physical {
             metallic: 0.5,
             roughness: 0.5,
             ao: 0.5,
+            opacity: 1.0,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+            emissive: Vector3::zeros(),
+            emissive_strength: 1.0,
             diffuse_texture: Texture::new(),
             normal_texture: Texture::new(),
             arm_texture: Texture::new(),
+            emissive_texture: Texture::new(),
+            ao_texture: Texture::new(),
+            roughness_texture: Texture::new(),
+            metallic_texture: Texture::new(),
+            height_texture: Texture::new(),
+            height_scale: 0.05,
+            parallax_steps: 16,
+            parallax_shadows: false,
+        }
+    }
+
+    // albedo from Kd, emission from Ke and opacity from d, anything not fully opaque is blended.
+    // exporters write all sorts of values so these are always clamped rather than rejected
+    pub fn from_mtl(mtl: &MtlData) -> Physical {
+        let mut builder = Physical::builder();
+        if let Some(diffuse) = mtl.diffuse {
+            builder = builder.albedo(diffuse);
         }
+        if let Some(emissive) = mtl.emissive {
+            builder = builder.emissive(emissive);
+        }
+        if let Some(dissolve) = mtl.dissolve {
+            builder = builder.opacity(dissolve);
+            if dissolve < 1.0 {
+                builder = builder.alpha_mode(AlphaMode::Blend);
+            }
+        }
+        builder.build_clamped()
+    }
+
+    // the first material in an obj's .mtl, the default when there isn't one
+    pub fn from_obj(data: &ObjData) -> Physical {
+        data.materials
+            .first()
+            .map(Physical::from_mtl)
+            .unwrap_or_else(Physical::default)
     }
 }
 
-impl Material for Physical {
-    fn link_shader(&self, program: u32) {
-        // link the uniforms with the shader
-        let albedo_loc = get_shader_location(program, "material.albedo");
-        let metallic_loc = get_shader_location(program, "material.metallic");
-        let roughness_loc = get_shader_location(program, "material.roughness");
-        let ao_loc = get_shader_location(program, "material.ao");
-        let diffuse_scaling_loc = get_shader_location(program, "material.diffuse_texture.scale");
-        let normal_scaling_loc = get_shader_location(program, "material.normal_texture.scale");
+// a Physical parameter outside the range it means anything in for the ggx model
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterError {
+    pub name: &'static str,
+    pub value: f32,
+    pub min: f32,
+    pub max: f32,
+}
 
-        unsafe {
-            gl::Uniform3f(albedo_loc, self.albedo.x, self.albedo.y, self.albedo.z);
-            gl::Uniform1f(metallic_loc, self.metallic);
-            gl::Uniform1f(roughness_loc, self.roughness);
-            gl::Uniform1f(ao_loc, self.ao);
-            gl::Uniform1f(diffuse_scaling_loc, self.diffuse_texture.scale);
-            gl::Uniform1f(normal_scaling_loc, self.normal_texture.scale);
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} is {}, expected a value between {} and {}",
+            self.name, self.value, self.min, self.max
+        )
+    }
+}
 
-            gl::Uniform1i(
-                get_shader_location(program, "material.diffuse_texture.enabled"),
-                self.diffuse_texture.enabled as i32,
-            );
+pub const FRACTION: RangeInclusive<f32> = 0.0..=1.0;
+const EMISSION: RangeInclusive<f32> = 0.0..=f32::MAX;
+const HEIGHT_SCALE: RangeInclusive<f32> = 0.0..=0.5;
+const PARALLAX_STEPS: RangeInclusive<f32> = 1.0..=128.0;
 
-            if let Some(diffuse) = self.diffuse_texture.tex {
-                gl::ActiveTexture(gl::TEXTURE0);
-                gl::BindTexture(gl::TEXTURE_2D, diffuse);
-                let texture_loc = get_shader_location(program, "material.diffuse_texture.tex");
-                gl::Uniform1i(texture_loc, 0);
-            }
+// NaNs become the bottom of the range
+pub fn clamp_parameter(value: f32, range: &RangeInclusive<f32>) -> f32 {
+    if value.is_nan() {
+        *range.start()
+    } else {
+        value.clamp(*range.start(), *range.end())
+    }
+}
 
-            gl::Uniform1i(
-                get_shader_location(program, "material.normal_texture.enabled"),
-                self.normal_texture.enabled as i32,
-            );
+// albedo, metallic, roughness, ao, opacity and the mask cutoff are all fractions in [0, 1].
+// metallic above 1 or roughness of 17 don't make a surface more metal or rougher, they push the
+// brdf outside its domain and the shader ends up with negative diffuse or NaNs. emission only
+// has to be non-negative, it is hdr and can go as bright as needed. parallax depth past half a
+// texture tile smears badly and the shader loops at most 128 steps
+pub struct PhysicalBuilder {
+    physical: Physical,
+}
 
-            if let Some(normal) = self.normal_texture.tex {
-                gl::ActiveTexture(gl::TEXTURE1);
-                gl::BindTexture(gl::TEXTURE_2D, normal);
-                let texture_loc = get_shader_location(program, "material.normal_texture.tex");
-                gl::Uniform1i(texture_loc, 1);
-            }
+impl PhysicalBuilder {
+    pub fn albedo(mut self, albedo: Vector3<f32>) -> PhysicalBuilder {
+        self.physical.albedo = albedo;
+        self
+    }
 
-            gl::Uniform1i(
-                get_shader_location(program, "material.arm_texture.enabled"),
-                self.arm_texture.enabled as i32,
-            );
+    pub fn metallic(mut self, metallic: f32) -> PhysicalBuilder {
+        self.physical.metallic = metallic;
+        self
+    }
+
+    pub fn roughness(mut self, roughness: f32) -> PhysicalBuilder {
+        self.physical.roughness = roughness;
+        self
+    }
+
+    pub fn ao(mut self, ao: f32) -> PhysicalBuilder {
+        self.physical.ao = ao;
+        self
+    }
+
+    pub fn opacity(mut self, opacity: f32) -> PhysicalBuilder {
+        self.physical.opacity = opacity;
+        self
+    }
+
+    pub fn alpha_mode(mut self, alpha_mode: AlphaMode) -> PhysicalBuilder {
+        self.physical.alpha_mode = alpha_mode;
+        self
+    }
+
+    pub fn double_sided(mut self, double_sided: bool) -> PhysicalBuilder {
+        self.physical.double_sided = double_sided;
+        self
+    }
+
+    pub fn emissive(mut self, emissive: Vector3<f32>) -> PhysicalBuilder {
+        self.physical.emissive = emissive;
+        self
+    }
 
-            if let Some(arm) = self.arm_texture.tex {
-                gl::ActiveTexture(gl::TEXTURE2);
-                gl::BindTexture(gl::TEXTURE_2D, arm);
-                let texture_loc = get_shader_location(program, "material.arm_texture.tex");
-                gl::Uniform1i(texture_loc, 2);
+    pub fn emissive_strength(mut self, strength: f32) -> PhysicalBuilder {
+        self.physical.emissive_strength = strength;
+        self
+    }
+
+    pub fn height_scale(mut self, scale: f32) -> PhysicalBuilder {
+        self.physical.height_scale = scale;
+        self
+    }
+
+    pub fn parallax_steps(mut self, steps: i32) -> PhysicalBuilder {
+        self.physical.parallax_steps = steps;
+        self
+    }
+
+    pub fn parallax_shadows(mut self, shadows: bool) -> PhysicalBuilder {
+        self.physical.parallax_shadows = shadows;
+        self
+    }
+
+    // debug builds report the first value out of range so bad numbers get fixed at the source,
+    // release builds clamp them into range instead of failing
+    pub fn build(self) -> Result<Physical, ParameterError> {
+        self.validate(cfg!(debug_assertions))
+    }
+
+    // clamps out of range values in every build, NaNs become the bottom of the range
+    pub fn build_clamped(self) -> Physical {
+        self.validate(false).expect("clamping never fails")
+    }
+
+    fn validate(mut self, strict: bool) -> Result<Physical, ParameterError> {
+        let check = |name: &'static str, value: f32, range: RangeInclusive<f32>| {
+            if strict && !range.contains(&value) {
+                return Err(ParameterError {
+                    name,
+                    value,
+                    min: *range.start(),
+                    max: *range.end(),
+                });
             }
+            Ok(clamp_parameter(value, &range))
+        };
+
+        let p = &mut self.physical;
+        for (i, name) in ["albedo.r", "albedo.g", "albedo.b"].into_iter().enumerate() {
+            p.albedo[i] = check(name, p.albedo[i], FRACTION)?;
+        }
+        p.metallic = check("metallic", p.metallic, FRACTION)?;
+        p.roughness = check("roughness", p.roughness, FRACTION)?;
+        p.ao = check("ao", p.ao, FRACTION)?;
+        p.opacity = check("opacity", p.opacity, FRACTION)?;
+        if let AlphaMode::Mask(cutoff) = p.alpha_mode {
+            p.alpha_mode = AlphaMode::Mask(check("alpha_cutoff", cutoff, FRACTION)?);
+        }
+        for (i, name) in ["emissive.r", "emissive.g", "emissive.b"].into_iter().enumerate() {
+            p.emissive[i] = check(name, p.emissive[i], EMISSION)?;
+        }
+        p.emissive_strength = check("emissive_strength", p.emissive_strength, EMISSION)?;
+        p.height_scale = check("height_scale", p.height_scale, HEIGHT_SCALE)?;
+        p.parallax_steps = check("parallax_steps", p.parallax_steps as f32, PARALLAX_STEPS)? as i32;
+
+        Ok(self.physical)
+    }
+}
+
+impl Material for Physical {
+    fn shader(&self) -> (&str, &str) {
+        ("pbr", "pbr")
+    }
+
+    fn parameters(&self) -> Vec<(&str, MaterialParam)> {
+        let steps = clamp_parameter(self.parallax_steps as f32, &PARALLAX_STEPS) as i32;
+        vec![
+            ("albedo", MaterialParam::Vec3(self.albedo.map(|c| clamp_parameter(c, &FRACTION)))),
+            ("metallic", MaterialParam::Float(clamp_parameter(self.metallic, &FRACTION))),
+            ("roughness", MaterialParam::Float(clamp_parameter(self.roughness, &FRACTION))),
+            ("ao", MaterialParam::Float(clamp_parameter(self.ao, &FRACTION))),
+            ("opacity", MaterialParam::Float(clamp_parameter(self.opacity, &FRACTION))),
+            ("alpha_mode", MaterialParam::Int(self.alpha_mode.shader_value())),
+            (
+                "alpha_cutoff",
+                MaterialParam::Float(clamp_parameter(self.alpha_mode.cutoff(), &FRACTION)),
+            ),
+            ("double_sided", MaterialParam::Int(self.double_sided as i32)),
+            (
+                "height_scale",
+                MaterialParam::Float(clamp_parameter(self.height_scale, &HEIGHT_SCALE)),
+            ),
+            ("parallax_steps", MaterialParam::Int(steps)),
+            ("parallax_shadows", MaterialParam::Int(self.parallax_shadows as i32)),
+            ("emissive", MaterialParam::Vec3(self.emissive.map(|c| clamp_parameter(c, &EMISSION)))),
+            (
+                "emissive_strength",
+                MaterialParam::Float(clamp_parameter(self.emissive_strength, &EMISSION)),
+            ),
+        ]
+    }
+
+    fn texture_slots(&self) -> Vec<(&str, &Texture)> {
+        vec![
+            ("diffuse_texture", &self.diffuse_texture),
+            ("normal_texture", &self.normal_texture),
+            ("arm_texture", &self.arm_texture),
+            ("emissive_texture", &self.emissive_texture),
+            ("ao_texture", &self.ao_texture),
+            ("roughness_texture", &self.roughness_texture),
+            ("metallic_texture", &self.metallic_texture),
+            ("height_texture", &self.height_texture),
+        ]
+    }
+
+    fn texture_slot_mut(&mut self, name: &str) -> Option<&mut Texture> {
+        match name {
+            "diffuse_texture" => Some(&mut self.diffuse_texture),
+            "normal_texture" => Some(&mut self.normal_texture),
+            "arm_texture" => Some(&mut self.arm_texture),
+            "emissive_texture" => Some(&mut self.emissive_texture),
+            "ao_texture" => Some(&mut self.ao_texture),
+            "roughness_texture" => Some(&mut self.roughness_texture),
+            "metallic_texture" => Some(&mut self.metallic_texture),
+            "height_texture" => Some(&mut self.height_texture),
+            _ => None,
         }
     }
 
-    fn toggle_map(&mut self, t: TextureType) {
-        // need to add a bool to the struct itself so we can easily toggle
-        match t {
-            TextureType::DIFFUSE => {
-                self.diffuse_texture.enabled = !self.diffuse_texture.enabled;
-            }
-            TextureType::NORMAL => {
-                self.normal_texture.enabled = !self.normal_texture.enabled;
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    fn double_sided(&self) -> bool {
+        self.double_sided
+    }
+}
+
+// a flat color and optional texture with no lighting, for ui panels, skies and markers
+pub struct Unlit {
+    pub color: Vector3<f32>,
+    // multiplied with the alpha of the texture when it is enabled
+    pub opacity: f32,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+    pub diffuse_texture: Texture,
+}
+
+impl Unlit {
+    pub fn new(color: Vector3<f32>) -> Unlit {
+        Unlit {
+            color,
+            opacity: 1.0,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+            diffuse_texture: Texture::new(),
+        }
+    }
+}
+
+impl Material for Unlit {
+    fn shader(&self) -> (&str, &str) {
+        ("blinn", "unlit")
+    }
+
+    fn parameters(&self) -> Vec<(&str, MaterialParam)> {
+        vec![
+            ("color", MaterialParam::Vec3(self.color)),
+            ("opacity", MaterialParam::Float(self.opacity)),
+        ]
+    }
+
+    fn texture_slots(&self) -> Vec<(&str, &Texture)> {
+        vec![("diffuse_texture", &self.diffuse_texture)]
+    }
+
+    fn texture_slot_mut(&mut self, name: &str) -> Option<&mut Texture> {
+        (name == "diffuse_texture").then_some(&mut self.diffuse_texture)
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    fn double_sided(&self) -> bool {
+        self.double_sided
+    }
+}
+
+// classic diffuse + specular highlight, lit by the directional light and every point light
+pub struct BlinnPhong {
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+    pub shininess: f32,
+    // fraction of the diffuse color shown where no light reaches
+    pub ambient: f32,
+    pub diffuse_texture: Texture,
+}
+
+impl BlinnPhong {
+    pub fn new(diffuse: Vector3<f32>, specular: Vector3<f32>, shininess: f32) -> BlinnPhong {
+        BlinnPhong {
+            diffuse,
+            specular,
+            shininess,
+            ambient: 0.05,
+            diffuse_texture: Texture::new(),
+        }
+    }
+}
+
+impl Material for BlinnPhong {
+    fn shader(&self) -> (&str, &str) {
+        ("blinn", "blinn")
+    }
+
+    fn parameters(&self) -> Vec<(&str, MaterialParam)> {
+        vec![
+            ("diffuse", MaterialParam::Vec3(self.diffuse)),
+            ("specular", MaterialParam::Vec3(self.specular)),
+            ("shininess", MaterialParam::Float(self.shininess)),
+            ("ambient", MaterialParam::Float(self.ambient)),
+        ]
+    }
+
+    fn texture_slots(&self) -> Vec<(&str, &Texture)> {
+        vec![("diffuse_texture", &self.diffuse_texture)]
+    }
+
+    fn texture_slot_mut(&mut self, name: &str) -> Option<&mut Texture> {
+        (name == "diffuse_texture").then_some(&mut self.diffuse_texture)
+    }
+}
+
+// cel shading: diffuse snapped to a few flat bands, a rim light and dark silhouette outlines
+pub struct Toon {
+    pub color: Vector3<f32>,
+    pub bands: i32,
+    pub rim_color: Vector3<f32>,
+    // higher keeps the rim closer to the edge
+    pub rim_power: f32,
+    pub outline_color: Vector3<f32>,
+    // fragments facing the camera less than this (n dot v) are drawn as outline, 0 turns it off
+    pub outline_width: f32,
+}
+
+impl Toon {
+    pub fn new(color: Vector3<f32>, bands: i32) -> Toon {
+        Toon {
+            color,
+            bands,
+            rim_color: Vector3::new(0.3, 0.3, 0.3),
+            rim_power: 4.0,
+            outline_color: Vector3::zeros(),
+            outline_width: 0.2,
+        }
+    }
+}
+
+impl Material for Toon {
+    fn shader(&self) -> (&str, &str) {
+        ("blinn", "toon")
+    }
+
+    fn parameters(&self) -> Vec<(&str, MaterialParam)> {
+        vec![
+            ("color", MaterialParam::Vec3(self.color)),
+            ("bands", MaterialParam::Int(self.bands)),
+            ("rim_color", MaterialParam::Vec3(self.rim_color)),
+            ("rim_power", MaterialParam::Float(self.rim_power)),
+            ("outline_color", MaterialParam::Vec3(self.outline_color)),
+            ("outline_width", MaterialParam::Float(self.outline_width)),
+        ]
+    }
+}
+
+// a uniform value set on a CustomMaterial
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaterialParam {
+    Int(i32),
+    Float(f32),
+    Vec2(Vector2<f32>),
+    Vec3(Vector3<f32>),
+    Vec4(Vector4<f32>),
+}
+
+impl MaterialParam {
+    // floats go in by their bits, two params hash the same only when they upload the same
+    fn hash_bits(&self, hasher: &mut impl Hasher) {
+        let floats: &[f32] = match self {
+            MaterialParam::Int(v) => {
+                (0, *v).hash(hasher);
+                return;
             }
-            TextureType::ARM => {
-                self.arm_texture.enabled = !self.arm_texture.enabled;
+            MaterialParam::Float(v) => std::slice::from_ref(v),
+            MaterialParam::Vec2(v) => v.as_slice(),
+            MaterialParam::Vec3(v) => v.as_slice(),
+            MaterialParam::Vec4(v) => v.as_slice(),
+        };
+        floats.len().hash(hasher);
+        for value in floats {
+            value.to_bits().hash(hasher);
+        }
+    }
+
+    fn link(&self, location: i32) {
+        unsafe {
+            match self {
+                MaterialParam::Int(v) => gl::Uniform1i(location, *v),
+                MaterialParam::Float(v) => gl::Uniform1f(location, *v),
+                MaterialParam::Vec2(v) => gl::Uniform2f(location, v.x, v.y),
+                MaterialParam::Vec3(v) => gl::Uniform3f(location, v.x, v.y, v.z),
+                MaterialParam::Vec4(v) => gl::Uniform4f(location, v.x, v.y, v.z, v.w),
             }
         }
     }
 }
+
+// a material described entirely by data: the shader to draw with, the material.<name> uniforms to
+// set on it and the textures it samples. new shaders can be used without another Material impl
+pub struct CustomMaterial {
+    pub vert: String,
+    pub frag: String,
+    pub params: HashMap<String, MaterialParam>,
+    // in unit order
+    pub textures: Vec<(String, Texture)>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+impl CustomMaterial {
+    pub fn new(vert: &str, frag: &str) -> CustomMaterial {
+        CustomMaterial {
+            vert: vert.to_string(),
+            frag: frag.to_string(),
+            params: HashMap::new(),
+            textures: Vec::new(),
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: MaterialParam) {
+        self.params.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<MaterialParam> {
+        self.params.get(name).copied()
+    }
+
+    // replaces the texture in a slot that already exists, otherwise declares a new one
+    pub fn set_texture(&mut self, name: &str, texture: Texture) {
+        match self.textures.iter_mut().find(|(slot, _)| slot == name) {
+            Some((_, existing)) => *existing = texture,
+            None => self.textures.push((name.to_string(), texture)),
+        }
+    }
+}
+
+impl Material for CustomMaterial {
+    fn shader(&self) -> (&str, &str) {
+        (&self.vert, &self.frag)
+    }
+
+    // in name order, so equal materials list them the same way
+    fn parameters(&self) -> Vec<(&str, MaterialParam)> {
+        let mut params: Vec<(&str, MaterialParam)> = self
+            .params
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+            .collect();
+        params.sort_by(|a, b| a.0.cmp(b.0));
+        params
+    }
+
+    fn texture_slots(&self) -> Vec<(&str, &Texture)> {
+        self.textures
+            .iter()
+            .map(|(name, texture)| (name.as_str(), texture))
+            .collect()
+    }
+
+    fn texture_slot_mut(&mut self, name: &str) -> Option<&mut Texture> {
+        self.textures
+            .iter_mut()
+            .find(|(slot, _)| slot == name)
+            .map(|(_, texture)| texture)
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    fn double_sided(&self) -> bool {
+        self.double_sided
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggles_declared_slots_only() {
+        let mut physical = Physical::default();
+        assert!(physical.toggle_map("arm_texture"));
+        assert!(physical.arm_texture.enabled);
+        assert!(physical.toggle_map("arm_texture"));
+        assert!(!physical.arm_texture.enabled);
+        assert!(!physical.toggle_map("detail_texture"));
+
+        let mut toon = Toon::new(Vector3::zeros(), 3);
+        assert!(toon.texture_slots().is_empty());
+        assert!(!toon.toggle_map("diffuse_texture"));
+    }
+
+    #[test]
+    fn slot_names_are_unique() {
+        let physical = Physical::default();
+        let mut names: Vec<&str> = physical.texture_slots().iter().map(|(n, _)| *n).collect();
+        let count = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), count);
+    }
+
+    #[test]
+    fn custom_material_params_and_slots() {
+        let mut custom = CustomMaterial::new("blinn", "water");
+        custom.set("speed", MaterialParam::Float(0.5));
+        custom.set("tint", MaterialParam::Vec3(Vector3::new(0.0, 0.3, 0.6)));
+        custom.set("speed", MaterialParam::Float(2.0));
+        assert_eq!(custom.get("speed"), Some(MaterialParam::Float(2.0)));
+        assert_eq!(custom.get("depth"), None);
+        assert_eq!(custom.shader(), ("blinn", "water"));
+
+        custom.set_texture("flow_texture", Texture::new());
+        custom.set_texture("foam_texture", Texture::new());
+        let mut replacement = Texture::new();
+        replacement.scale = 4.0;
+        custom.set_texture("flow_texture", replacement);
+
+        let slots = custom.texture_slots();
+        assert_eq!(slots.len(), 2);
+        assert_eq!(slots[0].0, "flow_texture");
+        assert_eq!(slots[0].1.scale, 4.0);
+
+        assert!(custom.toggle_map("foam_texture"));
+        assert!(custom.textures[1].1.enabled);
+    }
+
+    #[test]
+    fn equal_materials_share_a_batch_key() {
+        let key = |material: &dyn Material| batch_key(material);
+        assert_eq!(key(&Physical::default()), key(&Physical::default()));
+
+        let mut red = Physical::default();
+        red.albedo = Vector3::new(1.0, 0.0, 0.0);
+        assert_ne!(key(&red), key(&Physical::default()));
+
+        let mut textured = Physical::default();
+        textured.diffuse_texture.tex = Some(3);
+        assert_ne!(key(&textured), key(&Physical::default()));
+        let mut other_texture = Physical::default();
+        other_texture.diffuse_texture.tex = Some(4);
+        assert_ne!(key(&textured), key(&other_texture));
+
+        let mut banded = Toon::new(Vector3::zeros(), 3);
+        assert_eq!(key(&banded), key(&Toon::new(Vector3::zeros(), 3)));
+        banded.bands = 4;
+        assert_ne!(key(&banded), key(&Toon::new(Vector3::zeros(), 3)));
+
+        // the same values under another shader or name are another material
+        let mut water = CustomMaterial::new("blinn", "water");
+        water.set("speed", MaterialParam::Float(1.0));
+        water.set("depth", MaterialParam::Float(2.0));
+        let mut same = CustomMaterial::new("blinn", "water");
+        same.set("depth", MaterialParam::Float(2.0));
+        same.set("speed", MaterialParam::Float(1.0));
+        assert_eq!(key(&water), key(&same));
+        let mut lava = CustomMaterial::new("blinn", "lava");
+        lava.set("speed", MaterialParam::Float(1.0));
+        lava.set("depth", MaterialParam::Float(2.0));
+        assert_ne!(key(&water), key(&lava));
+        let mut renamed = CustomMaterial::new("blinn", "water");
+        renamed.set("speed", MaterialParam::Float(2.0));
+        renamed.set("depth", MaterialParam::Float(1.0));
+        assert_ne!(key(&water), key(&renamed));
+        assert_ne!(key(&Unlit::new(Vector3::zeros())), key(&Toon::new(Vector3::zeros(), 0)));
+    }
+
+    fn mtl(dissolve: Option<f32>) -> MtlData {
+        MtlData {
+            name: "test".to_string(),
+            diffuse: Some(Vector3::new(0.8, 0.2, 0.1)),
+            dissolve,
+            emissive: None,
+            diffuse_texture: None,
+        }
+    }
+
+    #[test]
+    fn mtl_dissolve_picks_the_alpha_mode() {
+        let glass = Physical::from_mtl(&mtl(Some(0.25)));
+        assert_eq!(glass.alpha_mode, AlphaMode::Blend);
+        assert_eq!(glass.opacity, 0.25);
+        assert!(glass.is_transparent());
+        assert_eq!(glass.albedo, Vector3::new(0.8, 0.2, 0.1));
+
+        let solid = Physical::from_mtl(&mtl(Some(1.0)));
+        assert_eq!(solid.alpha_mode, AlphaMode::Opaque);
+        assert_eq!(solid.opacity, 1.0);
+        assert!(!solid.is_transparent());
+
+        let unspecified = Physical::from_mtl(&mtl(None));
+        assert_eq!(unspecified.alpha_mode, AlphaMode::Opaque);
+
+        // out of range values from exporters are clamped
+        assert_eq!(Physical::from_mtl(&mtl(Some(-0.5))).opacity, 0.0);
+    }
+
+    #[test]
+    fn mtl_ke_becomes_emission() {
+        let glowing = MtlData {
+            emissive: Some(Vector3::new(4.0, 2.0, 0.0)),
+            ..mtl(None)
+        };
+        assert_eq!(Physical::from_mtl(&glowing).emissive, Vector3::new(4.0, 2.0, 0.0));
+        assert_eq!(Physical::from_mtl(&mtl(None)).emissive, Vector3::zeros());
+
+        let cube = crate::obj::parse_obj("resources/cube.obj").expect("unable to load cube");
+        assert_eq!(cube.materials[0].emissive, Some(Vector3::zeros()));
+    }
+
+    #[test]
+    fn obj_materials_come_from_their_mtl() {
+        let cube = crate::obj::parse_obj("resources/cube.obj").expect("unable to load cube");
+        assert_eq!(cube.materials[0].dissolve, Some(1.0));
+        let physical = Physical::from_obj(&cube);
+        assert_eq!(physical.alpha_mode, AlphaMode::Opaque);
+        assert_eq!(physical.albedo, Vector3::new(0.8, 0.8, 0.8));
+
+        // an empty .mtl, and none at all
+        let plane = crate::obj::parse_obj("resources/plane.obj").expect("unable to load plane");
+        assert_eq!(Physical::from_obj(&plane).albedo, Physical::default().albedo);
+        let teapot = crate::obj::parse_obj("resources/teapot.obj").expect("unable to load teapot");
+        assert!(teapot.materials.is_empty());
+        assert_eq!(Physical::from_obj(&teapot).albedo, Physical::default().albedo);
+    }
+
+    #[test]
+    fn builder_keeps_values_in_range() {
+        let physical = Physical::builder()
+            .albedo(Vector3::new(0.9, 0.1, 0.1))
+            .metallic(1.0)
+            .roughness(0.25)
+            .ao(1.0)
+            .emissive(Vector3::new(2.0, 1.0, 0.0))
+            .emissive_strength(8.0)
+            .alpha_mode(AlphaMode::Mask(0.3))
+            .build()
+            .unwrap();
+        assert_eq!(physical.metallic, 1.0);
+        assert_eq!(physical.roughness, 0.25);
+        assert_eq!(physical.emissive, Vector3::new(2.0, 1.0, 0.0));
+        assert_eq!(physical.alpha_mode, AlphaMode::Mask(0.3));
+    }
+
+    #[test]
+    fn builder_rejects_or_clamps_out_of_range_values() {
+        let metal = || Physical::builder().metallic(7.0).roughness(17.1);
+
+        // tests run with debug assertions, where build reports the first bad value
+        if cfg!(debug_assertions) {
+            assert_eq!(
+                metal().build().err(),
+                Some(ParameterError {
+                    name: "metallic",
+                    value: 7.0,
+                    min: 0.0,
+                    max: 1.0
+                })
+            );
+            assert_eq!(
+                Physical::builder().albedo(Vector3::new(0.5, -0.1, 0.5)).build().err().map(|e| e.name),
+                Some("albedo.g")
+            );
+            assert_eq!(
+                Physical::builder().emissive_strength(-1.0).build().err().map(|e| e.name),
+                Some("emissive_strength")
+            );
+            assert_eq!(
+                Physical::builder().parallax_steps(0).build().err().map(|e| e.name),
+                Some("parallax_steps")
+            );
+            assert!(Physical::builder().roughness(f32::NAN).build().is_err());
+        }
+
+        let clamped = metal().ao(1.5).build_clamped();
+        assert_eq!(clamped.metallic, 1.0);
+        assert_eq!(clamped.roughness, 1.0);
+        assert_eq!(clamped.ao, 1.0);
+        assert_eq!(Physical::builder().roughness(f32::NAN).build_clamped().roughness, 0.0);
+        assert_eq!(
+            Physical::builder().alpha_mode(AlphaMode::Mask(2.0)).build_clamped().alpha_mode,
+            AlphaMode::Mask(1.0)
+        );
+    }
+
+    #[test]
+    fn edits_out_of_range_are_clamped_when_linked() {
+        let mut physical = Physical::default();
+        physical.metallic = 7.0;
+        physical.roughness = f32::NAN;
+        physical.albedo = Vector3::new(2.0, 0.5, -1.0);
+        physical.parallax_steps = 1000;
+
+        let parameters: HashMap<&str, MaterialParam> = physical.parameters().into_iter().collect();
+        assert_eq!(parameters["metallic"], MaterialParam::Float(1.0));
+        assert_eq!(parameters["roughness"], MaterialParam::Float(0.0));
+        assert_eq!(parameters["albedo"], MaterialParam::Vec3(Vector3::new(1.0, 0.5, 0.0)));
+        assert_eq!(parameters["parallax_steps"], MaterialParam::Int(128));
+    }
+}
@@ -0,0 +1,841 @@
+// physical materials described in small text files instead of magic numbers in scene code.
+// a file is a list of `key = value` lines, textures get their own [slot] section named after the
+// material's texture slot, and `base = <name>` pulls in every value from another material first:
+//
+//     # ground.mat
+//     base = rock
+//     albedo = 0.8 0.6 0.0
+//     roughness = 0.9
+//     alpha = mask 0.5
+//
+//     [diffuse_texture]
+//     path = resources/grey-rocks.png
+//     scale = 25
+//     wrap = repeat
+//     filter = linear
+//
+// a MaterialLibrary loads every .mat file in a directory by file name and picks up edits to them
+// while the game is running
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::material::{AlphaMode, Material, ParameterError, Physical};
+use crate::obj::parse_vector;
+use crate::texture::{self, Filter, Sampler, Wrap};
+
+pub const MATERIAL_EXTENSION: &str = "mat";
+
+// how often the directory is checked for edited files
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaterialFileError {
+    Io(String),
+    Syntax { line: usize, message: String },
+    InvalidValue { key: String, value: String },
+    UnknownKey(String),
+    UnknownTextureSlot(String),
+    MissingTexturePath(String),
+    // pbr samples every slot as a sampler2D, cubemaps and arrays can't go in one
+    NotA2dTexture { slot: String, path: String },
+    // the texture file is missing or can't be decoded
+    Texture { slot: String, path: String, message: String },
+    UnknownMaterial(String),
+    UnknownBase(String),
+    CircularBase(String),
+    OutOfRange(ParameterError),
+}
+
+impl fmt::Display for MaterialFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MaterialFileError::Io(message) => write!(f, "{}", message),
+            MaterialFileError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            MaterialFileError::InvalidValue { key, value } => {
+                write!(f, "invalid value '{}' for '{}'", value, key)
+            }
+            MaterialFileError::UnknownKey(key) => write!(f, "unknown key '{}'", key),
+            MaterialFileError::UnknownTextureSlot(slot) => {
+                write!(f, "material has no texture slot '{}'", slot)
+            }
+            MaterialFileError::MissingTexturePath(slot) => write!(f, "[{}] has no path", slot),
+            MaterialFileError::NotA2dTexture { slot, path } => {
+                write!(f, "[{}] needs a 2d texture, {} is a cubemap or array", slot, path)
+            }
+            MaterialFileError::Texture { slot, path, message } => {
+                write!(f, "[{}] failed to load {}: {}", slot, path, message)
+            }
+            MaterialFileError::UnknownMaterial(name) => write!(f, "no material named '{}'", name),
+            MaterialFileError::UnknownBase(name) => write!(f, "base material '{}' not found", name),
+            MaterialFileError::CircularBase(name) => {
+                write!(f, "material '{}' inherits from itself", name)
+            }
+            MaterialFileError::OutOfRange(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+// a texture section once its values have been parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureDesc {
+    pub path: String,
+    pub scale: f32,
+    pub enabled: bool,
+    pub sampler: Sampler,
+}
+
+// the raw contents of one file, values are only interpreted when a material is built so a base
+// and the files inheriting from it can be merged key by key
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaterialFile {
+    pub base: Option<String>,
+    pub properties: BTreeMap<String, String>,
+    pub textures: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl MaterialFile {
+    pub fn parse(source: &str) -> Result<MaterialFile, MaterialFileError> {
+        let mut file = MaterialFile::default();
+        let mut section: Option<String> = None;
+
+        for (index, raw) in source.lines().enumerate() {
+            let line = index + 1;
+            let syntax = |message: &str| MaterialFileError::Syntax {
+                line,
+                message: message.to_string(),
+            };
+
+            let content = match raw.find('#') {
+                Some(comment) => &raw[..comment],
+                None => raw,
+            }
+            .trim();
+            if content.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = content.strip_prefix('[') {
+                let name = header
+                    .strip_suffix(']')
+                    .ok_or_else(|| syntax("expected ] after the section name"))?
+                    .trim();
+                if name.is_empty() {
+                    return Err(syntax("empty section name"));
+                }
+                file.textures.entry(name.to_string()).or_default();
+                section = Some(name.to_string());
+                continue;
+            }
+
+            let (key, value) = content
+                .split_once('=')
+                .ok_or_else(|| syntax("expected key = value"))?;
+            let (key, value) = (key.trim(), value.trim());
+            if key.is_empty() {
+                return Err(syntax("missing key before ="));
+            }
+
+            match &section {
+                Some(slot) => {
+                    file.textures
+                        .get_mut(slot)
+                        .unwrap()
+                        .insert(key.to_string(), value.to_string());
+                }
+                None if key == "base" => file.base = Some(value.to_string()),
+                None => {
+                    file.properties.insert(key.to_string(), value.to_string());
+                }
+            }
+        }
+
+        Ok(file)
+    }
+
+    // this file's values layered over a base, the result has no base of its own
+    fn inherit(&self, base: &MaterialFile) -> MaterialFile {
+        let mut merged = base.clone();
+        merged.base = None;
+        for (key, value) in &self.properties {
+            merged.properties.insert(key.clone(), value.clone());
+        }
+        for (slot, values) in &self.textures {
+            let merged_slot = merged.textures.entry(slot.clone()).or_default();
+            for (key, value) in values {
+                merged_slot.insert(key.clone(), value.clone());
+            }
+        }
+        merged
+    }
+
+    pub fn texture_descs(&self) -> Result<Vec<(String, TextureDesc)>, MaterialFileError> {
+        self.textures
+            .iter()
+            .map(|(slot, values)| Ok((slot.clone(), parse_texture(slot, values)?)))
+            .collect()
+    }
+
+    // everything except loading the textures themselves, see MaterialLibrary::build
+    // values go through PhysicalBuilder, so out of range numbers are errors in debug builds
+    pub fn physical(&self) -> Result<Physical, MaterialFileError> {
+        let mut builder = Physical::builder();
+
+        for (key, value) in &self.properties {
+            let invalid = || MaterialFileError::InvalidValue {
+                key: key.clone(),
+                value: value.clone(),
+            };
+            let number = || value.parse::<f32>().map_err(|_| invalid());
+            builder = match key.as_str() {
+                "albedo" => builder.albedo(parse_vector(value).ok_or_else(invalid)?),
+                "metallic" => builder.metallic(number()?),
+                "roughness" => builder.roughness(number()?),
+                "ao" => builder.ao(number()?),
+                "opacity" => builder.opacity(number()?),
+                "alpha" => builder.alpha_mode(parse_alpha_mode(value).ok_or_else(invalid)?),
+                "double_sided" => builder.double_sided(parse_bool(value).ok_or_else(invalid)?),
+                "emissive" => builder.emissive(parse_vector(value).ok_or_else(invalid)?),
+                "emissive_strength" => builder.emissive_strength(number()?),
+                "height_scale" => builder.height_scale(number()?),
+                "parallax_steps" => {
+                    builder.parallax_steps(value.parse::<i32>().map_err(|_| invalid())?)
+                }
+                "parallax_shadows" => {
+                    builder.parallax_shadows(parse_bool(value).ok_or_else(invalid)?)
+                }
+                _ => return Err(MaterialFileError::UnknownKey(key.clone())),
+            };
+        }
+        let mut physical = builder.build().map_err(MaterialFileError::OutOfRange)?;
+
+        for (slot, desc) in self.texture_descs()? {
+            let texture = physical
+                .texture_slot_mut(&slot)
+                .ok_or_else(|| MaterialFileError::UnknownTextureSlot(slot.clone()))?;
+            texture.scale = desc.scale;
+            texture.enabled = desc.enabled;
+        }
+
+        Ok(physical)
+    }
+}
+
+fn parse_texture(
+    slot: &str,
+    values: &BTreeMap<String, String>,
+) -> Result<TextureDesc, MaterialFileError> {
+    let mut desc = TextureDesc {
+        path: String::new(),
+        scale: 1.0,
+        enabled: true,
+        sampler: Sampler::default(),
+    };
+
+    for (key, value) in values {
+        let invalid = || MaterialFileError::InvalidValue {
+            key: format!("{}.{}", slot, key),
+            value: value.clone(),
+        };
+        match key.as_str() {
+            "path" => desc.path = value.clone(),
+            "scale" => desc.scale = value.parse().map_err(|_| invalid())?,
+            "enabled" => desc.enabled = parse_bool(value).ok_or_else(invalid)?,
+            "wrap" => {
+                desc.sampler.wrap = match value.as_str() {
+                    "repeat" => Wrap::Repeat,
+                    "clamp" => Wrap::Clamp,
+                    "mirror" => Wrap::Mirror,
+                    _ => return Err(invalid()),
+                }
+            }
+            "filter" => {
+                desc.sampler.filter = match value.as_str() {
+                    "linear" => Filter::Linear,
+                    "nearest" => Filter::Nearest,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(MaterialFileError::UnknownKey(format!("{}.{}", slot, key))),
+        }
+    }
+
+    if desc.path.is_empty() {
+        return Err(MaterialFileError::MissingTexturePath(slot.to_string()));
+    }
+    Ok(desc)
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "yes" | "1" => Some(true),
+        "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+// opaque, blend, additive or mask with an optional cutoff (0.5 when left out)
+fn parse_alpha_mode(value: &str) -> Option<AlphaMode> {
+    let mut parts = value.split_whitespace();
+    let mode = match parts.next()? {
+        "opaque" => AlphaMode::Opaque,
+        "blend" => AlphaMode::Blend,
+        "additive" => AlphaMode::Additive,
+        "mask" => AlphaMode::Mask(match parts.next() {
+            Some(cutoff) => cutoff.parse().ok()?,
+            None => 0.5,
+        }),
+        _ => return None,
+    };
+    parts.next().is_none().then_some(mode)
+}
+
+pub struct MaterialLibrary {
+    directory: Option<PathBuf>,
+    files: HashMap<String, MaterialFile>,
+    modified: HashMap<String, SystemTime>,
+    // textures are shared between materials and survive reloads
+    textures: HashMap<(String, Sampler), (u32, u32)>,
+    last_poll: Instant,
+}
+
+impl MaterialLibrary {
+    pub fn new() -> MaterialLibrary {
+        MaterialLibrary {
+            directory: None,
+            files: HashMap::new(),
+            modified: HashMap::new(),
+            textures: HashMap::new(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    // every .mat file in the directory, named after the file without its extension. files that
+    // fail to parse are left out and returned with their errors, an edit brings them back in
+    pub fn load(
+        directory: &str,
+    ) -> Result<(MaterialLibrary, Vec<(String, MaterialFileError)>), MaterialFileError> {
+        let mut library = MaterialLibrary::new();
+        library.directory = Some(PathBuf::from(directory));
+
+        let mut failed = Vec::new();
+        for path in material_paths(Path::new(directory))? {
+            match read_material(&path) {
+                Ok((name, file, modified)) => {
+                    library.files.insert(name.clone(), file);
+                    library.modified.insert(name, modified);
+                }
+                Err(err) => {
+                    let name = material_name(&path);
+                    if let Ok(modified) = fs::metadata(&path).and_then(|m| m.modified()) {
+                        library.modified.insert(name.clone(), modified);
+                    }
+                    failed.push((name, err));
+                }
+            }
+        }
+
+        Ok((library, failed))
+    }
+
+    pub fn insert(&mut self, name: &str, file: MaterialFile) {
+        self.files.insert(name.to_string(), file);
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.files.keys().map(|n| n.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    // the material's names from itself up to the root of its bases
+    fn chain(&self, name: &str) -> Result<Vec<&str>, MaterialFileError> {
+        let mut chain: Vec<&str> = Vec::new();
+        let mut current = name;
+        loop {
+            if chain.contains(&current) {
+                return Err(MaterialFileError::CircularBase(name.to_string()));
+            }
+            let (key, file) = self.files.get_key_value(current).ok_or_else(|| {
+                if chain.is_empty() {
+                    MaterialFileError::UnknownMaterial(current.to_string())
+                } else {
+                    MaterialFileError::UnknownBase(current.to_string())
+                }
+            })?;
+            chain.push(key.as_str());
+            match &file.base {
+                Some(base) => current = base,
+                None => return Ok(chain),
+            }
+        }
+    }
+
+    // the file with every base merged in
+    pub fn resolve(&self, name: &str) -> Result<MaterialFile, MaterialFileError> {
+        let chain = self.chain(name)?;
+        Ok(chain
+            .iter()
+            .rev()
+            .fold(MaterialFile::default(), |merged, link| {
+                self.files[*link].inherit(&merged)
+            }))
+    }
+
+    // builds the material and loads any textures it needs that aren't loaded yet
+    pub fn build(&mut self, name: &str) -> Result<Physical, MaterialFileError> {
+        let file = self.resolve(name)?;
+        let mut physical = file.physical()?;
+
+        for (slot, desc) in file.texture_descs()? {
+            // failed loads aren't cached, so fixing the path and saving the file tries again
+            let key = (desc.path.clone(), desc.sampler);
+            let (tex, target) = match self.textures.get(&key) {
+                Some(loaded) => *loaded,
+                None => {
+                    let loaded = unsafe { texture::load_texture_file(&desc.path, desc.sampler) }
+                        .map_err(|message| MaterialFileError::Texture {
+                            slot: slot.clone(),
+                            path: desc.path.clone(),
+                            message,
+                        })?;
+                    self.textures.insert(key, loaded);
+                    loaded
+                }
+            };
+            if target != gl::TEXTURE_2D {
+                return Err(MaterialFileError::NotA2dTexture {
+                    slot,
+                    path: desc.path,
+                });
+            }
+            let texture = physical.texture_slot_mut(&slot).unwrap();
+            texture.tex = Some(tex);
+            texture.target = target;
+        }
+
+        Ok(physical)
+    }
+
+    // names of the materials that inherit from any of the given ones, including those themselves
+    pub fn dependants(&self, changed: &[String]) -> Vec<String> {
+        let mut dependants: Vec<String> = self
+            .files
+            .keys()
+            .filter(|name| match self.chain(name) {
+                Ok(chain) => chain.iter().any(|link| changed.iter().any(|c| c == link)),
+                Err(_) => changed.contains(name),
+            })
+            .cloned()
+            .collect();
+        dependants.sort();
+        dependants
+    }
+
+    // re-reads files that were edited or added since they were last read, at most every
+    // POLL_INTERVAL. a file that fails to parse keeps its previous contents and is returned in
+    // failed with its error
+    pub fn poll_changes(&mut self) -> MaterialChanges {
+        let mut changes = MaterialChanges::default();
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return changes;
+        }
+        self.last_poll = Instant::now();
+
+        let Some(directory) = self.directory.clone() else {
+            return changes;
+        };
+        let Ok(paths) = material_paths(&directory) else {
+            return changes;
+        };
+
+        let mut changed = Vec::new();
+        for path in paths {
+            let name = material_name(&path);
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            if modified.is_none() || modified.as_ref() == self.modified.get(&name) {
+                continue;
+            }
+
+            match read_material(&path) {
+                Ok((name, file, modified)) => {
+                    self.files.insert(name.clone(), file);
+                    self.modified.insert(name.clone(), modified);
+                    changed.push(name);
+                }
+                Err(err) => {
+                    self.modified.insert(name.clone(), modified.unwrap());
+                    changes.failed.push((name, err));
+                }
+            }
+        }
+
+        if !changed.is_empty() {
+            changes.rebuild = self.dependants(&changed);
+        }
+        changes
+    }
+}
+
+// what a poll found: the materials that need rebuilding and the files that failed to parse
+#[derive(Debug, Default)]
+pub struct MaterialChanges {
+    pub rebuild: Vec<String>,
+    pub failed: Vec<(String, MaterialFileError)>,
+}
+
+fn material_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn material_paths(directory: &Path) -> Result<Vec<PathBuf>, MaterialFileError> {
+    let entries = fs::read_dir(directory)
+        .map_err(|err| MaterialFileError::Io(format!("{}: {}", directory.display(), err)))?;
+    Ok(entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == MATERIAL_EXTENSION))
+        .collect())
+}
+
+fn read_material(path: &Path) -> Result<(String, MaterialFile, SystemTime), MaterialFileError> {
+    let io_error = |err: std::io::Error| MaterialFileError::Io(format!("{}: {}", path.display(), err));
+    let source = fs::read_to_string(path).map_err(io_error)?;
+    let modified = fs::metadata(path).and_then(|m| m.modified()).map_err(io_error)?;
+    let file = MaterialFile::parse(&source).map_err(|err| match err {
+        MaterialFileError::Syntax { line, message } => MaterialFileError::Syntax {
+            line,
+            message: format!("{}: {}", path.display(), message),
+        },
+        err => err,
+    })?;
+    Ok((material_name(path), file, modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Vector3;
+
+    const GROUND: &str = "
+        # the ground everything stands on
+        albedo = 0.8 0.6 0.0
+        metallic = 0   # not a metal
+        roughness=0.9
+        height_scale = 0.04
+        parallax_steps = 32
+        parallax_shadows = yes
+
+        [diffuse_texture]
+        path = resources/grey-rocks.png
+        scale = 25
+        wrap = mirror
+        filter = nearest
+
+        [arm_texture]
+        path = resources/grey-rocks-arm.png
+        enabled = false
+    ";
+
+    #[test]
+    fn parses_properties_sections_and_comments() {
+        let file = MaterialFile::parse(GROUND).unwrap();
+        assert_eq!(file.base, None);
+        assert_eq!(file.properties["albedo"], "0.8 0.6 0.0");
+        assert_eq!(file.properties["metallic"], "0");
+        assert_eq!(file.properties["roughness"], "0.9");
+        assert_eq!(file.textures.len(), 2);
+        assert_eq!(file.textures["diffuse_texture"]["scale"], "25");
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_line_numbers() {
+        let missing_equals = MaterialFile::parse("albedo = 1 1 1\nmetallic 0.5\n");
+        assert!(matches!(
+            missing_equals,
+            Err(MaterialFileError::Syntax { line: 2, .. })
+        ));
+
+        let open_section = MaterialFile::parse("\n\n[diffuse_texture\n");
+        assert!(matches!(
+            open_section,
+            Err(MaterialFileError::Syntax { line: 3, .. })
+        ));
+
+        assert!(MaterialFile::parse(" = 4").is_err());
+        assert!(MaterialFile::parse("[ ]").is_err());
+    }
+
+    #[test]
+    fn builds_a_physical_material() {
+        let physical = MaterialFile::parse(GROUND).unwrap().physical().unwrap();
+        assert_eq!(physical.albedo, Vector3::new(0.8, 0.6, 0.0));
+        assert_eq!(physical.metallic, 0.0);
+        assert_eq!(physical.roughness, 0.9);
+        assert!(physical.diffuse_texture.enabled);
+        assert_eq!(physical.diffuse_texture.scale, 25.0);
+        assert!(!physical.arm_texture.enabled);
+        assert_eq!(physical.height_scale, 0.04);
+        assert_eq!(physical.parallax_steps, 32);
+        assert!(physical.parallax_shadows);
+        // textures are only loaded through the library
+        assert_eq!(physical.diffuse_texture.tex, None);
+
+        let descs = MaterialFile::parse(GROUND).unwrap().texture_descs().unwrap();
+        let diffuse = &descs.iter().find(|(slot, _)| slot == "diffuse_texture").unwrap().1;
+        assert_eq!(diffuse.path, "resources/grey-rocks.png");
+        assert_eq!(diffuse.sampler.wrap, Wrap::Mirror);
+        assert_eq!(diffuse.sampler.filter, Filter::Nearest);
+    }
+
+    #[test]
+    fn material_slots_take_only_2d_textures() {
+        let mut library = library(&[
+            ("sky", "[diffuse_texture]\npath = sky.dds"),
+            ("rocks", "[diffuse_texture]\npath = rocks.ktx2"),
+        ]);
+        // stands in for what load_texture_file gave back, so no gl is needed
+        let sampler = Sampler::default();
+        library.textures.insert(("sky.dds".to_string(), sampler), (1, gl::TEXTURE_CUBE_MAP));
+        library.textures.insert(("rocks.ktx2".to_string(), sampler), (2, gl::TEXTURE_2D));
+
+        assert_eq!(
+            library.build("sky").err(),
+            Some(MaterialFileError::NotA2dTexture {
+                slot: "diffuse_texture".to_string(),
+                path: "sky.dds".to_string()
+            })
+        );
+        assert_eq!(library.build("rocks").unwrap().diffuse_texture.tex, Some(2));
+    }
+
+    #[test]
+    fn missing_texture_files_are_errors() {
+        let mut library = library(&[
+            ("image", "[diffuse_texture]\npath = resources/missing.png"),
+            ("container", "[normal_texture]\npath = resources/missing.dds"),
+        ]);
+
+        for (name, slot, path) in [
+            ("image", "diffuse_texture", "resources/missing.png"),
+            ("container", "normal_texture", "resources/missing.dds"),
+        ] {
+            assert!(matches!(
+                library.build(name),
+                Err(MaterialFileError::Texture { slot: s, path: p, .. }) if s == slot && p == path
+            ));
+        }
+        assert!(library.textures.is_empty());
+    }
+
+    #[test]
+    fn parses_alpha_modes() {
+        let alpha = |value: &str| {
+            MaterialFile::parse(&format!("alpha = {}", value))
+                .unwrap()
+                .physical()
+                .map(|p| p.alpha_mode)
+        };
+        assert_eq!(alpha("opaque"), Ok(AlphaMode::Opaque));
+        assert_eq!(alpha("blend"), Ok(AlphaMode::Blend));
+        assert_eq!(alpha("additive"), Ok(AlphaMode::Additive));
+        assert_eq!(alpha("mask"), Ok(AlphaMode::Mask(0.5)));
+        assert_eq!(alpha("mask 0.25"), Ok(AlphaMode::Mask(0.25)));
+        assert!(alpha("mask half").is_err());
+        assert!(alpha("blend 0.3").is_err());
+        assert!(alpha("glass").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_values_and_unknown_keys() {
+        let build = |source: &str| MaterialFile::parse(source).unwrap().physical();
+        assert_eq!(
+            build("albedo = 1 0").err(),
+            Some(MaterialFileError::InvalidValue {
+                key: "albedo".to_string(),
+                value: "1 0".to_string()
+            })
+        );
+        if cfg!(debug_assertions) {
+            assert!(matches!(
+                build("roughness = 150.1").err(),
+                Some(MaterialFileError::OutOfRange(ParameterError {
+                    name: "roughness",
+                    ..
+                }))
+            ));
+        }
+        assert_eq!(
+            build("shininess = 3").err(),
+            Some(MaterialFileError::UnknownKey("shininess".to_string()))
+        );
+        assert_eq!(
+            build("double_sided = maybe").err(),
+            Some(MaterialFileError::InvalidValue {
+                key: "double_sided".to_string(),
+                value: "maybe".to_string()
+            })
+        );
+        assert_eq!(
+            build("[detail_texture]\npath = a.png").err(),
+            Some(MaterialFileError::UnknownTextureSlot("detail_texture".to_string()))
+        );
+        assert_eq!(
+            build("[diffuse_texture]\nscale = 2").err(),
+            Some(MaterialFileError::MissingTexturePath("diffuse_texture".to_string()))
+        );
+        assert_eq!(
+            build("[diffuse_texture]\npath = a.png\nwrap = tile").err(),
+            Some(MaterialFileError::InvalidValue {
+                key: "diffuse_texture.wrap".to_string(),
+                value: "tile".to_string()
+            })
+        );
+    }
+
+    fn library(files: &[(&str, &str)]) -> MaterialLibrary {
+        let mut library = MaterialLibrary::new();
+        for (name, source) in files {
+            library.insert(name, MaterialFile::parse(source).unwrap());
+        }
+        library
+    }
+
+    #[test]
+    fn inherits_from_base_materials() {
+        let library = library(&[
+            (
+                "metal",
+                "metallic = 1\nroughness = 0.3\n[arm_texture]\npath = metal-arm.png\nscale = 2",
+            ),
+            ("gold", "base = metal\nalbedo = 1.0 0.8 0.3"),
+            (
+                "scratched_gold",
+                "base = gold\nroughness = 0.6\n[arm_texture]\nscale = 8",
+            ),
+        ]);
+
+        let gold = library.resolve("gold").unwrap().physical().unwrap();
+        assert_eq!(gold.metallic, 1.0);
+        assert_eq!(gold.roughness, 0.3);
+        assert_eq!(gold.albedo, Vector3::new(1.0, 0.8, 0.3));
+
+        // texture sections merge key by key, the path comes from the root
+        let scratched = library.resolve("scratched_gold").unwrap();
+        assert_eq!(scratched.base, None);
+        assert_eq!(scratched.textures["arm_texture"]["path"], "metal-arm.png");
+        assert_eq!(scratched.textures["arm_texture"]["scale"], "8");
+        let scratched = scratched.physical().unwrap();
+        assert_eq!(scratched.roughness, 0.6);
+        assert_eq!(scratched.albedo, Vector3::new(1.0, 0.8, 0.3));
+        assert_eq!(scratched.arm_texture.scale, 8.0);
+    }
+
+    #[test]
+    fn missing_and_circular_bases() {
+        let library = library(&[
+            ("orphan", "base = nowhere"),
+            ("a", "base = b"),
+            ("b", "base = a"),
+        ]);
+        assert_eq!(
+            library.resolve("orphan"),
+            Err(MaterialFileError::UnknownBase("nowhere".to_string()))
+        );
+        assert_eq!(
+            library.resolve("a"),
+            Err(MaterialFileError::CircularBase("a".to_string()))
+        );
+        assert_eq!(
+            library.resolve("missing"),
+            Err(MaterialFileError::UnknownMaterial("missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn changes_propagate_to_dependants() {
+        let library = library(&[
+            ("metal", "metallic = 1"),
+            ("gold", "base = metal"),
+            ("scratched_gold", "base = gold"),
+            ("plastic", "metallic = 0"),
+        ]);
+        assert_eq!(
+            library.dependants(&["gold".to_string()]),
+            vec!["gold", "scratched_gold"]
+        );
+        assert_eq!(
+            library.dependants(&["metal".to_string()]),
+            vec!["gold", "metal", "scratched_gold"]
+        );
+        assert_eq!(library.dependants(&["plastic".to_string()]), vec!["plastic"]);
+    }
+
+    #[test]
+    fn loads_and_reloads_a_directory() {
+        let directory = std::env::temp_dir().join(format!("materials-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("base.mat"), "roughness = 0.5").unwrap();
+        fs::write(directory.join("child.mat"), "base = base\nmetallic = 1").unwrap();
+        fs::write(directory.join("notes.txt"), "not a material").unwrap();
+
+        let (mut library, failed) = MaterialLibrary::load(directory.to_str().unwrap()).unwrap();
+        assert!(failed.is_empty());
+        assert_eq!(library.names(), vec!["base", "child"]);
+        assert_eq!(library.resolve("child").unwrap().physical().unwrap().roughness, 0.5);
+
+        // nothing changed
+        library.last_poll -= POLL_INTERVAL;
+        assert!(library.poll_changes().rebuild.is_empty());
+
+        // an edit to the base rebuilds both; the mtime is forced forward since some filesystems
+        // only keep whole seconds
+        fs::write(directory.join("base.mat"), "roughness = 0.8").unwrap();
+        library.modified.insert("base".to_string(), SystemTime::UNIX_EPOCH);
+        library.last_poll -= POLL_INTERVAL;
+        assert_eq!(library.poll_changes().rebuild, vec!["base", "child"]);
+        assert_eq!(library.resolve("child").unwrap().physical().unwrap().roughness, 0.8);
+
+        // a broken edit keeps the old contents
+        fs::write(directory.join("base.mat"), "roughness 0.1").unwrap();
+        library.modified.insert("base".to_string(), SystemTime::UNIX_EPOCH);
+        library.last_poll -= POLL_INTERVAL;
+        let changes = library.poll_changes();
+        assert!(changes.rebuild.is_empty());
+        assert_eq!(changes.failed.len(), 1);
+        assert_eq!(changes.failed[0].0, "base");
+        assert!(matches!(changes.failed[0].1, MaterialFileError::Syntax { .. }));
+        assert_eq!(library.resolve("base").unwrap().physical().unwrap().roughness, 0.8);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn load_skips_broken_files() {
+        let directory =
+            std::env::temp_dir().join(format!("broken-materials-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("good.mat"), "roughness = 0.5").unwrap();
+        fs::write(directory.join("broken.mat"), "roughness 0.5").unwrap();
+
+        let (mut library, failed) = MaterialLibrary::load(directory.to_str().unwrap()).unwrap();
+        assert_eq!(library.names(), vec!["good"]);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, "broken");
+
+        // fixing the file brings it in on the next poll
+        fs::write(directory.join("broken.mat"), "roughness = 0.25").unwrap();
+        library.modified.insert("broken".to_string(), SystemTime::UNIX_EPOCH);
+        library.last_poll -= POLL_INTERVAL;
+        let changes = library.poll_changes();
+        assert!(changes.failed.is_empty());
+        assert_eq!(changes.rebuild, vec!["broken"]);
+        assert_eq!(library.resolve("broken").unwrap().physical().unwrap().roughness, 0.25);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,631 @@
+// cpu side mesh operations on ObjData.
+// every function here treats `indices` as triangles indexing into the attribute arrays, so they
+// work on both the flat lists from parse_obj/primitives and the indexed output of weld/simplify.
+// the renderer draws flat lists, so run indexed meshes through `expand` before building an Object
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use nalgebra::{Matrix3, Matrix4, Point3, Vector2, Vector3, Vector4};
+
+use crate::collision::get_bounding_box;
+use crate::obj::ObjData;
+
+pub fn triangle_count(data: &ObjData) -> usize {
+    triangles(data).len()
+}
+
+fn triangles(data: &ObjData) -> Vec<[u32; 3]> {
+    if data.indices.is_empty() {
+        return (0..data.vertices.len() as u32 / 3)
+            .map(|t| [t * 3, t * 3 + 1, t * 3 + 2])
+            .collect();
+    }
+
+    data.indices
+        .chunks_exact(3)
+        .map(|tri| [tri[0], tri[1], tri[2]])
+        .collect()
+}
+
+pub fn transform(data: &ObjData, matrix: &Matrix4<f32>) -> ObjData {
+    let linear: Matrix3<f32> = matrix.fixed_view::<3, 3>(0, 0).into();
+    let normal_matrix = linear
+        .try_inverse()
+        .map(|inv| inv.transpose())
+        .unwrap_or(linear);
+
+    let mut result = data.clone();
+    for v in result.vertices.iter_mut() {
+        *v = matrix.transform_point(&Point3::from(*v)).coords;
+    }
+    for n in result.normals.iter_mut() {
+        *n = (normal_matrix * *n).try_normalize(f32::EPSILON).unwrap_or(*n);
+    }
+    for t in result.tangents.iter_mut() {
+        *t = (linear * *t).try_normalize(f32::EPSILON).unwrap_or(*t);
+    }
+
+    // mirroring turns the triangles inside out, swap two corners to keep them counter-clockwise
+    if linear.determinant() < 0.0 {
+        result.indices = triangles(data)
+            .iter()
+            .flat_map(|&[a, b, c]| [a, c, b])
+            .collect();
+    }
+
+    result
+}
+
+pub fn merge(meshes: &[&ObjData]) -> ObjData {
+    let has_normals = meshes.iter().any(|m| !m.normals.is_empty());
+    let has_uvs = meshes.iter().any(|m| !m.tex_coords.is_empty());
+    let has_tangents = meshes.iter().any(|m| !m.tangents.is_empty());
+
+    let mut result = ObjData::new();
+    for mesh in meshes {
+        let offset = result.vertices.len() as u32;
+        let count = mesh.vertices.len();
+
+        result.vertices.extend_from_slice(&mesh.vertices);
+        if has_normals {
+            result.normals.extend(padded(&mesh.normals, count, Vector3::y()));
+        }
+        if has_uvs {
+            result.tex_coords.extend(padded(&mesh.tex_coords, count, Vector2::zeros()));
+        }
+        if has_tangents {
+            result.tangents.extend(padded(&mesh.tangents, count, Vector3::x()));
+        }
+        result.indices.extend(triangles(mesh).iter().flatten().map(|i| i + offset));
+    }
+
+    result
+}
+
+// meshes without an attribute get a default for it so the merged arrays stay the same length
+fn padded<T: Copy>(values: &[T], count: usize, default: T) -> impl Iterator<Item = T> + '_ {
+    (0..count).map(move |i| values.get(i).copied().unwrap_or(default))
+}
+
+// expands an indexed mesh back into the flat triangle list the render buffers expect
+pub fn expand(data: &ObjData) -> ObjData {
+    let mut result = ObjData::new();
+    for i in triangles(data).iter().flatten().map(|&i| i as usize) {
+        result.vertices.push(data.vertices[i]);
+        if let Some(n) = data.normals.get(i) {
+            result.normals.push(*n);
+        }
+        if let Some(uv) = data.tex_coords.get(i) {
+            result.tex_coords.push(*uv);
+        }
+        if let Some(t) = data.tangents.get(i) {
+            result.tangents.push(*t);
+        }
+    }
+    result.indices = (0..result.vertices.len() as u32).collect();
+
+    result
+}
+
+// merges vertices whose position and attributes all match within the tolerance
+pub fn weld(data: &ObjData, tolerance: f32) -> ObjData {
+    weld_by(data, tolerance, true)
+}
+
+fn weld_by(data: &ObjData, tolerance: f32, compare_attributes: bool) -> ObjData {
+    let tolerance = tolerance.max(f32::EPSILON);
+    let cell = |p: &Vector3<f32>| {
+        (
+            (p.x / tolerance).floor() as i64,
+            (p.y / tolerance).floor() as i64,
+            (p.z / tolerance).floor() as i64,
+        )
+    };
+
+    let same = |a: usize, b: usize| {
+        let close3 = |v: &[Vector3<f32>]| match (v.get(a), v.get(b)) {
+            (Some(x), Some(y)) => (x - y).norm() <= tolerance,
+            _ => true,
+        };
+        let close2 = |v: &[Vector2<f32>]| match (v.get(a), v.get(b)) {
+            (Some(x), Some(y)) => (x - y).norm() <= tolerance,
+            _ => true,
+        };
+
+        (data.vertices[a] - data.vertices[b]).norm() <= tolerance
+            && (!compare_attributes
+                || (close3(&data.normals) && close2(&data.tex_coords) && close3(&data.tangents)))
+    };
+
+    let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    let mut remap = vec![0_u32; data.vertices.len()];
+    let mut result = ObjData::new();
+
+    for (i, position) in data.vertices.iter().enumerate() {
+        let (cx, cy, cz) = cell(position);
+
+        let mut existing = None;
+        'search: for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(bucket) = grid.get(&(cx + dx, cy + dy, cz + dz)) {
+                        if let Some(&found) = bucket.iter().find(|&&j| same(i, j)) {
+                            existing = Some(found);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+
+        remap[i] = match existing {
+            Some(j) => remap[j],
+            None => {
+                grid.entry((cx, cy, cz)).or_default().push(i);
+                push_attributes(&mut result, data, i);
+                (result.vertices.len() - 1) as u32
+            }
+        };
+    }
+
+    result.indices = triangles(data)
+        .iter()
+        .flatten()
+        .map(|&i| remap[i as usize])
+        .collect();
+
+    result
+}
+
+fn push_attributes(target: &mut ObjData, source: &ObjData, i: usize) {
+    target.vertices.push(source.vertices[i]);
+    if let Some(n) = source.normals.get(i) {
+        target.normals.push(*n);
+    }
+    if let Some(uv) = source.tex_coords.get(i) {
+        target.tex_coords.push(*uv);
+    }
+    if let Some(t) = source.tangents.get(i) {
+        target.tangents.push(*t);
+    }
+}
+
+// drops triangles that reuse a vertex or whose area is at or below min_area, and any vertices
+// that are no longer referenced
+pub fn remove_degenerate_triangles(data: &ObjData, min_area: f32) -> ObjData {
+    let kept: Vec<[u32; 3]> = triangles(data)
+        .into_iter()
+        .filter(|&[a, b, c]| {
+            if a == b || b == c || a == c {
+                return false;
+            }
+            let (pa, pb, pc) = (
+                data.vertices[a as usize],
+                data.vertices[b as usize],
+                data.vertices[c as usize],
+            );
+            (pb - pa).cross(&(pc - pa)).norm() * 0.5 > min_area
+        })
+        .collect();
+
+    compact(data, &kept)
+}
+
+fn compact(data: &ObjData, tris: &[[u32; 3]]) -> ObjData {
+    let mut remap: HashMap<u32, u32> = HashMap::new();
+    let mut result = ObjData::new();
+
+    for &i in tris.iter().flatten() {
+        let index = *remap.entry(i).or_insert_with(|| {
+            push_attributes(&mut result, data, i as usize);
+            (result.vertices.len() - 1) as u32
+        });
+        result.indices.push(index);
+    }
+
+    result
+}
+
+#[derive(PartialEq)]
+struct Collapse {
+    cost: f64,
+    a: usize,
+    b: usize,
+    stamp: (u32, u32),
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // reversed so the binary heap pops the cheapest collapse first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Simplifier {
+    positions: Vec<Vector3<f64>>,
+    quadrics: Vec<Matrix4<f64>>,
+    tris: Vec<[usize; 3]>,
+    tri_alive: Vec<bool>,
+    vertex_tris: Vec<Vec<usize>>,
+    // each vertex is collapsed into `parent` or is its own representative
+    parent: Vec<usize>,
+    // bumped whenever a vertex moves so stale heap entries can be skipped
+    stamps: Vec<u32>,
+    // how far along the collapsed edge each surviving vertex ended up, used to blend attributes
+    blends: Vec<(usize, usize, f32)>,
+}
+
+impl Simplifier {
+    fn new(data: &ObjData) -> Simplifier {
+        let positions: Vec<Vector3<f64>> = data.vertices.iter().map(|v| v.cast::<f64>()).collect();
+        let tris: Vec<[usize; 3]> = triangles(data)
+            .iter()
+            .map(|t| t.map(|i| i as usize))
+            .collect();
+
+        let mut vertex_tris = vec![Vec::new(); positions.len()];
+        for (t, tri) in tris.iter().enumerate() {
+            for &v in tri {
+                vertex_tris[v].push(t);
+            }
+        }
+
+        let mut quadrics = vec![Matrix4::zeros(); positions.len()];
+        let mut edge_use: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        for (t, tri) in tris.iter().enumerate() {
+            let (p0, p1, p2) = (positions[tri[0]], positions[tri[1]], positions[tri[2]]);
+            let cross = (p1 - p0).cross(&(p2 - p0));
+            let area = cross.norm() * 0.5;
+            let Some(normal) = cross.try_normalize(1e-12) else {
+                continue;
+            };
+
+            let q = plane_quadric(&normal, &p0) * area;
+            for &v in tri {
+                quadrics[v] += q;
+            }
+
+            for (a, b) in [(tri[0], tri[1]), (tri[1], tri[2]), (tri[2], tri[0])] {
+                edge_use.entry((a.min(b), a.max(b))).or_insert((t, 0)).1 += 1;
+            }
+        }
+
+        // open borders get a perpendicular plane so they don't shrink away
+        for (&(a, b), &(t, uses)) in &edge_use {
+            if uses != 1 {
+                continue;
+            }
+            let tri = tris[t];
+            let face = (positions[tri[1]] - positions[tri[0]]).cross(&(positions[tri[2]] - positions[tri[0]]));
+            let edge = positions[b] - positions[a];
+            if let Some(normal) = edge.cross(&face).try_normalize(1e-12) {
+                let q = plane_quadric(&normal, &positions[a]) * edge.norm_squared() * 100.0;
+                quadrics[a] += q;
+                quadrics[b] += q;
+            }
+        }
+
+        let count = positions.len();
+        Simplifier {
+            positions,
+            quadrics,
+            tri_alive: vec![true; tris.len()],
+            tris,
+            vertex_tris,
+            parent: (0..count).collect(),
+            stamps: vec![0; count],
+            blends: (0..count).map(|i| (i, i, 0.0)).collect(),
+        }
+    }
+
+    fn error(q: &Matrix4<f64>, p: &Vector3<f64>) -> f64 {
+        let v = Vector4::new(p.x, p.y, p.z, 1.0);
+        (v.transpose() * q * v)[0].max(0.0)
+    }
+
+    fn candidate(&self, a: usize, b: usize) -> (f64, Vector3<f64>) {
+        let q = self.quadrics[a] + self.quadrics[b];
+        let (pa, pb) = (self.positions[a], self.positions[b]);
+
+        let mut options = vec![pa, pb, (pa + pb) * 0.5];
+        let system: Matrix3<f64> = q.fixed_view::<3, 3>(0, 0).into();
+        if system.determinant().abs() > 1e-12 {
+            if let Some(inv) = system.try_inverse() {
+                let optimal = -(inv * q.fixed_view::<3, 1>(0, 3));
+                // a nearly singular system can throw the point far away, only trust it close by
+                let reach = (pb - pa).norm() * 2.0;
+                if (optimal - (pa + pb) * 0.5).norm() <= reach {
+                    options.push(optimal);
+                }
+            }
+        }
+
+        options
+            .into_iter()
+            .map(|p| (Self::error(&q, &p), p))
+            .min_by(|x, y| x.0.total_cmp(&y.0))
+            .unwrap()
+    }
+
+    fn push_edges(&self, v: usize, heap: &mut BinaryHeap<Collapse>) {
+        let mut neighbours: Vec<usize> = self.vertex_tris[v]
+            .iter()
+            .filter(|&&t| self.tri_alive[t])
+            .flat_map(|&t| self.tris[t])
+            .filter(|&n| n != v)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+
+        for n in neighbours {
+            let (cost, _) = self.candidate(v, n);
+            heap.push(Collapse {
+                cost,
+                a: v,
+                b: n,
+                stamp: (self.stamps[v], self.stamps[n]),
+            });
+        }
+    }
+
+    // rejects collapses that would flip any surviving triangle around a or b
+    fn flips(&self, a: usize, b: usize, target: &Vector3<f64>) -> bool {
+        for &v in &[a, b] {
+            for &t in &self.vertex_tris[v] {
+                let tri = self.tris[t];
+                if !self.tri_alive[t] || (tri.contains(&a) && tri.contains(&b)) {
+                    continue;
+                }
+
+                let before: Vec<Vector3<f64>> = tri.iter().map(|&i| self.positions[i]).collect();
+                let after: Vec<Vector3<f64>> = tri
+                    .iter()
+                    .map(|&i| if i == a || i == b { *target } else { self.positions[i] })
+                    .collect();
+
+                let n0 = (before[1] - before[0]).cross(&(before[2] - before[0]));
+                let n1 = (after[1] - after[0]).cross(&(after[2] - after[0]));
+                if n0.dot(&n1) <= 0.0 {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn collapse(&mut self, a: usize, b: usize, target: Vector3<f64>) -> usize {
+        let (pa, pb) = (self.positions[a], self.positions[b]);
+        let edge = pb - pa;
+        let t = if edge.norm_squared() > 0.0 {
+            ((target - pa).dot(&edge) / edge.norm_squared()).clamp(0.0, 1.0) as f32
+        } else {
+            0.0
+        };
+        self.blends[a] = (a, b, t);
+
+        self.positions[a] = target;
+        self.quadrics[a] = self.quadrics[a] + self.quadrics[b];
+        self.parent[b] = a;
+        self.stamps[a] += 1;
+        self.stamps[b] += 1;
+
+        let mut removed = 0;
+        let moved = std::mem::take(&mut self.vertex_tris[b]);
+        for t in moved {
+            if !self.tri_alive[t] {
+                continue;
+            }
+            if self.tris[t].contains(&a) {
+                self.tri_alive[t] = false;
+                removed += 1;
+                continue;
+            }
+            for corner in self.tris[t].iter_mut() {
+                if *corner == b {
+                    *corner = a;
+                }
+            }
+            self.vertex_tris[a].push(t);
+        }
+        self.vertex_tris[a].retain(|&t| self.tri_alive[t]);
+
+        removed
+    }
+
+    fn root(&self, mut v: usize) -> usize {
+        while self.parent[v] != v {
+            v = self.parent[v];
+        }
+        v
+    }
+}
+
+fn plane_quadric(normal: &Vector3<f64>, point: &Vector3<f64>) -> Matrix4<f64> {
+    let plane = Vector4::new(normal.x, normal.y, normal.z, -normal.dot(point));
+    plane * plane.transpose()
+}
+
+// quadric error metric edge collapse (garland & heckbert) down to at most target_triangles.
+// vertices are welded on position first, so uv seams and hard edges get blended, and the result
+// may stay above the target when every remaining collapse would fold the surface over
+pub fn simplify(data: &ObjData, target_triangles: usize) -> ObjData {
+    let bbox = get_bounding_box(&data.vertices);
+    let diagonal = Vector3::new(
+        bbox.x_max - bbox.x_min,
+        bbox.y_max - bbox.y_min,
+        bbox.z_max - bbox.z_min,
+    )
+    .norm();
+    let welded = remove_degenerate_triangles(&weld_by(data, diagonal * 1e-6, false), 0.0);
+
+    let mut simplifier = Simplifier::new(&welded);
+    let mut alive = simplifier.tris.len();
+
+    let mut heap = BinaryHeap::new();
+    for v in 0..simplifier.positions.len() {
+        simplifier.push_edges(v, &mut heap);
+    }
+
+    while alive > target_triangles {
+        let Some(Collapse { a, b, stamp, .. }) = heap.pop() else {
+            break;
+        };
+        if simplifier.parent[a] != a
+            || simplifier.parent[b] != b
+            || stamp != (simplifier.stamps[a], simplifier.stamps[b])
+        {
+            continue;
+        }
+
+        let (_, target) = simplifier.candidate(a, b);
+        if simplifier.flips(a, b, &target) {
+            continue;
+        }
+
+        alive -= simplifier.collapse(a, b, target);
+        simplifier.push_edges(a, &mut heap);
+    }
+
+    // blend the attributes of the two original vertices each survivor came from
+    let mut result = welded.clone();
+    for v in 0..simplifier.positions.len() {
+        if simplifier.parent[v] != v {
+            continue;
+        }
+        let (a, b, t) = simplifier.blends[v];
+        result.vertices[v] = simplifier.positions[v].cast::<f32>();
+        if let (Some(na), Some(nb)) = (welded.normals.get(a), welded.normals.get(b)) {
+            result.normals[v] = na.lerp(nb, t).try_normalize(f32::EPSILON).unwrap_or(*na);
+        }
+        if let (Some(ua), Some(ub)) = (welded.tex_coords.get(a), welded.tex_coords.get(b)) {
+            result.tex_coords[v] = ua.lerp(ub, t);
+        }
+        if let (Some(ta), Some(tb)) = (welded.tangents.get(a), welded.tangents.get(b)) {
+            result.tangents[v] = ta.lerp(tb, t).try_normalize(f32::EPSILON).unwrap_or(*ta);
+        }
+    }
+
+    let kept: Vec<[u32; 3]> = simplifier
+        .tris
+        .iter()
+        .zip(&simplifier.tri_alive)
+        .filter(|(_, &alive)| alive)
+        .map(|(tri, _)| tri.map(|v| simplifier.root(v) as u32))
+        .collect();
+
+    remove_degenerate_triangles(&compact(&result, &kept), 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obj::parse_obj;
+
+    fn teapot() -> ObjData {
+        parse_obj("resources/teapot.obj").expect("unable to load teapot")
+    }
+
+    fn extents(data: &ObjData) -> Vector3<f32> {
+        let bb = get_bounding_box(&data.vertices);
+        Vector3::new(bb.x_max - bb.x_min, bb.y_max - bb.y_min, bb.z_max - bb.z_min)
+    }
+
+    #[test]
+    fn transform_moves_and_mirrors() {
+        let data = teapot();
+        let moved = transform(&data, &Matrix4::new_translation(&Vector3::new(0.0, 100.0, 0.0)));
+        let before = get_bounding_box(&data.vertices);
+        let after = get_bounding_box(&moved.vertices);
+        assert!((after.y_min - before.y_min - 100.0).abs() < 1e-3);
+        for (a, b) in moved.normals.iter().zip(&data.normals) {
+            assert!((a - b).norm() < 1e-5);
+        }
+
+        // winding relative to the vertex normals has to survive the mirror
+        let facing = |mesh: &ObjData| -> Vec<bool> {
+            triangles(mesh)
+                .iter()
+                .map(|&[a, b, c]| {
+                    let [pa, pb, pc] = [a, b, c].map(|i| mesh.vertices[i as usize]);
+                    (pb - pa).cross(&(pc - pa)).dot(&mesh.normals[a as usize]) > 0.0
+                })
+                .collect()
+        };
+        let mirrored = transform(&data, &Matrix4::new_nonuniform_scaling(&Vector3::new(-1.0, 1.0, 1.0)));
+        assert_eq!(facing(&mirrored), facing(&data));
+    }
+
+    #[test]
+    fn merge_offsets_indices() {
+        let data = teapot();
+        let moved = transform(&data, &Matrix4::new_translation(&Vector3::new(500.0, 0.0, 0.0)));
+        let merged = merge(&[&data, &moved]);
+
+        assert_eq!(merged.vertices.len(), data.vertices.len() * 2);
+        assert_eq!(triangle_count(&merged), triangle_count(&data) * 2);
+        // teapot has no uvs, so they stay empty rather than being padded
+        assert!(merged.tex_coords.is_empty());
+        let last = *merged.indices.last().unwrap() as usize;
+        assert_eq!(merged.vertices[last], moved.vertices[moved.indices.len() - 1]);
+    }
+
+    #[test]
+    fn weld_shares_vertices() {
+        let data = teapot();
+        let welded = weld(&data, 1e-4);
+
+        assert!(welded.vertices.len() < data.vertices.len() / 2);
+        assert_eq!(triangle_count(&welded), triangle_count(&data));
+
+        let flat = expand(&welded);
+        for (a, b) in flat.vertices.iter().zip(&data.vertices) {
+            assert!((a - b).norm() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn degenerate_triangles_are_dropped() {
+        let mut data = weld(&teapot(), 1e-4);
+        let before = triangle_count(&data);
+        data.indices.extend_from_slice(&[0, 0, 1]);
+        data.indices.extend_from_slice(&[0, 1, 2]);
+        let p = data.vertices[0];
+        data.vertices[2] = p;
+        data.vertices[1] = p;
+
+        let cleaned = remove_degenerate_triangles(&data, 0.0);
+        assert!(triangle_count(&cleaned) < before);
+        for tri in cleaned.indices.chunks_exact(3) {
+            assert!(tri[0] != tri[1] && tri[1] != tri[2] && tri[0] != tri[2]);
+        }
+    }
+
+    #[test]
+    fn simplify_reaches_target_and_keeps_shape() {
+        let data = teapot();
+        let simplified = simplify(&data, 400);
+
+        let count = triangle_count(&simplified);
+        assert!(count <= 400 && count > 200, "got {count} triangles");
+
+        let original = extents(&data);
+        let reduced = extents(&simplified);
+        assert!((original - reduced).norm() < original.norm() * 0.05);
+
+        for tri in simplified.indices.chunks_exact(3) {
+            assert!(tri[0] != tri[1] && tri[1] != tri[2] && tri[0] != tri[2]);
+        }
+        for n in &simplified.normals {
+            assert!((n.norm() - 1.0).abs() < 1e-3);
+        }
+    }
+}
@@ -4,12 +4,50 @@ use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
 
-#[derive(Debug)]
+// the parts of an .mtl material we use to build a Physical
+#[derive(Debug, Clone)]
+pub struct MtlData {
+    pub name: String,
+    pub diffuse: Option<Vector3<f32>>,
+    // the `d` value, 1.0 is fully opaque
+    pub dissolve: Option<f32>,
+    // Ke, tobj doesn't parse it so it comes through as an unknown parameter
+    pub emissive: Option<Vector3<f32>>,
+    pub diffuse_texture: Option<String>,
+}
+
+impl MtlData {
+    fn from_tobj(material: &tobj::Material) -> MtlData {
+        MtlData {
+            name: material.name.clone(),
+            diffuse: material.diffuse.map(|d| Vector3::new(d[0], d[1], d[2])),
+            dissolve: material.dissolve,
+            emissive: material.unknown_param.get("Ke").and_then(|ke| parse_vector(ke)),
+            diffuse_texture: material.diffuse_texture.clone(),
+        }
+    }
+}
+
+pub fn parse_vector(value: &str) -> Option<Vector3<f32>> {
+    let parts: Vec<f32> = value
+        .split_whitespace()
+        .map(|p| p.parse::<f32>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match parts.as_slice() {
+        [x, y, z] => Some(Vector3::new(*x, *y, *z)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ObjData {
     pub vertices: Vec<Vector3<f32>>,
     pub normals: Vec<Vector3<f32>>,
     pub tex_coords: Vec<Vector2<f32>>,
+    pub tangents: Vec<Vector3<f32>>,
     pub indices: Vec<u32>,
+    pub materials: Vec<MtlData>,
 }
 
 impl ObjData {
@@ -18,16 +56,77 @@ impl ObjData {
             vertices: Vec::new(),
             normals: Vec::new(),
             tex_coords: Vec::new(),
+            tangents: Vec::new(),
             indices: Vec::new(),
+            materials: Vec::new(),
         }
     }
 }
 
+// per vertex tangents for a flat triangle list, orthogonalized against the vertex normal.
+// falls back to an arbitrary perpendicular when there are no uvs or the uv mapping is degenerate
+pub fn compute_tangents(
+    vertices: &[Vector3<f32>],
+    normals: &[Vector3<f32>],
+    uvs: &[Vector2<f32>],
+) -> Vec<Vector3<f32>> {
+    let mut tangents = Vec::with_capacity(vertices.len());
+
+    for (t, tri) in vertices.chunks_exact(3).enumerate() {
+        let base = t * 3;
+        let edge1 = tri[1] - tri[0];
+        let edge2 = tri[2] - tri[0];
+
+        let mut face_tangent = Vector3::zeros();
+        if uvs.len() >= base + 3 {
+            let duv1 = uvs[base + 1] - uvs[base];
+            let duv2 = uvs[base + 2] - uvs[base];
+            let det = duv1.x * duv2.y - duv2.x * duv1.y;
+            if det.abs() > f32::EPSILON {
+                face_tangent = (edge1 * duv2.y - edge2 * duv1.y) / det;
+            }
+        }
+
+        let face_normal = edge1.cross(&edge2);
+        for i in 0..3 {
+            let n = match normals.get(base + i) {
+                Some(n) => *n,
+                None => face_normal,
+            };
+            tangents.push(orthogonal_tangent(&n, &face_tangent));
+        }
+    }
+
+    tangents
+}
+
+fn orthogonal_tangent(normal: &Vector3<f32>, tangent: &Vector3<f32>) -> Vector3<f32> {
+    let n = normal.try_normalize(f32::EPSILON).unwrap_or(Vector3::y());
+    let t = tangent - n * n.dot(tangent);
+    if let Some(t) = t.try_normalize(1e-6) {
+        return t;
+    }
+
+    // pick whichever axis is least aligned with the normal
+    let axis = if n.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+    (axis - n * n.dot(&axis)).normalize()
+}
+
 pub fn parse_obj(file_path: &str) -> Result<ObjData, io::Error> {
     let loaded_file = tobj::load_obj(file_path, &tobj::GPU_LOAD_OPTIONS);
     assert!(loaded_file.is_ok());
 
-    let (models, _materials) = loaded_file.expect("Failed to load OBJ file");
+    let (models, materials) = loaded_file.expect("Failed to load OBJ file");
+
+    // a missing or broken .mtl file shouldn't stop the mesh from loading
+    let materials = match materials {
+        Ok(materials) => materials.iter().map(MtlData::from_tobj).collect(),
+        Err(_) => Vec::new(),
+    };
 
     let mut vertices: Vec<Vector3<f32>> = Vec::new();
     let mut normals: Vec<Vector3<f32>> = Vec::new();
@@ -67,15 +166,19 @@ pub fn parse_obj(file_path: &str) -> Result<ObjData, io::Error> {
                 ));
             }
 
-            // Store indices for rendering
-            indices.push(*idx);
+            // the attributes above are already expanded per index, so each entry refers to its own slot
+            indices.push(indices.len() as u32);
         }
     }
 
+    let tangents = compute_tangents(&vertices, &normals, &uvs);
+
     Ok(ObjData {
         vertices,
         normals,
         tex_coords: uvs,
+        tangents,
         indices,
+        materials,
     })
 }
@@ -0,0 +1,890 @@
+// rigid body physics. the world steps at a fixed rate however long frames take, so the same inputs
+// always play out the same way. each step adds gravity and forces to the velocities, finds the
+// contacts between bodies, pushes them apart with impulses (restitution and coulomb friction
+// included) and only then moves everything. bodies tied to a scene object copy their transform
+// back into its Model with sync
+
+use std::collections::HashMap;
+
+use nalgebra::{Isometry3, Matrix3, Translation3, UnitQuaternion, Vector3};
+
+use crate::{
+    collision::{self, BoundingBox, Plane, Sphere},
+    convex::{self, ContactManifold, ContactPoint, ConvexHull},
+    render::Object,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyKind {
+    // never moves, however hard it is hit
+    Static,
+    Dynamic,
+}
+
+pub enum Collider {
+    Sphere(f32),
+    // in body space, around the centre of mass
+    Hull(ConvexHull),
+    // a world space half space, only for static bodies
+    Plane(Plane),
+}
+
+impl Collider {
+    pub fn cuboid(half_extents: Vector3<f32>) -> Collider {
+        let corners: Vec<Vector3<f32>> = (0..8)
+            .map(|i| {
+                Vector3::from_fn(|axis, _| {
+                    if i & (1 << axis) == 0 {
+                        -half_extents[axis]
+                    } else {
+                        half_extents[axis]
+                    }
+                })
+            })
+            .collect();
+        Collider::Hull(ConvexHull::from_points(&corners).expect("a cuboid needs some size"))
+    }
+
+    // the diagonal of the inertia tensor for a body of this shape and mass. hulls are treated as
+    // their bounding box
+    fn inertia(&self, mass: f32) -> Vector3<f32> {
+        match self {
+            Collider::Sphere(radius) => Vector3::repeat(0.4 * mass * radius * radius),
+            Collider::Hull(hull) => {
+                let size = collision::get_bounding_box(&hull.vertices).half_extents() * 2.0;
+                let squared = size.component_mul(&size);
+                Vector3::new(
+                    squared.y + squared.z,
+                    squared.x + squared.z,
+                    squared.x + squared.y,
+                ) * (mass / 12.0)
+            }
+            Collider::Plane(_) => Vector3::zeros(),
+        }
+    }
+}
+
+pub struct RigidBody {
+    pub kind: BodyKind,
+    // of the centre of mass
+    pub position: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub velocity: Vector3<f32>,
+    pub angular_velocity: Vector3<f32>,
+    pub inverse_mass: f32,
+    // diagonal, in body space
+    pub inverse_inertia: Vector3<f32>,
+    pub restitution: f32,
+    pub friction: f32,
+    pub collider: Collider,
+    // the scene object that follows this body, and where the centre of mass sits from its origin
+    pub object: Option<String>,
+    pub center_of_mass: Vector3<f32>,
+    force: Vector3<f32>,
+    torque: Vector3<f32>,
+}
+
+impl RigidBody {
+    // the mass only matters for dynamic bodies, which need a positive one
+    pub fn new(kind: BodyKind, collider: Collider, mass: f32) -> RigidBody {
+        assert!(
+            kind == BodyKind::Static || mass > 0.0,
+            "a dynamic body needs a positive mass, got {}",
+            mass
+        );
+        let (inverse_mass, inverse_inertia) = match kind {
+            BodyKind::Static => (0.0, Vector3::zeros()),
+            BodyKind::Dynamic => (
+                1.0 / mass,
+                collider
+                    .inertia(mass)
+                    .map(|i| if i > 0.0 { 1.0 / i } else { 0.0 }),
+            ),
+        };
+
+        RigidBody {
+            kind,
+            position: Vector3::zeros(),
+            rotation: UnitQuaternion::identity(),
+            velocity: Vector3::zeros(),
+            angular_velocity: Vector3::zeros(),
+            inverse_mass,
+            inverse_inertia,
+            restitution: 0.2,
+            friction: 0.5,
+            collider,
+            object: None,
+            center_of_mass: Vector3::zeros(),
+            force: Vector3::zeros(),
+            torque: Vector3::zeros(),
+        }
+    }
+
+    // a body shaped like the hull of an object's mesh, placed where the object is. None for flat
+    // meshes. the object's scale is baked in, and should be uniform
+    pub fn from_object(
+        name: &str,
+        object: &Object,
+        kind: BodyKind,
+        mass: f32,
+    ) -> Option<RigidBody> {
+        let model = &object.model;
+        let scaled: Vec<Vector3<f32>> = object
+            .vertices
+            .iter()
+            .map(|v| v.component_mul(&model.scale))
+            .collect();
+        let hull = ConvexHull::from_points(&scaled)?;
+        let center = collision::get_bounding_box(&hull.vertices).center();
+        let hull = ConvexHull {
+            vertices: hull.vertices.iter().map(|v| v - center).collect(),
+            faces: hull.faces,
+        };
+
+        let mut body = RigidBody::new(kind, Collider::Hull(hull), mass);
+        body.rotation = model.rotation;
+        body.position = model.scale.component_mul(&model.position) + model.rotation * center;
+        body.object = Some(name.to_string());
+        body.center_of_mass = center;
+        Some(body)
+    }
+
+    // keeps the body upright, it can still slide and be pushed around
+    pub fn lock_rotation(&mut self) {
+        self.inverse_inertia = Vector3::zeros();
+        self.angular_velocity = Vector3::zeros();
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.kind == BodyKind::Static
+    }
+
+    fn isometry(&self) -> Isometry3<f32> {
+        Isometry3::from_parts(Translation3::from(self.position), self.rotation)
+    }
+
+    fn world_inverse_inertia(&self) -> Matrix3<f32> {
+        let rotation = self.rotation.to_rotation_matrix();
+        rotation.matrix()
+            * Matrix3::from_diagonal(&self.inverse_inertia)
+            * rotation.matrix().transpose()
+    }
+
+    pub fn velocity_at(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        self.velocity + self.angular_velocity.cross(&(point - self.position))
+    }
+
+    // an instant change in momentum at a world point, off centre pushes spin the body
+    pub fn apply_impulse(&mut self, impulse: &Vector3<f32>, point: &Vector3<f32>) {
+        self.velocity += impulse * self.inverse_mass;
+        self.angular_velocity +=
+            self.world_inverse_inertia() * (point - self.position).cross(impulse);
+    }
+
+    // held for the next step only
+    pub fn apply_force(&mut self, force: &Vector3<f32>, point: &Vector3<f32>) {
+        self.force += force;
+        self.torque += (point - self.position).cross(force);
+    }
+
+    fn world_hull(&self) -> Option<ConvexHull> {
+        match &self.collider {
+            Collider::Hull(hull) => Some(hull.transform(&self.isometry().to_homogeneous())),
+            _ => None,
+        }
+    }
+
+    fn bounding_box(&self, hull: Option<&ConvexHull>) -> BoundingBox {
+        match (&self.collider, hull) {
+            (Collider::Sphere(radius), _) => {
+                let (min, max) = (
+                    self.position.add_scalar(-radius),
+                    self.position.add_scalar(*radius),
+                );
+                BoundingBox::new(min.x, max.x, min.y, max.y, min.z, max.z)
+            }
+            (Collider::Hull(_), Some(hull)) => collision::get_bounding_box(&hull.vertices),
+            _ => BoundingBox::new(
+                f32::NEG_INFINITY,
+                f32::INFINITY,
+                f32::NEG_INFINITY,
+                f32::INFINITY,
+                f32::NEG_INFINITY,
+                f32::INFINITY,
+            ),
+        }
+    }
+}
+
+// two bodies touching, by their index in the world
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contact {
+    pub a: usize,
+    pub b: usize,
+    pub manifold: ContactManifold,
+}
+
+pub struct PhysicsWorld {
+    pub bodies: Vec<RigidBody>,
+    pub gravity: Vector3<f32>,
+    // seconds per fixed step
+    pub timestep: f32,
+    // more solver passes settle stacks better
+    pub iterations: usize,
+    // the most steps one call to step will take, so a long stall doesn't snowball
+    pub max_steps: usize,
+    // how much of the overlap is pushed out each step, and how much is left alone so resting
+    // contacts don't jitter
+    pub correction: f32,
+    pub slop: f32,
+    // impacts slower than this don't bounce
+    pub bounce_threshold: f32,
+    // from the last step
+    pub contacts: Vec<Contact>,
+    accumulator: f32,
+}
+
+impl PhysicsWorld {
+    pub fn new() -> PhysicsWorld {
+        PhysicsWorld {
+            bodies: Vec::new(),
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+            timestep: 1.0 / 60.0,
+            iterations: 10,
+            max_steps: 8,
+            correction: 0.4,
+            slop: 0.01,
+            bounce_threshold: 1.0,
+            contacts: Vec::new(),
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn add(&mut self, body: RigidBody) -> usize {
+        self.bodies.push(body);
+        self.bodies.len() - 1
+    }
+
+    // the body following a scene object
+    pub fn find(&self, object: &str) -> Option<usize> {
+        self.bodies
+            .iter()
+            .position(|body| body.object.as_deref() == Some(object))
+    }
+
+    // runs however many fixed steps fit in the time since the last call, the rest carries over.
+    // returns the number of steps taken
+    pub fn step(&mut self, delta_time: f32) -> usize {
+        self.accumulator += delta_time;
+        let mut steps = 0;
+        while self.accumulator >= self.timestep && steps < self.max_steps {
+            self.fixed_step();
+            self.accumulator -= self.timestep;
+            steps += 1;
+        }
+        // whatever is left after hitting max_steps is dropped rather than caught up on later
+        if steps == self.max_steps {
+            self.accumulator = self.accumulator.min(self.timestep);
+        }
+        steps
+    }
+
+    pub fn fixed_step(&mut self) {
+        let dt = self.timestep;
+
+        for body in self.bodies.iter_mut().filter(|body| !body.is_static()) {
+            body.velocity += (self.gravity + body.force * body.inverse_mass) * dt;
+            body.angular_velocity += body.world_inverse_inertia() * body.torque * dt;
+            body.force = Vector3::zeros();
+            body.torque = Vector3::zeros();
+        }
+
+        self.contacts = self.find_contacts();
+        self.solve();
+        self.separate();
+
+        for body in self.bodies.iter_mut().filter(|body| !body.is_static()) {
+            body.position += body.velocity * dt;
+            body.rotation =
+                UnitQuaternion::from_scaled_axis(body.angular_velocity * dt) * body.rotation;
+            body.rotation.renormalize();
+        }
+    }
+
+    // every pair in index order so the solver always sees them in the same order
+    fn find_contacts(&self) -> Vec<Contact> {
+        let hulls: Vec<Option<ConvexHull>> = self.bodies.iter().map(|b| b.world_hull()).collect();
+        let bounds: Vec<BoundingBox> = self
+            .bodies
+            .iter()
+            .zip(&hulls)
+            .map(|(body, hull)| body.bounding_box(hull.as_ref()))
+            .collect();
+
+        let mut contacts = Vec::new();
+        for a in 0..self.bodies.len() {
+            for b in a + 1..self.bodies.len() {
+                if self.bodies[a].is_static() && self.bodies[b].is_static() {
+                    continue;
+                }
+                if !bounds[a].intersects(&bounds[b]) {
+                    continue;
+                }
+                let manifold = collide(
+                    (&self.bodies[a], hulls[a].as_ref()),
+                    (&self.bodies[b], hulls[b].as_ref()),
+                );
+                if let Some(manifold) = manifold {
+                    contacts.push(Contact { a, b, manifold });
+                }
+            }
+        }
+        contacts
+    }
+
+    // sequential impulses. each contact point's impulse is accumulated over the iterations and
+    // clamped as a total, so early passes that overshoot get taken back
+    fn solve(&mut self) {
+        struct Point {
+            a: usize,
+            b: usize,
+            normal: Vector3<f32>,
+            tangents: [Vector3<f32>; 2],
+            position: Vector3<f32>,
+            // the speed apart along the normal the solver aims for
+            target: f32,
+            friction: f32,
+            normal_impulse: f32,
+            tangent_impulses: [f32; 2],
+        }
+
+        let mut points: Vec<Point> = Vec::new();
+        for contact in &self.contacts {
+            let (body_a, body_b) = (&self.bodies[contact.a], &self.bodies[contact.b]);
+            let normal = contact.manifold.normal;
+            let tangent = normal
+                .cross(&Vector3::x())
+                .try_normalize(1e-3)
+                .unwrap_or_else(|| normal.cross(&Vector3::y()).normalize());
+
+            for ContactPoint { position, .. } in &contact.manifold.points {
+                let approach =
+                    (body_b.velocity_at(position) - body_a.velocity_at(position)).dot(&normal);
+                let restitution = body_a.restitution.max(body_b.restitution);
+                let bounce = if approach < -self.bounce_threshold {
+                    -restitution * approach
+                } else {
+                    0.0
+                };
+
+                points.push(Point {
+                    a: contact.a,
+                    b: contact.b,
+                    normal,
+                    tangents: [tangent, normal.cross(&tangent)],
+                    position: *position,
+                    target: bounce,
+                    friction: (body_a.friction * body_b.friction).sqrt(),
+                    normal_impulse: 0.0,
+                    tangent_impulses: [0.0; 2],
+                });
+            }
+        }
+
+        // how much an impulse along direction at the point changes the speed apart there
+        let response = |bodies: &[RigidBody], point: &Point, direction: &Vector3<f32>| {
+            [(&bodies[point.a]), (&bodies[point.b])]
+                .iter()
+                .map(|body| {
+                    let arm = point.position - body.position;
+                    let spin = body.world_inverse_inertia() * arm.cross(direction);
+                    body.inverse_mass + spin.cross(&arm).dot(direction)
+                })
+                .sum::<f32>()
+        };
+
+        let apply = |bodies: &mut [RigidBody], point: &Point, impulse: Vector3<f32>| {
+            bodies[point.a].apply_impulse(&-impulse, &point.position);
+            bodies[point.b].apply_impulse(&impulse, &point.position);
+        };
+
+        for _ in 0..self.iterations {
+            for point in points.iter_mut() {
+                let relative = |bodies: &[RigidBody]| {
+                    bodies[point.b].velocity_at(&point.position)
+                        - bodies[point.a].velocity_at(&point.position)
+                };
+
+                let mass = response(&self.bodies, point, &point.normal);
+                if mass <= 0.0 {
+                    continue;
+                }
+                let speed = relative(&self.bodies).dot(&point.normal);
+                let total = (point.normal_impulse + (point.target - speed) / mass).max(0.0);
+                let change = total - point.normal_impulse;
+                point.normal_impulse = total;
+                apply(&mut self.bodies, point, point.normal * change);
+
+                // friction can't push harder than the contact is being pressed together
+                let limit = point.friction * point.normal_impulse;
+                for i in 0..2 {
+                    let tangent = point.tangents[i];
+                    let mass = response(&self.bodies, point, &tangent);
+                    if mass <= 0.0 {
+                        continue;
+                    }
+                    let slide = relative(&self.bodies).dot(&tangent);
+                    let total = (point.tangent_impulses[i] - slide / mass).clamp(-limit, limit);
+                    let change = total - point.tangent_impulses[i];
+                    point.tangent_impulses[i] = total;
+                    apply(&mut self.bodies, point, tangent * change);
+                }
+            }
+        }
+    }
+
+    // overlap is pushed out by moving the bodies rather than speeding them apart, which would
+    // carry on as a bounce after they'd separated
+    fn separate(&mut self) {
+        for contact in &self.contacts {
+            let share = self.bodies[contact.a].inverse_mass + self.bodies[contact.b].inverse_mass;
+            let Some(depth) = contact
+                .manifold
+                .points
+                .iter()
+                .map(|p| p.depth)
+                .max_by(f32::total_cmp)
+            else {
+                continue;
+            };
+            if share <= 0.0 || depth <= self.slop {
+                continue;
+            }
+            let shift = contact.manifold.normal * (self.correction * (depth - self.slop) / share);
+            let a = &mut self.bodies[contact.a];
+            a.position -= shift * a.inverse_mass;
+            let b = &mut self.bodies[contact.b];
+            b.position += shift * b.inverse_mass;
+        }
+    }
+
+    // moves the scene objects that follow bodies to where their bodies are
+    pub fn sync(&self, objects: &mut HashMap<String, Object>) {
+        for body in &self.bodies {
+            let Some(object) = body.object.as_ref().and_then(|name| objects.get_mut(name)) else {
+                continue;
+            };
+            // the model matrix scales its translation, so undo that here
+            let origin = body.position - body.rotation * body.center_of_mass;
+            object.model.position = origin.component_div(&object.model.scale);
+            object.model.rotation = body.rotation;
+        }
+    }
+}
+
+// the contact manifold between two bodies, normal from a to b
+fn collide(
+    (a, a_hull): (&RigidBody, Option<&ConvexHull>),
+    (b, b_hull): (&RigidBody, Option<&ConvexHull>),
+) -> Option<ContactManifold> {
+    let flip = |manifold: ContactManifold| ContactManifold {
+        normal: -manifold.normal,
+        points: manifold.points,
+    };
+
+    match (&a.collider, &b.collider) {
+        (Collider::Plane(_), Collider::Plane(_)) => None,
+        (Collider::Plane(plane), _) => against_plane(plane, b, b_hull),
+        (_, Collider::Plane(plane)) => against_plane(plane, a, a_hull).map(flip),
+        (Collider::Sphere(radius_a), Collider::Sphere(radius_b)) => {
+            let offset = b.position - a.position;
+            let distance = offset.norm();
+            if distance > radius_a + radius_b {
+                return None;
+            }
+            let normal = offset
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::y);
+            let depth = radius_a + radius_b - distance;
+            Some(ContactManifold {
+                normal,
+                points: vec![ContactPoint {
+                    position: a.position + normal * (radius_a - depth * 0.5),
+                    depth,
+                }],
+            })
+        }
+        (Collider::Sphere(radius), Collider::Hull(_)) => {
+            sphere_against_hull(&Sphere::new(a.position, *radius), b_hull?)
+        }
+        (Collider::Hull(_), Collider::Sphere(radius)) => {
+            sphere_against_hull(&Sphere::new(b.position, *radius), a_hull?).map(flip)
+        }
+        (Collider::Hull(_), Collider::Hull(_)) => convex::contact_manifold(a_hull?, b_hull?),
+    }
+}
+
+fn against_plane(
+    plane: &Plane,
+    body: &RigidBody,
+    hull: Option<&ConvexHull>,
+) -> Option<ContactManifold> {
+    let points: Vec<ContactPoint> = match &body.collider {
+        Collider::Sphere(radius) => {
+            let depth = radius - plane.signed_distance(&body.position);
+            vec![ContactPoint {
+                position: body.position - plane.normal * *radius,
+                depth,
+            }]
+        }
+        Collider::Hull(_) => hull?
+            .vertices
+            .iter()
+            .map(|vertex| ContactPoint {
+                position: *vertex,
+                depth: -plane.signed_distance(vertex),
+            })
+            .collect(),
+        Collider::Plane(_) => return None,
+    };
+
+    let mut points: Vec<ContactPoint> = points.into_iter().filter(|p| p.depth >= 0.0).collect();
+    if points.is_empty() {
+        return None;
+    }
+    // a hull with many vertices in the ground keeps its deepest few
+    points.sort_by(|x, y| y.depth.total_cmp(&x.depth));
+    Some(ContactManifold {
+        normal: plane.normal,
+        points: convex::reduce(points),
+    })
+}
+
+fn sphere_against_hull(sphere: &Sphere, hull: &ConvexHull) -> Option<ContactManifold> {
+    let penetration = convex::penetration(sphere, hull)?;
+    Some(ContactManifold {
+        normal: penetration.normal,
+        points: vec![ContactPoint {
+            position: (penetration.point_a + penetration.point_b) * 0.5,
+            depth: penetration.depth,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{buffers::RenderBuffers, material::Physical, primitives, render::Model};
+
+    fn ground() -> RigidBody {
+        RigidBody::new(
+            BodyKind::Static,
+            Collider::Plane(Plane::new(Vector3::y(), 0.0)),
+            0.0,
+        )
+    }
+
+    fn ball(y: f32) -> RigidBody {
+        let mut body = RigidBody::new(BodyKind::Dynamic, Collider::Sphere(0.5), 1.0);
+        body.position = Vector3::new(0.0, y, 0.0);
+        body
+    }
+
+    fn crate_at(position: Vector3<f32>) -> RigidBody {
+        let mut body = RigidBody::new(
+            BodyKind::Dynamic,
+            Collider::cuboid(Vector3::repeat(0.5)),
+            1.0,
+        );
+        body.position = position;
+        body
+    }
+
+    fn run(world: &mut PhysicsWorld, seconds: f32) {
+        for _ in 0..(seconds / world.timestep).round() as usize {
+            world.fixed_step();
+        }
+    }
+
+    #[test]
+    fn free_fall_follows_the_integrator_exactly() {
+        let mut world = PhysicsWorld::new();
+        let body = world.add(ball(100.0));
+        run(&mut world, 1.0);
+
+        // semi-implicit euler adds the new velocity each step: g dt^2 (1 + 2 + ... + n)
+        let dt = world.timestep;
+        let fallen = 9.81 * dt * dt * (60.0 * 61.0 / 2.0);
+        assert!((world.bodies[body].position.y - (100.0 - fallen)).abs() < 1e-3);
+        assert!((world.bodies[body].velocity.y + 9.81).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fixed_steps_ignore_how_frames_are_sliced() {
+        let build = || {
+            let mut world = PhysicsWorld::new();
+            world.add(ground());
+            world.add(crate_at(Vector3::new(0.0, 3.0, 0.0)));
+            let mut spinning = crate_at(Vector3::new(0.3, 5.0, 0.2));
+            spinning.angular_velocity = Vector3::new(1.0, 2.0, 0.5);
+            world.add(spinning);
+            world
+        };
+
+        let mut even = build();
+        for _ in 0..120 {
+            even.step(1.0 / 60.0);
+        }
+        let mut uneven = build();
+        let frames = [0.005, 0.03, 0.011, 0.021, 0.0165];
+        let mut elapsed = 0.0;
+        let mut steps = 0;
+        while steps < 120 {
+            let frame = frames[steps % frames.len()];
+            elapsed += frame;
+            steps += uneven.step(frame);
+        }
+        assert!(elapsed > 0.0);
+        assert_eq!(steps, 120);
+
+        for (a, b) in even.bodies.iter().zip(&uneven.bodies) {
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.rotation, b.rotation);
+        }
+    }
+
+    #[test]
+    fn long_frames_take_a_bounded_number_of_steps() {
+        let mut world = PhysicsWorld::new();
+        world.add(ball(10.0));
+        assert_eq!(world.step(0.01), 0);
+        assert_eq!(world.step(0.01), 1);
+        assert_eq!(world.step(5.0), world.max_steps);
+        assert!(world.step(0.0) <= 1);
+    }
+
+    #[test]
+    fn balls_and_boxes_come_to_rest_on_the_ground() {
+        let mut world = PhysicsWorld::new();
+        world.add(ground());
+        let resting_ball = world.add(ball(2.0));
+        let resting_box = world.add(crate_at(Vector3::new(3.0, 2.0, 0.0)));
+        run(&mut world, 3.0);
+
+        let ball = &world.bodies[resting_ball];
+        assert!((ball.position.y - 0.5).abs() < 0.03);
+        assert!(ball.velocity.norm() < 0.05);
+
+        let body = &world.bodies[resting_box];
+        assert!((body.position.y - 0.5).abs() < 0.03);
+        assert!(body.velocity.norm() < 0.05);
+        assert!(body.angular_velocity.norm() < 0.05);
+        // it landed flat and stays that way
+        assert!(body.rotation.angle() < 0.01);
+        assert_eq!(world.contacts.len(), 2);
+    }
+
+    #[test]
+    fn boxes_stack() {
+        let mut world = PhysicsWorld::new();
+        world.add(ground());
+        let bottom = world.add(crate_at(Vector3::new(0.0, 0.5, 0.0)));
+        let top = world.add(crate_at(Vector3::new(0.1, 1.6, 0.0)));
+        run(&mut world, 3.0);
+
+        assert!((world.bodies[bottom].position.y - 0.5).abs() < 0.05);
+        assert!((world.bodies[top].position.y - 1.5).abs() < 0.05);
+        assert!((world.bodies[top].position.x - 0.1).abs() < 0.05);
+        assert!(world.bodies[top].velocity.norm() < 0.05);
+    }
+
+    #[test]
+    fn restitution_decides_the_bounce() {
+        let peak_after_bounce = |restitution: f32| {
+            // the bouncier of the two wins, so the ground has to match
+            let mut world = PhysicsWorld::new();
+            let mut floor = ground();
+            floor.restitution = restitution;
+            world.add(floor);
+            let mut body = ball(5.0);
+            body.restitution = restitution;
+            let index = world.add(body);
+
+            let mut bounced = false;
+            let mut peak: f32 = 0.0;
+            for _ in 0..240 {
+                world.fixed_step();
+                let body = &world.bodies[index];
+                bounced |= body.velocity.y > 0.0 && body.position.y < 1.0;
+                if bounced {
+                    peak = peak.max(body.position.y);
+                }
+            }
+            peak
+        };
+
+        assert!(peak_after_bounce(1.0) > 4.0);
+        let half = peak_after_bounce(0.5);
+        assert!(half > 1.2 && half < 2.5);
+        assert!(peak_after_bounce(0.0) < 0.6);
+    }
+
+    #[test]
+    fn friction_stops_sliding_boxes() {
+        let slide = |friction: f32| {
+            let mut world = PhysicsWorld::new();
+            let mut floor = ground();
+            floor.friction = friction;
+            world.add(floor);
+            let mut body = crate_at(Vector3::new(0.0, 0.5, 0.0));
+            body.friction = friction;
+            body.velocity = Vector3::new(5.0, 0.0, 0.0);
+            let index = world.add(body);
+            run(&mut world, 2.0);
+            (
+                world.bodies[index].position.x,
+                world.bodies[index].velocity.x,
+            )
+        };
+
+        // coulomb friction stops it after v^2 / (2 mu g)
+        let (distance, speed) = slide(0.5);
+        assert!(speed.abs() < 0.05);
+        assert!((distance - 25.0 / (2.0 * 0.5 * 9.81)).abs() < 0.3);
+
+        let (distance, speed) = slide(0.0);
+        assert!((speed - 5.0).abs() < 0.01);
+        assert!((distance - 10.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn equal_balls_swap_velocities_without_gravity() {
+        let mut world = PhysicsWorld::new();
+        world.gravity = Vector3::zeros();
+        let mut left = ball(0.0);
+        left.position.x = -2.0;
+        left.velocity.x = 3.0;
+        left.restitution = 1.0;
+        let mut right = ball(0.0);
+        right.position.x = 2.0;
+        right.restitution = 1.0;
+        let (left, right) = (world.add(left), world.add(right));
+        run(&mut world, 2.0);
+
+        let (left, right) = (&world.bodies[left], &world.bodies[right]);
+        assert!(left.velocity.x.abs() < 0.05);
+        assert!((right.velocity.x - 3.0).abs() < 0.05);
+        // momentum is kept whatever happened in between
+        assert!((left.velocity + right.velocity - Vector3::new(3.0, 0.0, 0.0)).norm() < 1e-4);
+    }
+
+    #[test]
+    fn static_bodies_stay_put() {
+        let mut world = PhysicsWorld::new();
+        let mut wall = RigidBody::new(
+            BodyKind::Static,
+            Collider::cuboid(Vector3::repeat(1.0)),
+            1.0,
+        );
+        wall.position = Vector3::new(0.0, 0.0, 0.0);
+        let wall = world.add(wall);
+        let mut other = RigidBody::new(BodyKind::Static, Collider::Sphere(1.0), 1.0);
+        other.position = Vector3::new(0.5, 0.0, 0.0);
+        world.add(other);
+        let mut thrown = ball(0.0);
+        thrown.position.x = -3.0;
+        thrown.velocity.x = 10.0;
+        let thrown = world.add(thrown);
+        world.gravity = Vector3::zeros();
+        run(&mut world, 1.0);
+
+        assert_eq!(world.bodies[wall].position, Vector3::zeros());
+        assert_eq!(world.bodies[wall].velocity, Vector3::zeros());
+        // it came back off the wall instead of going through
+        assert!(world.bodies[thrown].velocity.x < 0.0);
+        assert!(world.bodies[thrown].position.x < -1.4);
+        // two statics overlapping never make a contact
+        assert!(world
+            .contacts
+            .iter()
+            .all(|c| !(world.bodies[c.a].is_static() && world.bodies[c.b].is_static())));
+    }
+
+    #[test]
+    #[should_panic]
+    fn dynamic_bodies_need_mass() {
+        RigidBody::new(BodyKind::Dynamic, Collider::Sphere(1.0), 0.0);
+    }
+
+    #[test]
+    fn off_centre_impulses_spin_bodies() {
+        let mut body = crate_at(Vector3::zeros());
+        body.apply_impulse(&Vector3::new(0.0, 0.0, 1.0), &Vector3::new(0.5, 0.0, 0.0));
+        assert_eq!(body.velocity, Vector3::new(0.0, 0.0, 1.0));
+        assert!(body.angular_velocity.y < 0.0);
+
+        body.lock_rotation();
+        body.apply_impulse(&Vector3::new(0.0, 0.0, 1.0), &Vector3::new(0.5, 0.0, 0.0));
+        assert_eq!(body.angular_velocity, Vector3::zeros());
+
+        let mut world = PhysicsWorld::new();
+        world.gravity = Vector3::zeros();
+        let index = world.add(crate_at(Vector3::zeros()));
+        world.bodies[index].apply_force(&Vector3::new(60.0, 0.0, 0.0), &Vector3::zeros());
+        world.fixed_step();
+        assert!((world.bodies[index].velocity.x - 1.0).abs() < 1e-5);
+        // forces only last a step
+        world.fixed_step();
+        assert!((world.bodies[index].velocity.x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn objects_follow_their_bodies() {
+        // an off centre mesh, scaled up, dropped onto the ground
+        let data = crate::mesh::transform(
+            &primitives::cube(1.0),
+            &nalgebra::Matrix4::new_translation(&Vector3::new(0.0, 0.5, 0.0)),
+        );
+        let mut object = Object::new(
+            Model::new(),
+            RenderBuffers::new(),
+            data.vertices,
+            Vec::new(),
+            Vec::new(),
+            Box::new(Physical::default()),
+        );
+        object.model.scale(Vector3::repeat(2.0));
+        object.model.translate(Vector3::new(1.0, 2.0, 0.0));
+
+        let body = RigidBody::from_object("crate", &object, BodyKind::Dynamic, 1.0).unwrap();
+        assert!((body.position - Vector3::new(2.0, 5.0, 0.0)).norm() < 1e-5);
+        assert!((body.center_of_mass - Vector3::new(0.0, 1.0, 0.0)).norm() < 1e-5);
+
+        let mut objects = HashMap::new();
+        objects.insert("crate".to_string(), object);
+        let mut world = PhysicsWorld::new();
+        world.add(ground());
+        world.add(body);
+        world.sync(&mut objects);
+        assert!((objects["crate"].model.position - Vector3::new(1.0, 2.0, 0.0)).norm() < 1e-5);
+
+        run(&mut world, 3.0);
+        world.sync(&mut objects);
+        // the mesh's bottom sits on the ground, its origin at the bottom
+        let model = &objects["crate"].model;
+        assert!(model.position.y.abs() < 0.02);
+        assert!((world.bodies[1].position.y - 1.0).abs() < 0.03);
+
+        // flat meshes can't be bodies
+        let plane = primitives::plane(2.0, 2.0, 1);
+        let flat = Object::new(
+            Model::new(),
+            RenderBuffers::new(),
+            plane.vertices,
+            Vec::new(),
+            Vec::new(),
+            Box::new(Physical::default()),
+        );
+        assert!(RigidBody::from_object("flat", &flat, BodyKind::Static, 1.0).is_none());
+    }
+}
@@ -0,0 +1,317 @@
+// pixel accurate selection. objects are drawn with their id instead of a colour into an integer
+// framebuffer the size of the window, which is read back under the cursor or over a rectangle.
+// ids are one based so 0 is left for the background, and only rendered when something asks
+
+use crate::{camera::Camera, render::Object, shader};
+
+pub struct PickingPass {
+    pub width: i32,
+    pub height: i32,
+    framebuffer: u32,
+    texture: u32,
+    depth: u32,
+    program: u32,
+    // object names by id - 1, as of the last render
+    names: Vec<String>,
+}
+
+impl PickingPass {
+    pub fn new(width: i32, height: i32) -> PickingPass {
+        let mut pass = PickingPass {
+            width,
+            height,
+            framebuffer: 0,
+            texture: 0,
+            depth: 0,
+            program: shader::get_or_load("blinn", "picking"),
+            names: Vec::new(),
+        };
+        pass.create_framebuffer();
+        pass
+    }
+
+    fn create_framebuffer(&mut self) {
+        unsafe {
+            gl::GenFramebuffers(1, &mut self.framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+
+            gl::GenTextures(1, &mut self.texture);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::R32UI as i32,
+                self.width,
+                self.height,
+                0,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                std::ptr::null(),
+            );
+            // integer textures can't be filtered
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                self.texture,
+                0,
+            );
+
+            gl::GenRenderbuffers(1, &mut self.depth);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH_COMPONENT32F,
+                self.width,
+                self.height,
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::RENDERBUFFER,
+                self.depth,
+            );
+
+            assert_eq!(
+                gl::CheckFramebufferStatus(gl::FRAMEBUFFER),
+                gl::FRAMEBUFFER_COMPLETE,
+                "picking framebuffer is incomplete"
+            );
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    // follows the window, the old attachments are thrown away
+    pub fn resize(&mut self, width: i32, height: i32) {
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.framebuffer);
+            gl::DeleteTextures(1, &self.texture);
+            gl::DeleteRenderbuffers(1, &self.depth);
+        }
+        self.width = width;
+        self.height = height;
+        self.create_framebuffer();
+    }
+
+    // draws every object's id from the camera, in the same viewport the camera draws to
+    pub fn render<'a>(
+        &mut self,
+        objects: impl Iterator<Item = (&'a String, &'a Object)>,
+        camera: &Camera,
+    ) {
+        let mut objects: Vec<(&String, &Object)> = objects.collect();
+        objects.sort_by(|a, b| a.0.cmp(b.0));
+        self.names = objects.iter().map(|(name, _)| name.to_string()).collect();
+
+        let (x, y, width, height) = camera.viewport_pixels(self.width, self.height);
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::Viewport(0, 0, self.width, self.height);
+            gl::ClearBufferuiv(gl::COLOR, 0, [0u32; 4].as_ptr());
+            camera.apply_depth_state();
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+            gl::Viewport(x, y, width, height);
+            gl::Enable(gl::DEPTH_TEST);
+            // ids are exact, no blending and no multisampling may mix them
+            gl::Disable(gl::BLEND);
+            gl::Disable(gl::MULTISAMPLE);
+            gl::Disable(gl::CULL_FACE);
+
+            gl::UseProgram(self.program);
+            camera.link_shader(self.program);
+            gl::Uniform1i(shader::get_shader_location(self.program, "instanced"), 0);
+            let model_loc = shader::get_shader_location(self.program, "model");
+            let id_loc = shader::get_shader_location(self.program, "objectId");
+
+            for (i, (_, object)) in objects.iter().enumerate() {
+                let model = object.model.get_model_matrix();
+                gl::UniformMatrix4fv(model_loc, 1, gl::FALSE, model.as_ptr());
+                gl::Uniform1ui(id_loc, i as u32 + 1);
+                object.buffers.bind();
+                gl::DrawArrays(gl::TRIANGLES, 0, object.buffers.size);
+            }
+
+            gl::BindVertexArray(0);
+            gl::Enable(gl::MULTISAMPLE);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    // the ids in a window space rectangle (y down, like sdl), row by row from the top left.
+    // the parts outside the framebuffer read as background
+    fn read(&self, x: i32, y: i32, width: i32, height: i32) -> Vec<u32> {
+        let mut ids = vec![0u32; (width * height) as usize];
+        let Some((gl_x, gl_y, gl_width, gl_height)) =
+            clamp_region(x, y, width, height, self.width, self.height)
+        else {
+            return ids;
+        };
+
+        let mut pixels = vec![0u32; (gl_width * gl_height) as usize];
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+            gl::ReadPixels(
+                gl_x,
+                gl_y,
+                gl_width,
+                gl_height,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                pixels.as_mut_ptr().cast(),
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+
+        // gl rows go bottom up, turn them back into the requested rectangle
+        for row in 0..gl_height {
+            let window_y = self.height - 1 - (gl_y + row);
+            for column in 0..gl_width {
+                let (rx, ry) = (gl_x + column - x, window_y - y);
+                ids[(ry * width + rx) as usize] = pixels[(row * gl_width + column) as usize];
+            }
+        }
+        ids
+    }
+
+    fn name(&self, id: u32) -> Option<&str> {
+        let index = (id as usize).checked_sub(1)?;
+        self.names.get(index).map(|name| name.as_str())
+    }
+
+    // the object under a window pixel. with a radius the nearest object within that many pixels
+    // counts, so thin or tiny things can still be clicked
+    pub fn pick(&self, x: i32, y: i32, radius: i32) -> Option<String> {
+        let size = radius * 2 + 1;
+        let ids = self.read(x - radius, y - radius, size, size);
+        let id = nearest_id(&ids, size, size, (radius, radius), radius)?;
+        self.name(id).map(|name| name.to_string())
+    }
+
+    // every object with at least one pixel inside the rectangle between two window corners
+    pub fn pick_rect(&self, from: (i32, i32), to: (i32, i32)) -> Vec<String> {
+        let (x, y) = (from.0.min(to.0), from.1.min(to.1));
+        let (width, height) = ((from.0 - to.0).abs() + 1, (from.1 - to.1).abs() + 1);
+        unique_ids(&self.read(x, y, width, height))
+            .into_iter()
+            .filter_map(|id| self.name(id).map(|name| name.to_string()))
+            .collect()
+    }
+}
+
+// a window space rectangle cut down to the framebuffer and flipped into gl's bottom up rows
+fn clamp_region(
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    target_width: i32,
+    target_height: i32,
+) -> Option<(i32, i32, i32, i32)> {
+    let (left, right) = (x.max(0), (x + width).min(target_width));
+    let (top, bottom) = (y.max(0), (y + height).min(target_height));
+    if left >= right || top >= bottom {
+        return None;
+    }
+    Some((left, target_height - bottom, right - left, bottom - top))
+}
+
+// the id closest to center within radius, ties going to whichever comes first
+fn nearest_id(
+    ids: &[u32],
+    width: i32,
+    height: i32,
+    center: (i32, i32),
+    radius: i32,
+) -> Option<u32> {
+    let mut best: Option<(i32, u32)> = None;
+    for y in 0..height {
+        for x in 0..width {
+            let id = ids[(y * width + x) as usize];
+            let distance = (x - center.0).pow(2) + (y - center.1).pow(2);
+            if id == 0 || distance > radius * radius {
+                continue;
+            }
+            if best.is_none_or(|(best_distance, _)| distance < best_distance) {
+                best = Some((distance, id));
+            }
+        }
+    }
+    best.map(|(_, id)| id)
+}
+
+fn unique_ids(ids: &[u32]) -> Vec<u32> {
+    let mut unique: Vec<u32> = ids.iter().copied().filter(|id| *id != 0).collect();
+    unique.sort_unstable();
+    unique.dedup();
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_are_clamped_and_flipped() {
+        // the top left 10x10 of a 100x50 target is gl's rows 40 to 49
+        assert_eq!(clamp_region(0, 0, 10, 10, 100, 50), Some((0, 40, 10, 10)));
+        // hanging off the bottom right corner
+        assert_eq!(clamp_region(95, 45, 10, 10, 100, 50), Some((95, 0, 5, 5)));
+        assert_eq!(clamp_region(-5, -5, 10, 10, 100, 50), Some((0, 45, 5, 5)));
+        assert_eq!(clamp_region(100, 0, 10, 10, 100, 50), None);
+        assert_eq!(clamp_region(-20, 0, 10, 10, 100, 50), None);
+    }
+
+    #[test]
+    fn the_pixel_under_the_cursor_wins() {
+        #[rustfmt::skip]
+        let ids = [
+            0, 0, 2, 0, 0,
+            0, 0, 0, 0, 0,
+            0, 0, 1, 0, 3,
+            0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0,
+        ];
+        assert_eq!(nearest_id(&ids, 5, 5, (2, 2), 2), Some(1));
+        assert_eq!(nearest_id(&ids, 5, 5, (2, 2), 0), Some(1));
+    }
+
+    #[test]
+    fn fuzzy_picks_take_the_nearest_pixel_in_the_radius() {
+        #[rustfmt::skip]
+        let ids = [
+            4, 0, 0, 0, 0,
+            0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0,
+            0, 0, 0, 5, 0,
+            0, 0, 0, 0, 0,
+        ];
+        assert_eq!(nearest_id(&ids, 5, 5, (2, 2), 2), Some(5));
+        assert_eq!(nearest_id(&ids, 5, 5, (2, 2), 1), None);
+
+        // the corners of the square are further than the radius
+        #[rustfmt::skip]
+        let corner = [
+            6, 0, 0,
+            0, 0, 0,
+            0, 0, 0,
+        ];
+        assert_eq!(nearest_id(&corner, 3, 3, (1, 1), 1), None);
+        assert_eq!(nearest_id(&[0; 9], 3, 3, (1, 1), 1), None);
+    }
+
+    #[test]
+    fn rectangles_collect_each_id_once() {
+        assert_eq!(unique_ids(&[0, 3, 3, 1, 0, 7, 1]), vec![1, 3, 7]);
+        assert!(unique_ids(&[0, 0, 0]).is_empty());
+    }
+}
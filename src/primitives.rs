@@ -0,0 +1,419 @@
+// procedural meshes so we can block out scenes without needing asset files.
+// everything is centered on the origin with +y up and counter-clockwise winding, and comes back
+// as the same flat triangle list that parse_obj produces
+
+use std::f32::consts::{PI, TAU};
+
+use nalgebra::{Vector2, Vector3};
+
+use crate::obj::{compute_tangents, ObjData};
+
+struct MeshBuilder {
+    positions: Vec<Vector3<f32>>,
+    normals: Vec<Vector3<f32>>,
+    uvs: Vec<Vector2<f32>>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn new() -> MeshBuilder {
+        MeshBuilder {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn push_vertex(&mut self, position: Vector3<f32>, normal: Vector3<f32>, uv: Vector2<f32>) -> u32 {
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.uvs.push(uv);
+        (self.positions.len() - 1) as u32
+    }
+
+    fn push_triangle(&mut self, a: u32, b: u32, c: u32) {
+        // drop the slivers we get at the poles of the lathed shapes
+        let pa = self.positions[a as usize];
+        let pb = self.positions[b as usize];
+        let pc = self.positions[c as usize];
+        if (pb - pa).cross(&(pc - pa)).norm_squared() <= f32::EPSILON * f32::EPSILON {
+            return;
+        }
+
+        self.indices.extend_from_slice(&[a, b, c]);
+    }
+
+    // a (cols + 1) x (rows + 1) grid of vertices, f maps (u, v) in [0, 1] to a position and normal.
+    // the partial derivatives of the surface must satisfy du x dv = outward normal
+    fn grid<F>(&mut self, cols: u32, rows: u32, f: F)
+    where
+        F: Fn(f32, f32) -> (Vector3<f32>, Vector3<f32>),
+    {
+        let start = self.positions.len() as u32;
+        for row in 0..=rows {
+            let v = row as f32 / rows as f32;
+            for col in 0..=cols {
+                let u = col as f32 / cols as f32;
+                let (position, normal) = f(u, v);
+                self.push_vertex(position, normal, Vector2::new(u, 1.0 - v));
+            }
+        }
+
+        let stride = cols + 1;
+        for row in 0..rows {
+            for col in 0..cols {
+                let i00 = start + row * stride + col;
+                let i10 = i00 + 1;
+                let i01 = i00 + stride;
+                let i11 = i01 + 1;
+                self.push_triangle(i00, i10, i01);
+                self.push_triangle(i10, i11, i01);
+            }
+        }
+    }
+
+    // sweeps a profile of (radius, y, normal radius, normal y) rows around the y axis, top to bottom
+    fn lathe(&mut self, profile: &[(f32, f32, f32, f32)], segments: u32) {
+        let rows = (profile.len() - 1) as u32;
+        self.grid(segments, rows, |u, v| {
+            let (radius, y, n_r, n_y) = profile[(v * rows as f32).round() as usize];
+            let (sin, cos) = (u * TAU).sin_cos();
+            (
+                Vector3::new(radius * cos, y, radius * sin),
+                Vector3::new(n_r * cos, n_y, n_r * sin).normalize(),
+            )
+        });
+    }
+
+    fn disk(&mut self, radius: f32, y: f32, facing_up: bool, segments: u32) {
+        let normal = if facing_up {
+            Vector3::y()
+        } else {
+            -Vector3::y()
+        };
+        let center = self.push_vertex(Vector3::new(0.0, y, 0.0), normal, Vector2::new(0.5, 0.5));
+
+        let ring: Vec<u32> = (0..=segments)
+            .map(|i| {
+                let (sin, cos) = (i as f32 / segments as f32 * TAU).sin_cos();
+                self.push_vertex(
+                    Vector3::new(radius * cos, y, radius * sin),
+                    normal,
+                    Vector2::new(0.5 + 0.5 * cos, 0.5 + 0.5 * sin),
+                )
+            })
+            .collect();
+
+        for pair in ring.windows(2) {
+            if facing_up {
+                self.push_triangle(center, pair[1], pair[0]);
+            } else {
+                self.push_triangle(center, pair[0], pair[1]);
+            }
+        }
+    }
+
+    fn build(self) -> ObjData {
+        let mut data = ObjData::new();
+        for &index in &self.indices {
+            data.vertices.push(self.positions[index as usize]);
+            data.normals.push(self.normals[index as usize]);
+            data.tex_coords.push(self.uvs[index as usize]);
+        }
+        data.tangents = compute_tangents(&data.vertices, &data.normals, &data.tex_coords);
+        data.indices = (0..data.vertices.len() as u32).collect();
+
+        data
+    }
+}
+
+pub fn cube(size: f32) -> ObjData {
+    let half = size * 0.5;
+    let faces = [
+        (Vector3::x(), Vector3::z()),
+        (-Vector3::x(), -Vector3::z()),
+        (Vector3::y(), Vector3::x()),
+        (-Vector3::y(), Vector3::x()),
+        (Vector3::z(), -Vector3::x()),
+        (-Vector3::z(), Vector3::x()),
+    ];
+
+    let mut builder = MeshBuilder::new();
+    for (normal, u_axis) in faces {
+        let v_axis = normal.cross(&u_axis);
+        builder.grid(1, 1, |u, v| {
+            (
+                normal * half + u_axis * (u - 0.5) * size + v_axis * (v - 0.5) * size,
+                normal,
+            )
+        });
+    }
+
+    builder.build()
+}
+
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> ObjData {
+    let segments = segments.max(3);
+    let rings = rings.max(2);
+
+    let profile: Vec<(f32, f32, f32, f32)> = (0..=rings)
+        .map(|i| {
+            let (sin, cos) = (i as f32 / rings as f32 * PI).sin_cos();
+            (radius * sin, radius * cos, sin, cos)
+        })
+        .collect();
+
+    let mut builder = MeshBuilder::new();
+    builder.lathe(&profile, segments);
+    builder.build()
+}
+
+pub fn icosphere(radius: f32, subdivisions: u32) -> ObjData {
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let mut points: Vec<Vector3<f32>> = [
+        (-1.0, t, 0.0),
+        (1.0, t, 0.0),
+        (-1.0, -t, 0.0),
+        (1.0, -t, 0.0),
+        (0.0, -1.0, t),
+        (0.0, 1.0, t),
+        (0.0, -1.0, -t),
+        (0.0, 1.0, -t),
+        (t, 0.0, -1.0),
+        (t, 0.0, 1.0),
+        (-t, 0.0, -1.0),
+        (-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|&(x, y, z)| Vector3::new(x, y, z).normalize())
+    .collect();
+
+    let mut faces: Vec<[usize; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = std::collections::HashMap::new();
+        let mut midpoint = |a: usize, b: usize, points: &mut Vec<Vector3<f32>>| -> usize {
+            let key = (a.min(b), a.max(b));
+            *midpoints.entry(key).or_insert_with(|| {
+                points.push(((points[a] + points[b]) * 0.5).normalize());
+                points.len() - 1
+            })
+        };
+
+        let mut next = Vec::with_capacity(faces.len() * 4);
+        for [a, b, c] in faces {
+            let ab = midpoint(a, b, &mut points);
+            let bc = midpoint(b, c, &mut points);
+            let ca = midpoint(c, a, &mut points);
+            next.push([a, ab, ca]);
+            next.push([b, bc, ab]);
+            next.push([c, ca, bc]);
+            next.push([ab, bc, ca]);
+        }
+        faces = next;
+    }
+
+    let spherical_uv = |p: &Vector3<f32>| {
+        Vector2::new(
+            0.5 + p.z.atan2(p.x) / TAU,
+            0.5 + p.y.clamp(-1.0, 1.0).asin() / PI,
+        )
+    };
+
+    let mut builder = MeshBuilder::new();
+    for face in faces {
+        let mut uvs = face.map(|i| spherical_uv(&points[i]));
+
+        // triangles straddling the seam would otherwise interpolate across the whole texture
+        let max_u = uvs.iter().fold(0.0_f32, |acc, uv| acc.max(uv.x));
+        if max_u - uvs.iter().fold(1.0_f32, |acc, uv| acc.min(uv.x)) > 0.5 {
+            for uv in uvs.iter_mut() {
+                if uv.x < 0.5 {
+                    uv.x += 1.0;
+                }
+            }
+        }
+
+        let tri: Vec<u32> = face
+            .iter()
+            .zip(uvs)
+            .map(|(&i, uv)| builder.push_vertex(points[i] * radius, points[i], uv))
+            .collect();
+        builder.push_triangle(tri[0], tri[1], tri[2]);
+    }
+
+    builder.build()
+}
+
+pub fn plane(width: f32, depth: f32, subdivisions: u32) -> ObjData {
+    let cells = subdivisions + 1;
+    let mut builder = MeshBuilder::new();
+    builder.grid(cells, cells, |u, v| {
+        (
+            Vector3::new((u - 0.5) * width, 0.0, (0.5 - v) * depth),
+            Vector3::y(),
+        )
+    });
+    builder.build()
+}
+
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> ObjData {
+    let segments = segments.max(3);
+    let half = height * 0.5;
+
+    let mut builder = MeshBuilder::new();
+    builder.lathe(&[(radius, half, 1.0, 0.0), (radius, -half, 1.0, 0.0)], segments);
+    builder.disk(radius, half, true, segments);
+    builder.disk(radius, -half, false, segments);
+    builder.build()
+}
+
+pub fn cone(radius: f32, height: f32, segments: u32) -> ObjData {
+    let segments = segments.max(3);
+    let half = height * 0.5;
+
+    // the side normal leans up by the slope of the cone
+    let mut builder = MeshBuilder::new();
+    builder.lathe(&[(0.0, half, height, radius), (radius, -half, height, radius)], segments);
+    builder.disk(radius, -half, false, segments);
+    builder.build()
+}
+
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+) -> ObjData {
+    let mut builder = MeshBuilder::new();
+    builder.grid(major_segments.max(3), minor_segments.max(3), |u, v| {
+        let (sin_phi, cos_phi) = (u * TAU).sin_cos();
+        let (sin_theta, cos_theta) = (v * TAU).sin_cos();
+        let normal = Vector3::new(cos_theta * cos_phi, -sin_theta, cos_theta * sin_phi);
+        let center = Vector3::new(major_radius * cos_phi, 0.0, major_radius * sin_phi);
+        (center + normal * minor_radius, normal)
+    });
+    builder.build()
+}
+
+// height is the length of the cylindrical section, the full capsule is height + 2 * radius tall
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> ObjData {
+    let half = height * 0.5;
+    let rings = rings.max(1);
+
+    let mut profile = Vec::new();
+    for i in 0..=rings {
+        let (sin, cos) = (i as f32 / rings as f32 * PI * 0.5).sin_cos();
+        profile.push((radius * sin, half + radius * cos, sin, cos));
+    }
+    for i in 0..=rings {
+        let (sin, cos) = (PI * 0.5 + i as f32 / rings as f32 * PI * 0.5).sin_cos();
+        profile.push((radius * sin, -half + radius * cos, sin, cos));
+    }
+
+    let mut builder = MeshBuilder::new();
+    builder.lathe(&profile, segments.max(3));
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::get_bounding_box;
+
+    fn all_primitives() -> Vec<(&'static str, ObjData)> {
+        vec![
+            ("cube", cube(2.0)),
+            ("uv_sphere", uv_sphere(1.0, 16, 8)),
+            ("icosphere", icosphere(1.0, 2)),
+            ("plane", plane(4.0, 2.0, 3)),
+            ("cylinder", cylinder(1.0, 2.0, 12)),
+            ("cone", cone(1.0, 2.0, 12)),
+            ("torus", torus(2.0, 0.5, 16, 8)),
+            ("capsule", capsule(0.5, 1.0, 12, 4)),
+        ]
+    }
+
+    #[test]
+    fn attributes_line_up() {
+        for (name, data) in all_primitives() {
+            let count = data.vertices.len();
+            assert!(count > 0 && count % 3 == 0, "{name}");
+            assert_eq!(data.normals.len(), count, "{name}");
+            assert_eq!(data.tex_coords.len(), count, "{name}");
+            assert_eq!(data.tangents.len(), count, "{name}");
+            assert_eq!(data.indices.len(), count, "{name}");
+        }
+    }
+
+    #[test]
+    fn winding_faces_outward() {
+        for (name, data) in all_primitives() {
+            for (t, tri) in data.vertices.chunks_exact(3).enumerate() {
+                let face = (tri[1] - tri[0]).cross(&(tri[2] - tri[0]));
+                let normal = data.normals[t * 3] + data.normals[t * 3 + 1] + data.normals[t * 3 + 2];
+                assert!(face.dot(&normal) > 0.0, "{name} triangle {t} is wound inward");
+            }
+        }
+    }
+
+    #[test]
+    fn normals_and_tangents_are_orthonormal() {
+        for (name, data) in all_primitives() {
+            for (n, t) in data.normals.iter().zip(&data.tangents) {
+                assert!((n.norm() - 1.0).abs() < 1e-4, "{name}");
+                assert!((t.norm() - 1.0).abs() < 1e-4, "{name}");
+                assert!(n.dot(t).abs() < 1e-4, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn expected_sizes() {
+        let bb = get_bounding_box(&cube(2.0).vertices);
+        assert_eq!((bb.x_min, bb.x_max, bb.y_min, bb.y_max), (-1.0, 1.0, -1.0, 1.0));
+        assert_eq!(cube(1.0).vertices.len(), 36);
+
+        let bb = get_bounding_box(&plane(4.0, 2.0, 3).vertices);
+        assert_eq!((bb.x_min, bb.x_max, bb.z_min, bb.z_max), (-2.0, 2.0, -1.0, 1.0));
+        assert_eq!(plane(1.0, 1.0, 3).vertices.len(), 4 * 4 * 6);
+
+        for p in uv_sphere(3.0, 16, 8).vertices.iter().chain(&icosphere(3.0, 1).vertices) {
+            assert!((p.norm() - 3.0).abs() < 1e-4);
+        }
+
+        let bb = get_bounding_box(&capsule(0.5, 1.0, 12, 4).vertices);
+        assert!((bb.y_max - 1.0).abs() < 1e-5 && (bb.y_min + 1.0).abs() < 1e-5);
+
+        let bb = get_bounding_box(&torus(2.0, 0.5, 16, 8).vertices);
+        assert!((bb.x_max - 2.5).abs() < 1e-5 && (bb.y_max - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn icosphere_triangle_count() {
+        assert_eq!(icosphere(1.0, 0).vertices.len(), 20 * 3);
+        assert_eq!(icosphere(1.0, 2).vertices.len(), 20 * 16 * 3);
+    }
+}
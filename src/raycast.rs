@@ -1,6 +1,6 @@
-use nalgebra::{Matrix4, Point3, Vector3};
+use nalgebra::{Matrix4, Point3, Vector2, Vector3};
 
-use crate::{collision::BoundingBox, scene::Scene};
+use crate::collision::BoundingBox;
 
 pub struct Ray {
     pub origin: Point3<f32>,
@@ -29,70 +29,164 @@ impl Ray {
         Some(intersection)
     }
 
-    pub fn intersect_bounding_box(&self, bbox: &BoundingBox) -> Option<(Point3<f32>, Point3<f32>)> {
-        let epsilon = 1e-6;
+    // distance along the ray to where it enters the box and the normal of the face it goes in
+    // through. a ray starting inside the box hits it straight away, facing back along the ray
+    pub fn intersect_bounding_box(&self, bbox: &BoundingBox) -> Option<(f32, Vector3<f32>)> {
+        let min = bbox.min();
+        let max = bbox.max();
+        let mut t_enter = f32::NEG_INFINITY;
+        let mut t_exit = f32::INFINITY;
+        let mut normal = -self.direction;
+
+        for axis in 0..3 {
+            let origin = self.origin[axis];
+            let direction = self.direction[axis];
+            if direction.abs() < 1e-8 {
+                // parallel to this slab, either always inside it or never
+                if origin < min[axis] || origin > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let near = (min[axis] - origin) / direction;
+            let far = (max[axis] - origin) / direction;
+            let (near, far, side) = if near <= far {
+                (near, far, -1.0)
+            } else {
+                (far, near, 1.0)
+            };
+
+            if near > t_enter {
+                t_enter = near;
+                normal = Vector3::zeros();
+                normal[axis] = side;
+            }
+            t_exit = t_exit.min(far);
+        }
 
-        let inv_dir_x = if self.direction.x.abs() > epsilon {
-            1.0 / self.direction.x
-        } else {
-            f32::INFINITY
-        };
-        let inv_dir_y = if self.direction.y.abs() > epsilon {
-            1.0 / self.direction.y
-        } else {
-            f32::INFINITY
-        };
-        let inv_dir_z = if self.direction.z.abs() > epsilon {
-            1.0 / self.direction.z
+        if t_enter > t_exit || t_exit < 0.0 {
+            None
+        } else if t_enter < 0.0 {
+            Some((0.0, -self.direction))
         } else {
-            f32::INFINITY
-        };
+            Some((t_enter, normal))
+        }
+    }
+}
 
-        let t1 = (bbox.x_min - self.origin.x) * inv_dir_x;
-        let t2 = (bbox.x_max - self.origin.x) * inv_dir_x;
-        let t3 = (bbox.y_min - self.origin.y) * inv_dir_y;
-        let t4 = (bbox.y_max - self.origin.y) * inv_dir_y;
-        let t5 = (bbox.z_min - self.origin.z) * inv_dir_z;
-        let t6 = (bbox.z_max - self.origin.z) * inv_dir_z;
+impl Ray {
+    // möller-trumbore. distance along the ray and the barycentric u and v of b and c, from either
+    // side of the triangle. edges get a little slack so rays through shared edges and vertices
+    // can't slip between neighbouring triangles
+    pub fn intersect_triangle(
+        &self,
+        a: &Vector3<f32>,
+        b: &Vector3<f32>,
+        c: &Vector3<f32>,
+    ) -> Option<(f32, f32, f32)> {
+        let epsilon = 1e-7;
+        let slack = 1e-5;
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(&edge2);
+        let determinant = edge1.dot(&p);
+        // parallel to the triangle, or the triangle has no area
+        if determinant.abs() < epsilon {
+            return None;
+        }
 
-        let t_min = t1.min(t2).max(t3.min(t4)).max(t5.min(t6));
-        let t_max = t1.max(t2).min(t3.max(t4)).min(t5.max(t6));
+        let inverse = 1.0 / determinant;
+        let s = self.origin.coords - a;
+        let u = s.dot(&p) * inverse;
+        if !(-slack..=1.0 + slack).contains(&u) {
+            return None;
+        }
 
-        if t_min > t_max + epsilon || t_max < epsilon {
-            None
+        let q = s.cross(&edge1);
+        let v = self.direction.dot(&q) * inverse;
+        if v < -slack || u + v > 1.0 + slack {
+            return None;
+        }
+
+        let t = edge2.dot(&q) * inverse;
+        if t > epsilon {
+            Some((t, u, v))
         } else {
-            let entry_point = self.origin + self.direction * t_min;
-            let exit_point = self.origin + self.direction * t_max;
-            Some((entry_point, exit_point))
+            None
         }
     }
 }
 
-pub fn ray_intersect_bb_projection(sc: &mut Scene, x: i32, y: i32) -> Option<Vector3<f32>> {
-    let aspect: f32 = (sc.settings.screen_width as f32) / (sc.settings.screen_height as f32);
-    let projection = Matrix4::new_perspective(aspect, sc.settings.fovy, 0.1, 10000.0);
-    let main_camera = sc.cameras.get_mut(&sc.active_camera).unwrap();
+// one object a ray went through, see Scene::raycast
+#[derive(Debug, Clone, PartialEq)]
+pub struct RayHit {
+    pub object: String,
+    pub distance: f32,
+    pub point: Point3<f32>,
+    pub normal: Vector3<f32>,
+    // set when the object's triangles were tested, not just its bounds
+    pub triangle: Option<usize>,
+    pub barycentric: Option<Vector3<f32>>,
+    pub uv: Option<Vector2<f32>>,
+}
 
-    let view_projection_matrix = projection * main_camera.view_matrix();
-    let inverse_vp_matrix = view_projection_matrix.try_inverse().unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let ndc_x = (x as f32 / sc.settings.screen_width as f32) * 2.0 - 1.0;
-    let ndc_y = 1.0 - (y as f32 / sc.settings.screen_height as f32) * 2.0;
+    fn unit_box() -> BoundingBox {
+        BoundingBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0)
+    }
 
-    let ndc_point = Point3::new(ndc_x, ndc_y, -1.0);
-    let world_point = inverse_vp_matrix.transform_point(&ndc_point);
-    let ray_direction = (world_point - main_camera.position).normalize();
+    fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray {
+        Ray {
+            origin: Point3::from(origin),
+            direction: Vector3::from(direction).normalize(),
+        }
+    }
 
-    let ray = Ray {
-        origin: main_camera.position,
-        direction: ray_direction,
-    };
+    #[test]
+    fn hits_report_distance_and_face_normal() {
+        let (distance, normal) = ray([0.0, 0.0, 5.0], [0.0, 0.0, -1.0])
+            .intersect_bounding_box(&unit_box())
+            .unwrap();
+        assert_eq!(distance, 4.0);
+        assert_eq!(normal, Vector3::new(0.0, 0.0, 1.0));
+
+        let (distance, normal) = ray([-3.0, 0.5, 0.0], [1.0, 0.0, 0.0])
+            .intersect_bounding_box(&unit_box())
+            .unwrap();
+        assert_eq!(distance, 2.0);
+        assert_eq!(normal, Vector3::new(-1.0, 0.0, 0.0));
+
+        // coming down at an angle onto the top face
+        let (distance, normal) = ray([0.0, 3.0, -2.0], [0.0, -1.0, 1.0])
+            .intersect_bounding_box(&unit_box())
+            .unwrap();
+        assert!((distance - 2.0_f32.sqrt() * 2.0).abs() < 1e-5);
+        assert_eq!(normal, Vector3::new(0.0, 1.0, 0.0));
+    }
 
-    let floor = sc.object_map.get_mut(&"main_plain".to_string()).unwrap();
+    #[test]
+    fn misses_boxes_beside_and_behind() {
+        assert!(ray([0.0, 2.0, 5.0], [0.0, 0.0, -1.0])
+            .intersect_bounding_box(&unit_box())
+            .is_none());
+        assert!(ray([0.0, 0.0, 5.0], [0.0, 0.0, 1.0])
+            .intersect_bounding_box(&unit_box())
+            .is_none());
+        assert!(ray([3.0, 3.0, 0.0], [1.0, -1.0, 0.0])
+            .intersect_bounding_box(&unit_box())
+            .is_none());
+    }
 
-    if let Some((entry_point, _)) = ray.intersect_bounding_box(&floor.bounding_box) {
-        Some(Vector3::new(entry_point.x, entry_point.y, entry_point.z))
-    } else {
-        None
+    #[test]
+    fn starting_inside_hits_at_once() {
+        let (distance, normal) = ray([0.0, 0.0, 0.0], [1.0, 0.0, 0.0])
+            .intersect_bounding_box(&unit_box())
+            .unwrap();
+        assert_eq!(distance, 0.0);
+        assert_eq!(normal, Vector3::new(-1.0, 0.0, 0.0));
     }
 }
@@ -5,8 +5,12 @@ use nalgebra::{OVector, Point, Unit, Vector, Vector2};
 
 use crate::{
     buffers::RenderBuffers,
+    bvh::MeshBvh,
+    camera::Camera,
     collision::{self, BoundingBox},
-    material::Material,
+    lod::{self, LodGroup, LodLevel, LodMetric},
+    material::{Material, Physical},
+    obj::ObjData,
     vertex::Vertex,
 };
 
@@ -74,6 +78,17 @@ impl Model {
     }
 }
 
+// counters for what made it to the screen in a frame, reset at the start of each frame
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RenderStats {
+    pub drawn: u32,
+    pub culled: u32,
+    pub draw_calls: u32,
+    pub program_switches: u32,
+    pub material_switches: u32,
+    pub mesh_switches: u32,
+}
+
 pub struct Object {
     pub model: Model,
     pub buffers: RenderBuffers,
@@ -81,8 +96,12 @@ pub struct Object {
     pub vertices: Vec<Vector3<f32>>,
     pub normals: Vec<Vector3<f32>>,
     pub uvs: Vec<Vector2<f32>>,
+    // empty until set, init computes them from the uvs then
+    pub tangents: Vec<Vector3<f32>>,
     pub bounding_box: collision::BoundingBox,
-    pub shader_program: u32,
+    pub lod: Option<LodGroup>,
+    // triangle level ray queries in model space, built by init
+    pub bvh: Option<MeshBvh>,
 }
 
 impl Object {
@@ -100,20 +119,100 @@ impl Object {
             vertices: v,
             normals: n,
             uvs: uv,
+            tangents: Vec::new(),
             material,
             bounding_box: BoundingBox::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
-            shader_program: 0,
+            lod: None,
+            bvh: None,
         }
     }
 
+    // an object with an obj's mesh, drawn with the material from its .mtl
+    pub fn from_obj(data: &ObjData) -> Object {
+        let mut object = Object::new(
+            Model::new(),
+            RenderBuffers::new(),
+            data.vertices.clone(),
+            data.normals.clone(),
+            data.tex_coords.clone(),
+            Box::new(Physical::from_obj(data)),
+        );
+        object.tangents = data.tangents.clone();
+        object
+    }
+
+    // adds a coarser mesh, see LodLevel for how the threshold is interpreted with each metric.
+    // every level of an object is measured the same way
+    pub fn add_lod(&mut self, data: &ObjData, metric: LodMetric, threshold: f32) {
+        let lod = self.lod.get_or_insert_with(|| LodGroup::new(metric));
+        assert_eq!(lod.metric, metric, "lod levels of one object must share a metric");
+        lod.add_level(LodLevel::new(data, threshold));
+    }
+
     pub fn init(&mut self) {
         // initialize our buffers
-        self.buffers.init(&self.vertices, &self.normals, &self.uvs);
+        self.buffers
+            .init(&self.vertices, &self.normals, &self.uvs, &self.tangents);
 
         // calculate centroid
         // self.model.calculate_centroid(self.vertices);
         // etc etc
 
         self.bounding_box = collision::get_bounding_box(&self.vertices);
+        self.bvh = Some(MeshBvh::new(&self.vertices));
+
+        if let Some(lod) = &mut self.lod {
+            for level in lod.levels.iter_mut() {
+                level.init();
+            }
+        }
+    }
+
+    pub fn world_bounding_box(&self) -> BoundingBox {
+        self.bounding_box.transform_by(&self.model)
+    }
+
+    // world space bounding sphere, used for lod selection
+    pub fn bounding_sphere(&self) -> (Vector3<f32>, f32) {
+        let bb = &self.bounding_box;
+        let min = Vector3::new(bb.x_min, bb.y_min, bb.z_min);
+        let max = Vector3::new(bb.x_max, bb.y_max, bb.z_max);
+
+        let center = self
+            .model
+            .get_model_matrix()
+            .transform_point(&((min + max) * 0.5).into());
+        let radius = (max - min).norm() * 0.5 * self.model.scale.abs().max();
+
+        (center.coords, radius)
+    }
+
+    pub fn update_lod(&mut self, camera: &Camera, delta_time: f32) {
+        let (center, radius) = self.bounding_sphere();
+        let Some(lod) = &mut self.lod else {
+            return;
+        };
+
+        let distance = ((center - camera.position.coords).norm() - radius).max(0.0);
+        let value = match lod.metric {
+            LodMetric::Distance => distance,
+            LodMetric::ScreenSize => lod::screen_size(radius, distance + radius, &camera.projection),
+        };
+        lod.select(value, delta_time);
+    }
+
+    // the buffers to draw this frame with the dither fade to pass to the shader as lodFade
+    pub fn lod_draws(&self) -> Vec<(&RenderBuffers, f32)> {
+        match &self.lod {
+            Some(lod) => lod
+                .draws()
+                .into_iter()
+                .map(|(level, fade)| match level {
+                    0 => (&self.buffers, fade),
+                    n => (&lod.levels[n - 1].buffers, fade),
+                })
+                .collect(),
+            None => vec![(&self.buffers, 0.0)],
+        }
     }
 }
@@ -0,0 +1,387 @@
+// collects everything visible in a frame, sorts it to keep gl state changes down and then draws it.
+// opaque items are grouped by shader program, then material, then mesh and drawn front to back
+// within a group, transparent items are drawn back to front after all of them
+
+use std::cmp::Ordering;
+
+use nalgebra::{Matrix4, Point3};
+
+use crate::{
+    camera::Camera,
+    collision::BoundingBox,
+    frustum::Frustum,
+    instancing::InstancedObject,
+    material::{self, AlphaMode, Material},
+    render::{Object, RenderStats},
+    scene::Scene,
+    shader,
+};
+
+pub enum Drawable<'a> {
+    Object(&'a Object),
+    Instanced(&'a InstancedObject),
+}
+
+impl Drawable<'_> {
+    fn material(&self) -> &dyn Material {
+        match self {
+            Drawable::Object(object) => object.material.as_ref(),
+            Drawable::Instanced(instanced) => instanced.material.as_ref(),
+        }
+    }
+}
+
+pub struct DrawItem<'a> {
+    pub drawable: Drawable<'a>,
+    pub program: u32,
+    // see material::batch_key
+    pub material: u64,
+    pub mesh: u32,
+    // distance along the view direction to the center of the world bounds
+    pub depth: f32,
+}
+
+impl DrawItem<'_> {
+    fn opaque_order(&self, other: &DrawItem) -> Ordering {
+        self.program
+            .cmp(&other.program)
+            .then(self.material.cmp(&other.material))
+            .then(self.mesh.cmp(&other.mesh))
+            .then(self.depth.total_cmp(&other.depth))
+    }
+}
+
+pub struct RenderQueue<'a> {
+    pub opaque: Vec<DrawItem<'a>>,
+    pub transparent: Vec<DrawItem<'a>>,
+    pub stats: RenderStats,
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new() -> RenderQueue<'a> {
+        RenderQueue {
+            opaque: Vec::new(),
+            transparent: Vec::new(),
+            stats: RenderStats::default(),
+        }
+    }
+
+    // queues every object in the scene that survives frustum culling
+    pub fn collect(&mut self, scene: &'a Scene, view: &Matrix4<f32>, frustum: &Frustum) {
+        for object in scene.object_map.values() {
+            self.push(
+                Drawable::Object(object),
+                object.buffers.vao,
+                &object.world_bounding_box(),
+                view,
+                frustum,
+            );
+        }
+
+        for instanced in scene.instanced_objects.values() {
+            self.push(
+                Drawable::Instanced(instanced),
+                instanced.buffers.vao,
+                &instanced.world_bounding_box(),
+                view,
+                frustum,
+            );
+        }
+    }
+
+    fn push(
+        &mut self,
+        drawable: Drawable<'a>,
+        mesh: u32,
+        world_box: &BoundingBox,
+        view: &Matrix4<f32>,
+        frustum: &Frustum,
+    ) {
+        if !frustum.intersects_aabb(world_box) {
+            self.stats.culled += 1;
+            return;
+        }
+
+        let center = view.transform_point(&Point3::from(world_box.center()));
+        let transparent = drawable.material().is_transparent();
+        let item = DrawItem {
+            material: material::batch_key(drawable.material()),
+            program: material::program(drawable.material()),
+            drawable,
+            mesh,
+            depth: -center.z,
+        };
+
+        if transparent {
+            self.transparent.push(item);
+        } else {
+            self.opaque.push(item);
+        }
+    }
+
+    pub fn sort(&mut self) {
+        self.opaque.sort_by(|a, b| a.opaque_order(b));
+        self.transparent.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+    }
+
+    pub fn execute(&mut self, scene: &Scene, camera: &Camera, resolution: (i32, i32)) -> RenderStats {
+        let mut current_program = None;
+        let mut current_material = None;
+        let mut current_mesh = None;
+        let mut current_state = None;
+        let mut uniforms = DrawUniforms::default();
+
+        for item in self.opaque.iter().chain(self.transparent.iter()) {
+            if current_program != Some(item.program) {
+                unsafe {
+                    gl::UseProgram(item.program);
+                }
+                link_frame_uniforms(scene, camera, resolution, item.program);
+                uniforms = DrawUniforms::new(item.program);
+                current_program = Some(item.program);
+                // uniforms live on the program, so the material has to be linked again
+                current_material = None;
+                self.stats.program_switches += 1;
+            }
+
+            if current_material != Some(item.material) {
+                let material = item.drawable.material();
+                material::link_material(material, item.program);
+                current_material = Some(item.material);
+                self.stats.material_switches += 1;
+
+                let state = (material.alpha_mode(), material.double_sided());
+                if current_state != Some(state) {
+                    apply_render_state(state.0, state.1);
+                    current_state = Some(state);
+                }
+            }
+
+            match item.drawable {
+                Drawable::Object(object) => unsafe {
+                    gl::Uniform1i(uniforms.instanced, 0);
+                    let model = object.model.get_model_matrix();
+                    gl::UniformMatrix4fv(uniforms.model, 1, gl::FALSE, model.as_ptr());
+
+                    // two levels are drawn while cross fading between them
+                    for (buffers, fade) in object.lod_draws() {
+                        if current_mesh != Some(buffers.vao) {
+                            buffers.bind();
+                            current_mesh = Some(buffers.vao);
+                            self.stats.mesh_switches += 1;
+                        }
+                        gl::Uniform1f(uniforms.lod_fade, fade);
+                        gl::DrawArrays(gl::TRIANGLES, 0, buffers.size);
+                        self.stats.draw_calls += 1;
+                    }
+                    self.stats.drawn += 1;
+                },
+                Drawable::Instanced(instanced) => unsafe {
+                    gl::Uniform1i(uniforms.instanced, 1);
+                    gl::Uniform1f(uniforms.lod_fade, 0.0);
+                    if current_mesh != Some(item.mesh) {
+                        instanced.buffers.bind();
+                        current_mesh = Some(item.mesh);
+                        self.stats.mesh_switches += 1;
+                    }
+                    instanced.draw();
+                    self.stats.draw_calls += 1;
+                    self.stats.drawn += instanced.len() as u32;
+                },
+            }
+        }
+
+        unsafe {
+            gl::BindVertexArray(0);
+        }
+        apply_render_state(AlphaMode::Opaque, true);
+
+        self.stats
+    }
+}
+
+// locations of the uniforms set for every draw, looked up once each time the program changes
+#[derive(Default)]
+struct DrawUniforms {
+    instanced: i32,
+    lod_fade: i32,
+    model: i32,
+}
+
+impl DrawUniforms {
+    fn new(program: u32) -> DrawUniforms {
+        DrawUniforms {
+            instanced: shader::get_shader_location(program, "instanced"),
+            lod_fade: shader::get_shader_location(program, "lodFade"),
+            model: shader::get_shader_location(program, "model"),
+        }
+    }
+}
+
+// blending, depth writes and face culling for a material
+fn apply_render_state(alpha_mode: AlphaMode, double_sided: bool) {
+    unsafe {
+        match alpha_mode {
+            AlphaMode::Opaque | AlphaMode::Mask(_) => {
+                gl::Disable(gl::BLEND);
+                gl::DepthMask(gl::TRUE);
+            }
+            // transparent surfaces still test against depth but don't write it, so everything
+            // behind them that is drawn later (they're sorted back to front) still shows through
+            AlphaMode::Blend => {
+                gl::Enable(gl::BLEND);
+                gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+                gl::DepthMask(gl::FALSE);
+            }
+            AlphaMode::Additive => {
+                gl::Enable(gl::BLEND);
+                gl::BlendFunc(gl::SRC_ALPHA, gl::ONE);
+                gl::DepthMask(gl::FALSE);
+            }
+        }
+
+        if double_sided {
+            gl::Disable(gl::CULL_FACE);
+        } else {
+            gl::Enable(gl::CULL_FACE);
+            gl::CullFace(gl::BACK);
+        }
+    }
+}
+
+// everything that is the same for every object drawn with a program this frame
+fn link_frame_uniforms(scene: &Scene, camera: &Camera, resolution: (i32, i32), program: u32) {
+    unsafe {
+        // view and projection both come from the camera
+        camera.link_shader(program);
+        for (index, pl) in scene.point_lights.iter().enumerate() {
+            pl.link_shader(program, index.try_into().unwrap());
+        }
+
+        // link the dir light if it is present
+        if let Some(dir_light) = &scene.directional_light {
+            dir_light.link_shader(program);
+        }
+
+        gl::Uniform1i(
+            shader::get_shader_location(program, "numPointLights"),
+            scene.point_lights.len().try_into().unwrap(),
+        );
+
+        gl::Uniform2f(
+            shader::get_shader_location(program, "resolution"),
+            resolution.0 as f32,
+            resolution.1 as f32,
+        );
+
+        gl::Uniform1i(
+            shader::get_shader_location(program, "debugView"),
+            scene.settings.debug_view.shader_value(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{buffers::RenderBuffers, material::Physical, render::Model};
+    use nalgebra::Vector3;
+
+    fn object() -> Object {
+        Object::new(
+            Model::new(),
+            RenderBuffers::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Box::new(Physical::default()),
+        )
+    }
+
+    fn item(object: &Object, program: u32, material: u64, mesh: u32, depth: f32) -> DrawItem<'_> {
+        DrawItem {
+            drawable: Drawable::Object(object),
+            program,
+            material,
+            mesh,
+            depth,
+        }
+    }
+
+    fn order(items: &[DrawItem]) -> Vec<(u32, u64, u32, f32)> {
+        items
+            .iter()
+            .map(|i| (i.program, i.material, i.mesh, i.depth))
+            .collect()
+    }
+
+    #[test]
+    fn opaque_items_group_by_state_then_go_front_to_back() {
+        let object = object();
+        let mut queue = RenderQueue::new();
+        queue.opaque = vec![
+            item(&object, 2, 1, 1, 5.0),
+            item(&object, 1, 2, 1, 1.0),
+            item(&object, 1, 1, 2, 3.0),
+            item(&object, 1, 1, 1, 9.0),
+            item(&object, 1, 1, 1, 2.0),
+            item(&object, 1, 2, 1, 0.5),
+        ];
+        queue.sort();
+        assert_eq!(
+            order(&queue.opaque),
+            vec![
+                (1, 1, 1, 2.0),
+                (1, 1, 1, 9.0),
+                (1, 1, 2, 3.0),
+                (1, 2, 1, 0.5),
+                (1, 2, 1, 1.0),
+                (2, 1, 1, 5.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn transparent_items_go_back_to_front_whatever_their_state() {
+        let object = object();
+        let mut queue = RenderQueue::new();
+        queue.transparent = vec![
+            item(&object, 1, 1, 1, 2.0),
+            item(&object, 2, 3, 1, 10.0),
+            item(&object, 1, 1, 1, -1.0),
+            item(&object, 1, 2, 4, 6.0),
+        ];
+        queue.sort();
+        let depths: Vec<f32> = queue.transparent.iter().map(|i| i.depth).collect();
+        assert_eq!(depths, vec![10.0, 6.0, 2.0, -1.0]);
+    }
+
+    #[test]
+    fn equal_materials_on_separate_objects_batch() {
+        let mut first = object();
+        let second = object();
+        let mut third = object();
+        let red = Physical::builder().albedo(Vector3::new(1.0, 0.0, 0.0));
+        third.material = Box::new(red.build().unwrap());
+        let key = |object: &Object| material::batch_key(object.material.as_ref());
+        assert_eq!(key(&first), key(&second));
+        assert_ne!(key(&first), key(&third));
+
+        let mut queue = RenderQueue::new();
+        queue.opaque = vec![
+            item(&first, 1, key(&first), 1, 1.0),
+            item(&third, 1, key(&third), 2, 2.0),
+            item(&second, 1, key(&second), 3, 3.0),
+        ];
+        queue.sort();
+        let mut switches = queue.opaque.iter().map(|i| i.material).collect::<Vec<_>>();
+        switches.dedup();
+        assert_eq!(switches.len(), 2);
+
+        // a toggled map is a different material until it's toggled back
+        first.material.toggle_map("normal_texture");
+        assert_ne!(key(&first), key(&second));
+        first.material.toggle_map("normal_texture");
+        assert_eq!(key(&first), key(&second));
+    }
+}
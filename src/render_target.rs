@@ -0,0 +1,90 @@
+// an offscreen framebuffer a camera can render into. the colour texture is an ordinary 2d texture,
+// so it can go straight into a material's texture slot for monitors, mirrors and the like
+
+pub struct RenderTarget {
+    pub width: i32,
+    pub height: i32,
+    pub framebuffer: u32,
+    pub texture: u32,
+    depth: u32,
+}
+
+impl RenderTarget {
+    pub fn new(width: i32, height: i32) -> RenderTarget {
+        let mut target = RenderTarget {
+            width,
+            height,
+            framebuffer: 0,
+            texture: 0,
+            depth: 0,
+        };
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut target.framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, target.framebuffer);
+
+            gl::GenTextures(1, &mut target.texture);
+            gl::BindTexture(gl::TEXTURE_2D, target.texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as i32,
+                width,
+                height,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                target.texture,
+                0,
+            );
+
+            // depth only has to work while rendering, it's never sampled
+            gl::GenRenderbuffers(1, &mut target.depth);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, target.depth);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH_COMPONENT32F, width, height);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::RENDERBUFFER,
+                target.depth,
+            );
+
+            assert_eq!(
+                gl::CheckFramebufferStatus(gl::FRAMEBUFFER),
+                gl::FRAMEBUFFER_COMPLETE,
+                "render target framebuffer is incomplete"
+            );
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        target
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+        }
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.framebuffer);
+            gl::DeleteTextures(1, &self.texture);
+            gl::DeleteRenderbuffers(1, &self.depth);
+        }
+    }
+}
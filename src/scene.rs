@@ -4,25 +4,38 @@ use std::{collections::HashMap, time::Instant};
 
 use sdl2::event::Event as SDL2Event;
 use sdl2::{event::Event, keyboard::Keycode, mouse::MouseButton, Sdl};
-use nalgebra::{Point, Point3, Vector3};
+use nalgebra::{Matrix4, Point, Point3, Vector3};
 
 use crate::directional_light::DirectionalLight;
-use crate::{camera::Camera, point_light::PointLight, render::Object};
+use crate::{
+    bvh::{MeshBvh, SceneBvh},
+    camera::Camera,
+    camera_controller::{CameraContext, CameraController},
+    collision::{self, BoundingBox},
+    instancing::InstancedObject,
+    material::DebugView,
+    material_library::{MaterialFileError, MaterialLibrary},
+    physics::PhysicsWorld,
+    picking::PickingPass,
+    point_light::PointLight,
+    raycast::{Ray, RayHit},
+    render::{Object, RenderStats},
+};
 
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct Settings {
     pub screen_width: i32,
     pub screen_height: i32,
-    pub fovy: f32,
+    pub debug_view: DebugView,
 }
 
 impl Settings {
-    pub fn new(screen_width: i32, screen_height: i32, fovy: f32) -> Settings {
+    pub fn new(screen_width: i32, screen_height: i32) -> Settings {
         return Settings {
             screen_width,
             screen_height,
-            fovy,
+            debug_view: DebugView::Lit,
         };
     }
 
@@ -30,7 +43,7 @@ impl Settings {
         return Settings {
             screen_width: 1200,
             screen_height: 800,
-            fovy: 45.0_f32.to_radians(),
+            debug_view: DebugView::Lit,
         };
     }
 }
@@ -39,11 +52,28 @@ pub struct Scene {
     pub scene_time: Instant,
     pub active_camera: String,
     pub object_map: HashMap<String, Object>,
+    pub instanced_objects: HashMap<String, InstancedObject>,
     pub point_lights: Vec<PointLight>,
     pub directional_light: Option<DirectionalLight>,
     pub cameras: HashMap<String, Camera>,
     pub settings: Settings,
     pub player_target: Vector3<f32>,
+    pub render_stats: RenderStats,
+    pub material_library: Option<MaterialLibrary>,
+    // object name to the library material it was given, so edits to the file reach the object
+    pub material_bindings: HashMap<String, String>,
+    // moves the active camera from input, with the object a follow camera should chase
+    pub camera_controller: Option<Box<dyn CameraController>>,
+    pub camera_target: Option<String>,
+    // object bounds for raycast, see rebuild_bvh
+    pub bvh: Option<SceneBvh>,
+    // gpu selection, only there once a scene asks for it since it needs gl
+    pub picking: Option<PickingPass>,
+    // selected object names, and where a left drag to select a rectangle started
+    pub selection: Vec<String>,
+    pub drag_start: Option<(i32, i32)>,
+    // bodies that move objects around, see update_physics
+    pub physics: PhysicsWorld,
 
     pub on_start: fn(&mut Scene),
     pub on_update: fn(&mut Scene),
@@ -58,11 +88,22 @@ impl Scene {
             scene_time: Instant::now(),
             active_camera: "".to_string(),
             object_map: HashMap::new(),
+            instanced_objects: HashMap::new(),
             point_lights: Vec::new(),
             cameras: HashMap::new(),
             settings: Settings::default(),
             player_target: Vector3::zeros(),
             directional_light: None,
+            render_stats: RenderStats::default(),
+            material_library: None,
+            material_bindings: HashMap::new(),
+            camera_controller: None,
+            camera_target: None,
+            bvh: None,
+            picking: None,
+            selection: Vec::new(),
+            drag_start: None,
+            physics: PhysicsWorld::new(),
 
             on_start: no_op,
             on_update: no_op,
@@ -85,4 +126,299 @@ impl Scene {
     pub fn start(&mut self) {
         (self.on_start)(self)
     }
+
+    // gives an object a material from the library and keeps it up to date with the file
+    pub fn bind_material(&mut self, object: &str, material: &str) -> Result<(), MaterialFileError> {
+        let library = self
+            .material_library
+            .as_mut()
+            .ok_or_else(|| MaterialFileError::UnknownMaterial(material.to_string()))?;
+        let built = library.build(material)?;
+        self.object_map
+            .get_mut(object)
+            .expect("no object to bind the material to")
+            .material = Box::new(built);
+        self.material_bindings
+            .insert(object.to_string(), material.to_string());
+        Ok(())
+    }
+
+    // the ray under a window position from the active camera
+    pub fn screen_point_to_ray(&self, x: i32, y: i32) -> Option<Ray> {
+        let window = (self.settings.screen_width, self.settings.screen_height);
+        self.cameras
+            .get(&self.active_camera)?
+            .screen_point_to_ray(x, y, window)
+    }
+
+    // renders the picking pass from the active camera, sized to the window. None without picking
+    fn render_picking(&mut self) -> Option<&PickingPass> {
+        let picking = self.picking.as_mut()?;
+        let camera = self.cameras.get(&self.active_camera)?;
+        picking.resize(self.settings.screen_width, self.settings.screen_height);
+        picking.render(self.object_map.iter(), camera);
+        Some(picking)
+    }
+
+    // the object drawn nearest to a window pixel, within radius pixels of it
+    pub fn pick(&mut self, x: i32, y: i32, radius: i32) -> Option<String> {
+        self.render_picking()?.pick(x, y, radius)
+    }
+
+    // every object visible inside the rectangle between two window corners
+    pub fn pick_rect(&mut self, from: (i32, i32), to: (i32, i32)) -> Vec<String> {
+        match self.render_picking() {
+            Some(picking) => picking.pick_rect(from, to),
+            None => Vec::new(),
+        }
+    }
+
+    // steps the simulation by the frame's time and moves the objects with bodies to match
+    pub fn update_physics(&mut self, delta_time: f32) {
+        if self.physics.step(delta_time) > 0 {
+            self.physics.sync(&mut self.object_map);
+        }
+    }
+
+    // the world bounds of everything raycast can hit, rebuilt once a frame after things have moved
+    pub fn rebuild_bvh(&mut self) {
+        let bounds = self
+            .object_map
+            .iter()
+            .map(|(name, object)| (name.clone(), object.world_bounding_box()))
+            .chain(
+                self.instanced_objects
+                    .iter()
+                    .map(|(name, instanced)| (name.clone(), instanced.world_bounding_box())),
+            )
+            .collect();
+        self.bvh = Some(SceneBvh::new(bounds));
+    }
+
+    // every object and instanced group the ray hits that the filter accepts by name, nearest
+    // first. objects with a mesh bvh are hit on their triangles, everything else on its bounds.
+    // candidates come from the scene bvh when there is one, so it has to be kept up to date
+    pub fn raycast(&self, ray: &Ray, filter: impl Fn(&str) -> bool) -> Vec<RayHit> {
+        let names: Vec<&str> = match &self.bvh {
+            Some(bvh) => bvh.candidates(ray),
+            None => self
+                .object_map
+                .keys()
+                .chain(self.instanced_objects.keys())
+                .map(|name| name.as_str())
+                .collect(),
+        };
+
+        let mut hits: Vec<RayHit> = names
+            .into_iter()
+            .filter(|name| filter(name))
+            .filter_map(|name| {
+                if let Some(object) = self.object_map.get(name) {
+                    match &object.bvh {
+                        Some(bvh) => hit_mesh(ray, name, bvh, object),
+                        None => hit_bounds(ray, name, &object.world_bounding_box()),
+                    }
+                } else {
+                    let instanced = self.instanced_objects.get(name)?;
+                    hit_bounds(ray, name, &instanced.world_bounding_box())
+                }
+            })
+            .collect();
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+
+    pub fn update_camera(&mut self, delta_time: f32) {
+        let Some(controller) = self.camera_controller.as_mut() else {
+            return;
+        };
+
+        // everything but the followed object can get in the way of the camera
+        let mut context = CameraContext::new();
+        for (name, object) in self.object_map.iter() {
+            context
+                .objects
+                .insert(name.clone(), object.model.position.into());
+            if Some(name) == self.camera_target.as_ref() {
+                context.target = Some(object.model.position.into());
+            } else {
+                context.obstacles.push(object.world_bounding_box());
+            }
+        }
+
+        if let Some(camera) = self.cameras.get_mut(&self.active_camera) {
+            controller.update(camera, &context, delta_time);
+        }
+    }
+
+    // rebuilds the materials of bound objects whose files changed on disk. returns the materials
+    // that failed to parse or build, their objects keep the previous material
+    pub fn reload_materials(&mut self) -> Vec<(String, MaterialFileError)> {
+        let Some(library) = self.material_library.as_mut() else {
+            return Vec::new();
+        };
+
+        let changes = library.poll_changes();
+        let mut failed = changes.failed;
+        for name in changes.rebuild {
+            let objects = self
+                .material_bindings
+                .iter()
+                .filter(|(_, bound)| **bound == name)
+                .map(|(object, _)| object);
+
+            // each object gets its own copy, the textures are shared through the library
+            for object in objects {
+                match library.build(&name) {
+                    Ok(material) => {
+                        if let Some(object) = self.object_map.get_mut(object) {
+                            object.material = Box::new(material);
+                        }
+                    }
+                    Err(err) => {
+                        failed.push((name.clone(), err));
+                        break;
+                    }
+                }
+            }
+        }
+        failed
+    }
+}
+
+fn hit_bounds(ray: &Ray, name: &str, bbox: &BoundingBox) -> Option<RayHit> {
+    let (distance, normal) = ray.intersect_bounding_box(bbox)?;
+    Some(RayHit {
+        object: name.to_string(),
+        distance,
+        point: ray.origin + ray.direction * distance,
+        normal,
+        triangle: None,
+        barycentric: None,
+        uv: None,
+    })
+}
+
+// the ray is taken into model space without renormalizing, so distances along it stay world ones
+fn hit_mesh(ray: &Ray, name: &str, bvh: &MeshBvh, object: &Object) -> Option<RayHit> {
+    let inverse = object.model.get_model_matrix().try_inverse()?;
+    let local = Ray {
+        origin: inverse.transform_point(&ray.origin),
+        direction: inverse.transform_vector(&ray.direction),
+    };
+    let hit = bvh.intersect(&local, &object.vertices, &object.uvs)?;
+    let normal = inverse
+        .transpose()
+        .transform_vector(&hit.normal)
+        .try_normalize(f32::EPSILON)
+        .unwrap_or(-ray.direction);
+
+    Some(RayHit {
+        object: name.to_string(),
+        distance: hit.distance,
+        point: ray.origin + ray.direction * hit.distance,
+        normal,
+        triangle: Some(hit.triangle),
+        barycentric: Some(hit.barycentric),
+        uv: Some(hit.uv),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        buffers::RenderBuffers, material::Physical, primitives, render::Model,
+    };
+
+    // objects never touch gl until init, so a box can be set by hand
+    fn add_box(scene: &mut Scene, name: &str, bbox: BoundingBox) {
+        let mut object = Object::new(
+            Model::new(),
+            RenderBuffers::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Box::new(Physical::default()),
+        );
+        object.bounding_box = bbox;
+        scene.object_map.insert(name.to_string(), object);
+    }
+
+    #[test]
+    fn raycast_returns_nearest_hits_first() {
+        let mut scene = Scene::new();
+        let cube = |x: f32, z: f32| BoundingBox::new(x - 1.0, x + 1.0, -1.0, 1.0, z - 1.0, z + 1.0);
+        add_box(&mut scene, "far", cube(0.0, -20.0));
+        add_box(&mut scene, "near", cube(0.0, -10.0));
+        add_box(&mut scene, "aside", cube(6.0, -10.0));
+        let ray = Ray {
+            origin: Point3::origin(),
+            direction: Vector3::new(0.0, 0.0, -1.0),
+        };
+
+        let hits = scene.raycast(&ray, |_| true);
+        let names: Vec<&str> = hits.iter().map(|hit| hit.object.as_str()).collect();
+        assert_eq!(names, vec!["near", "far"]);
+        assert_eq!(hits[0].distance, 9.0);
+        assert_eq!(hits[0].point, Point3::new(0.0, 0.0, -9.0));
+        assert_eq!(hits[0].normal, Vector3::new(0.0, 0.0, 1.0));
+
+        let hits = scene.raycast(&ray, |name| name != "near");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].object, "far");
+    }
+
+    #[test]
+    fn raycast_uses_world_space_bounds() {
+        let mut scene = Scene::new();
+        let unit = BoundingBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+        add_box(&mut scene, "moved", unit);
+        scene
+            .object_map
+            .get_mut("moved")
+            .unwrap()
+            .model
+            .translate(Vector3::new(0.0, 0.0, -30.0));
+        let ray = Ray {
+            origin: Point3::origin(),
+            direction: Vector3::new(0.0, 0.0, -1.0),
+        };
+
+        let hits = scene.raycast(&ray, |_| true);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].distance, 29.0);
+    }
+
+    #[test]
+    fn raycast_hits_triangles_when_there_is_a_mesh_bvh() {
+        let mut scene = Scene::new();
+        let sphere = primitives::uv_sphere(1.0, 32, 16);
+        add_box(&mut scene, "ball", collision::get_bounding_box(&sphere.vertices));
+        let ball = scene.object_map.get_mut("ball").unwrap();
+        ball.bvh = Some(MeshBvh::new(&sphere.vertices));
+        ball.vertices = sphere.vertices;
+        ball.uvs = sphere.tex_coords;
+        // the model matrix scales the translation as well, this puts the ball at z = -10
+        ball.model.translate(Vector3::new(0.0, 0.0, -5.0));
+        ball.model.scale(Vector3::new(2.0, 2.0, 2.0));
+        scene.rebuild_bvh();
+
+        let ray = Ray {
+            origin: Point3::origin(),
+            direction: Vector3::new(0.0, 0.0, -1.0),
+        };
+        let hits = scene.raycast(&ray, |_| true);
+        assert_eq!(hits.len(), 1);
+        assert!((hits[0].distance - 8.0).abs() < 0.02);
+        assert!(hits[0].normal.z > 0.98);
+        assert!(hits[0].triangle.is_some() && hits[0].uv.is_some());
+
+        // through the corner of its bounds, which only a box test would count
+        let corner = Ray {
+            origin: Point3::new(1.9, 1.9, 0.0),
+            direction: Vector3::new(0.0, 0.0, -1.0),
+        };
+        assert!(scene.raycast(&corner, |_| true).is_empty());
+    }
 }
@@ -0,0 +1,108 @@
+// a grid of spheres sweeping metallic from left to right and roughness from bottom to top, every
+// one built through PhysicalBuilder. run with `material-preview` as the first argument and compare
+// against an earlier screenshot after touching pbr.frag.glsl
+
+use nalgebra::{Point3, Vector3};
+use sdl2::event::Event as SDL2Event;
+use sdl2::keyboard::Keycode;
+
+use crate::directional_light::DirectionalLight;
+use crate::{
+    buffers,
+    camera::Camera,
+    material,
+    point_light::PointLight,
+    primitives,
+    render::{Model, Object},
+    scene::{Scene, Settings},
+};
+
+const GRID_SIZE: usize = 7;
+const SPACING: f32 = 25.0;
+const RADIUS: f32 = 10.0;
+
+pub fn material_preview(settings: Settings) -> Scene {
+    let mut sc = Scene::new();
+    sc.settings = settings;
+
+    let mut dir_light = DirectionalLight::new();
+    dir_light.direction = Vector3::new(-0.3, -0.5, -1.0);
+    sc.directional_light = Some(dir_light);
+
+    fn on_start(sc: &mut Scene) {
+        let extent = (GRID_SIZE - 1) as f32 * SPACING;
+
+        sc.active_camera = "main".to_string();
+        sc.cameras.insert(
+            "main".to_string(),
+            Camera::new(
+                Point3::new(0.0, 0.0, extent * 1.6),
+                Point3::new(0.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            ),
+        );
+
+        let sphere_data = primitives::uv_sphere(RADIUS, 48, 24);
+        let step = 1.0 / (GRID_SIZE - 1) as f32;
+        for row in 0..GRID_SIZE {
+            for column in 0..GRID_SIZE {
+                let metallic = column as f32 * step;
+                let roughness = row as f32 * step;
+                let material = material::Physical::builder()
+                    .albedo(Vector3::new(0.9, 0.1, 0.1))
+                    .metallic(metallic)
+                    .roughness(roughness)
+                    .ao(1.0)
+                    .build()
+                    .expect("preview material out of range");
+
+                let mut sphere = Object::new(
+                    Model::new(),
+                    buffers::RenderBuffers::new(),
+                    sphere_data.vertices.clone(),
+                    sphere_data.normals.clone(),
+                    sphere_data.tex_coords.clone(),
+                    Box::new(material),
+                );
+                sphere.model.translate(Vector3::new(
+                    column as f32 * SPACING - extent * 0.5,
+                    row as f32 * SPACING - extent * 0.5,
+                    0.0,
+                ));
+                sc.object_map
+                    .insert(format!("sphere_m{}_r{}", column, row), sphere);
+            }
+        }
+
+        for (_, object) in sc.object_map.iter_mut() {
+            object.init();
+        }
+
+        // a key and a rim light so both the diffuse falloff and the highlights show up
+        let mut key = PointLight::new();
+        key.position = Vector3::new(-extent, extent, extent);
+        key.color = Vector3::new(1.0, 1.0, 1.0);
+        key.strength = 400.0;
+        sc.point_lights.push(key);
+
+        let mut rim = PointLight::new();
+        rim.position = Vector3::new(extent, -extent * 0.5, extent * 0.5);
+        rim.strength = 150.0;
+        sc.point_lights.push(rim);
+    }
+    sc.set_on_start(on_start);
+
+    fn on_event(sc: &mut Scene, e: SDL2Event) {
+        if let SDL2Event::KeyDown {
+            keycode: Some(Keycode::V),
+            ..
+        } = e
+        {
+            sc.settings.debug_view = sc.settings.debug_view.next();
+            println!("debug view: {:?}", sc.settings.debug_view);
+        }
+    }
+    sc.on_event = on_event;
+
+    sc
+}
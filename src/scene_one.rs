@@ -5,20 +5,30 @@ use sdl2::event::Event as SDL2Event;
 use sdl2::{event::Event, keyboard::Keycode, mouse::MouseButton, Sdl};
 
 use crate::directional_light::DirectionalLight;
-use crate::shader::Shader;
 use crate::texture;
 use crate::{
     buffers,
-    camera::Camera,
-    material, obj,
+    camera::{Camera, Projection, Viewport},
+    camera_controller::{CameraController, FlyController, FollowController, OrbitController},
+    camera_path::{CameraPath, LookAt},
+    collision,
+    instancing::{Instance, InstancedObject},
+    lod::LodMetric,
+    material, mesh,
+    material_library::MaterialLibrary,
+    obj::{self, ObjData},
+    physics::{BodyKind, Collider, RigidBody},
+    picking::PickingPass,
     point_light::PointLight,
-    raycast::{ray_intersect_bb_projection, Ray},
+    primitives,
     render::{Model, Object},
+    render_target::RenderTarget,
     scene::{Scene, Settings},
-    texture::loadTexture,
+    spline::{Easing, Spline},
 };
 
 const ROTATION_SPEED: f32 = std::f32::consts::PI * 3.0;
+const ORB_COUNT: usize = 120;
 
 pub fn scene_one(settings: Settings) -> Scene {
     let mut sc = Scene::new();
@@ -29,34 +39,79 @@ pub fn scene_one(settings: Settings) -> Scene {
     sc.directional_light.as_mut().unwrap().direction.z = -1.0;
 
     fn on_start(sc: &mut Scene) {
-        // load in pbr shader to be used by objects in our scene
-        let mut shader_program: u32 = 0;
-        unsafe {
-            shader_program = Shader::new("pbr".to_string()).program;
-        }
-
         sc.active_camera = "main".to_string();
-        sc.cameras.insert(
-            "main".to_string(),
-            Camera::new(
-                Point3::new(0.0, 300.0, 300.0),
-                Point3::new(0.0, 0.0, 0.0),
-                Vector3::new(0.0, 1.0, 0.0),
-            ),
+        let mut main_camera = Camera::new(
+            Point3::new(0.0, 300.0, 300.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        // no far plane to clip the ground at the horizon, and reversed depth keeps the distant
+        // ground from z fighting
+        main_camera.projection = Projection::InfiniteReversed {
+            fovy: 60.0_f32.to_radians(),
+            near: 0.1,
+        };
+        sc.cameras.insert("main".to_string(), main_camera);
+        sc.camera_controller = Some(Box::new(OrbitController::from_camera(
+            sc.get_active_cam(),
+        )));
+        sc.camera_target = Some("player".to_string());
+        sc.picking = Some(PickingPass::new(
+            sc.settings.screen_width,
+            sc.settings.screen_height,
+        ));
+
+        // top down minimap in the top right corner, drawn over the main view
+        let mut minimap = Camera::new(
+            Point3::new(0.0, 1000.0, 0.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -1.0),
         );
+        minimap.projection = Projection::Orthographic {
+            height: 700.0,
+            near: 1.0,
+            far: 2000.0,
+        };
+        minimap.viewport = Viewport::new(0.78, 0.68, 0.2, 0.3);
+        minimap.order = 1;
+        sc.cameras.insert("minimap".to_string(), minimap);
+
+        // a security camera watching the sphere row, drawn first into a texture that the monitor
+        // behind it shows. it faces away from the monitor so it never samples what it renders to
+        let mut security = Camera::new(
+            Point3::new(0.0, 60.0, 150.0),
+            Point3::new(0.0, 25.0, -15.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        let security_target = RenderTarget::new(512, 256);
+        let mut screen = material::Unlit::new(Vector3::new(1.0, 1.0, 1.0));
+        screen.diffuse_texture.tex = Some(security_target.texture);
+        screen.diffuse_texture.enabled = true;
+        security.render_target = Some(security_target);
+        security.order = -1;
+        sc.cameras.insert("security".to_string(), security);
+
+        let mut monitor = Object::from_obj(&primitives::plane(80.0, 40.0, 0));
+        monitor.material = Box::new(screen);
+        monitor.model.rotate(Vector3::x_axis(), std::f32::consts::FRAC_PI_2);
+        monitor.model.translate(Vector3::new(0.0, 40.0, 200.0));
+        sc.object_map.insert("monitor".to_string(), monitor);
 
         // OBJECTS
         let sphere_data = obj::parse_obj("resources/sphere-smooth.obj")
             .expect("unable to load obj file for sphere");
 
-        let mut red_material = material::Physical::new(Vector3::new(0.5, 0.0, 0.0), 3.0, 0.1, 1.5);
-
-        // unsafe {
-        //     let tex = loadTexture("resources/checkers.png");
-        //     let n_tex = loadTexture("resources/checkers-normal.png");
-        //     red_material.diffuse_texture = Some(tex);
-        //     red_material.normal_texture = Some(n_tex);
-        // }
+        // polished metal spheres, green is brushed
+        let metal = |albedo: Vector3<f32>, roughness: f32| {
+            material::Physical::builder()
+                .albedo(albedo)
+                .metallic(1.0)
+                .roughness(roughness)
+                .ao(1.0)
+                .build()
+                .expect("invalid sphere material")
+        };
+        let red_material = metal(Vector3::new(0.5, 0.0, 0.0), 0.1);
 
         let mut red: Object = Object::new(
             Model::new(),
@@ -68,7 +123,6 @@ pub fn scene_one(settings: Settings) -> Scene {
             Box::new(red_material),
         );
         red.model.translate(Vector3::new(0.0, 25.0, -15.0));
-        red.shader_program = shader_program;
         sc.object_map.insert("red".to_string(), red);
 
         let mut green = Object::new(
@@ -77,15 +131,9 @@ pub fn scene_one(settings: Settings) -> Scene {
             sphere_data.vertices.clone(),
             sphere_data.normals.clone(),
             sphere_data.tex_coords.clone(),
-            Box::new(material::Physical::new(
-                Vector3::new(0.0, 0.5, 0.0),
-                3.0,
-                17.1,
-                1.5,
-            )),
+            Box::new(metal(Vector3::new(0.0, 0.5, 0.0), 0.9)),
         );
         green.model.translate(Vector3::new(65.0, 25.0, -15.0));
-        green.shader_program = shader_program;
         sc.object_map.insert("green".to_string(), green);
 
         let mut blue = Object::new(
@@ -94,48 +142,33 @@ pub fn scene_one(settings: Settings) -> Scene {
             sphere_data.vertices.clone(),
             sphere_data.normals.clone(),
             sphere_data.tex_coords.clone(),
-            Box::new(material::Physical::new(
-                Vector3::new(0.0, 0.0, 0.5),
-                7.0,
-                0.1,
-                1.5,
-            )),
+            Box::new(metal(Vector3::new(0.0, 0.0, 0.5), 0.1)),
         );
         blue.model.translate(Vector3::new(-65.0, 25.0, -15.0));
-        blue.shader_program = shader_program;
         sc.object_map.insert("blue".to_string(), blue);
 
-        let plane_data = obj::parse_obj("resources/plane.obj").expect("unable to load plane data");
-
-        let mut plane_material =
-            material::Physical::new(Vector3::new(0.8, 0.6, 0.0), 0.0, 150.1, 0.1);
-
-        unsafe {
-            let tex = loadTexture("resources/grey-rocks.png");
-            let n_tex = loadTexture("resources/grey-rocks-normal.png");
-            let arm_tex = loadTexture("resources/grey-rocks-arm.png");
-            plane_material.diffuse_texture.tex = Some(tex);
-            plane_material.diffuse_texture.enabled = true;
-            plane_material.diffuse_texture.scale = 25.0;
-
-            plane_material.normal_texture.tex = Some(n_tex);
-            plane_material.normal_texture.enabled = true;
-            plane_material.normal_texture.scale = 25.0;
-
-            plane_material.arm_texture.tex = Some(arm_tex);
-            plane_material.arm_texture.enabled = true;
-            plane_material.arm_texture.scale = 25.0;
+        // coarser spheres for when the camera pulls back, dithered over a third of a second. the
+        // green one switches on how much of the screen it covers, so zooming counts too
+        let sphere_lods = [
+            mesh::expand(&mesh::simplify(&sphere_data, 320)),
+            mesh::expand(&mesh::simplify(&sphere_data, 96)),
+        ];
+        for (key, metric, thresholds) in [
+            ("red", LodMetric::Distance, [450.0, 800.0]),
+            ("green", LodMetric::ScreenSize, [0.1, 0.06]),
+            ("blue", LodMetric::Distance, [450.0, 800.0]),
+        ] {
+            let sphere = sc.object_map.get_mut(key).unwrap();
+            for (data, threshold) in sphere_lods.iter().zip(thresholds) {
+                sphere.add_lod(data, metric, threshold);
+            }
+            sphere.lod.as_mut().unwrap().fade_duration = 0.3;
         }
 
-        let mut main_plane = Object::new(
-            Model::new(),
-            buffers::RenderBuffers::new(),
-            plane_data.vertices.clone(),
-            plane_data.normals.clone(),
-            plane_data.tex_coords.clone(),
-            Box::new(plane_material),
-        );
-        main_plane.shader_program = shader_program;
+        let plane_data = obj::parse_obj("resources/plane.obj").expect("unable to load plane data");
+
+        // whatever the plane's .mtl says until the library's ground material replaces it below
+        let main_plane = Object::from_obj(&plane_data);
         // main_plane.model.scale(Vector3::new(100.0, 100.0, 100.0));
         sc.object_map.insert("main_plain".to_string(), main_plane);
 
@@ -147,21 +180,137 @@ pub fn scene_one(settings: Settings) -> Scene {
             cube_data.vertices.clone(),
             cube_data.normals.clone(),
             cube_data.tex_coords.clone(),
-            Box::new(material::Physical::new(
-                Vector3::new(0.0, 0.0, 0.5),
-                7.0,
-                0.1,
-                1.5,
-            )),
+            Box::new(metal(Vector3::new(0.0, 0.0, 0.5), 0.1)),
         );
-        player_cube.model.translate(Vector3::new(0.0, 0.0, 20.0));
-        player_cube.shader_program = shader_program;
+        // dropped in from a little above the ground
+        player_cube.model.translate(Vector3::new(0.0, 3.0, 20.0));
+        sc.player_target = Vector3::new(0.0, 0.0, 20.0);
         sc.object_map.insert("player".to_string(), player_cube);
 
+        // a pane of glass in front of the spheres, drawn in the transparent pass
+        let glass_data = mesh::transform(
+            &primitives::cube(1.0),
+            &Matrix4::new_nonuniform_scaling(&Vector3::new(160.0, 60.0, 2.0)),
+        );
+        let mut glass = Object::new(
+            Model::new(),
+            buffers::RenderBuffers::new(),
+            glass_data.vertices.clone(),
+            glass_data.normals.clone(),
+            glass_data.tex_coords.clone(),
+            Box::new(material::Physical::default()),
+        );
+        glass.model.translate(Vector3::new(0.0, 30.0, 10.0));
+        sc.object_map.insert("glass".to_string(), glass);
+
+        // the player walks on the ground and can't go through the glass. it's kept upright so
+        // friction doesn't tip it over while it's steered
+        sc.physics.gravity = Vector3::new(0.0, -300.0, 0.0);
+        sc.physics.add(RigidBody::new(
+            BodyKind::Static,
+            Collider::Plane(collision::Plane::new(Vector3::y(), 0.0)),
+            0.0,
+        ));
+        for (name, kind) in [("player", BodyKind::Dynamic), ("glass", BodyKind::Static)] {
+            let mut body = RigidBody::from_object(name, &sc.object_map[name], kind, 1.0)
+                .expect("physics objects need some volume");
+            body.friction = 0.2;
+            if kind == BodyKind::Dynamic {
+                body.lock_rotation();
+            }
+            sc.physics.add(body);
+        }
+
+        // a glowing pickup, its emission is hdr and tonemaps to near white
+        let pickup_data = primitives::icosphere(8.0, 2);
+        let mut pickup = Object::new(
+            Model::new(),
+            buffers::RenderBuffers::new(),
+            pickup_data.vertices.clone(),
+            pickup_data.normals.clone(),
+            pickup_data.tex_coords.clone(),
+            Box::new(material::Physical::default()),
+        );
+        pickup.model.translate(Vector3::new(120.0, 12.0, 80.0));
+        sc.object_map.insert("pickup".to_string(), pickup);
+
+        // materials kept in materials/*.mat, edits to those files show up while running
+        let (library, failed) =
+            MaterialLibrary::load("materials").expect("unable to load the material library");
+        for (name, err) in failed {
+            println!("skipped material {}: {}", name, err);
+        }
+        sc.material_library = Some(library);
+        for (object, material) in [("main_plain", "ground"), ("glass", "glass"), ("pickup", "pickup")] {
+            sc.bind_material(object, material)
+                .unwrap_or_else(|err| panic!("unable to load material {}: {}", material, err));
+        }
+
+        // a row of stylised objects, each material picks its own shader
+        let stylised: [(&str, ObjData, Box<dyn material::Material>, f32); 3] = [
+            (
+                "toon_torus",
+                primitives::torus(20.0, 7.0, 32, 16),
+                Box::new(material::Toon::new(Vector3::new(0.9, 0.4, 0.2), 3)),
+                -60.0,
+            ),
+            (
+                "blinn_capsule",
+                primitives::capsule(10.0, 30.0, 24, 8),
+                Box::new(material::BlinnPhong::new(
+                    Vector3::new(0.2, 0.3, 0.8),
+                    Vector3::new(1.0, 1.0, 1.0),
+                    64.0,
+                )),
+                0.0,
+            ),
+            (
+                "unlit_marker",
+                primitives::cone(10.0, 30.0, 24),
+                Box::new(material::Unlit::new(Vector3::new(1.0, 0.9, 0.2))),
+                60.0,
+            ),
+        ];
+        for (name, data, material, x) in stylised {
+            let mut object = Object::new(
+                Model::new(),
+                buffers::RenderBuffers::new(),
+                data.vertices.clone(),
+                data.normals.clone(),
+                data.tex_coords.clone(),
+                material,
+            );
+            object.model.translate(Vector3::new(x, 25.0, -120.0));
+            sc.object_map.insert(name.to_string(), object);
+        }
+
+        // a ring of orbs drawn with a single instanced call
+        let mut orbs = InstancedObject::new(
+            &primitives::icosphere(6.0, 2),
+            Box::new(material::Physical::default()),
+        );
+        for i in 0..ORB_COUNT {
+            let t = i as f32 / ORB_COUNT as f32;
+            let angle = t * std::f32::consts::TAU;
+            let mut model = Model::new();
+            model.translate(Vector3::new(angle.cos() * 250.0, 6.0, angle.sin() * 250.0));
+            orbs.add_instance(Instance::new(
+                model,
+                Vector3::new(t, 0.3, 1.0 - t),
+                0.0,
+                0.2 + 0.6 * t,
+                1.0,
+            ));
+        }
+        sc.instanced_objects.insert("orbs".to_string(), orbs);
+
         // initialize the objects
         for (key, object) in sc.object_map.iter_mut() {
             object.init();
         }
+        for (_, instanced) in sc.instanced_objects.iter_mut() {
+            instanced.init();
+        }
 
         // LIGHTS
         let mut light = PointLight::new();
@@ -201,28 +350,24 @@ pub fn scene_one(settings: Settings) -> Scene {
 
         // }
 
+        // hop the orbs one at a time, only the moving instance gets re-uploaded
+        let elapsed = sc.scene_time.elapsed().as_secs_f32();
+        let orbs = sc.instanced_objects.get_mut("orbs").unwrap();
+        let hop = elapsed * 4.0;
+        let orb = orbs.instance_mut(hop as usize % ORB_COUNT);
+        orb.model.position.y = 6.0 + (hop.fract() * std::f32::consts::PI).sin() * 20.0;
+
         let light2 = sc.point_lights.get_mut(1).unwrap();
         light2.position.x += f32::sin(sc.scene_time.elapsed().as_secs_f32() - 5.0) * 0.5;
 
-        // have the camera look at the player cube
-        let mut player = sc.object_map.get_mut(&"player".to_string()).unwrap();
-        let mut main_camera = sc.cameras.get_mut(&"main".to_string()).unwrap();
-
-        // main_camera.look_at_target(Point3::new(
-        //     player.model.position.x,
-        //     player.model.position.y,
-        //     player.model.position.z,
-        // ));
-
-        // main_camera.position = Point3::new(
-        //     player.model.position.x + 200.0, // Slight offset to ensure it's not at the same position
-        //     player.model.position.x + 200.0,
-        //     player.model.position.z + 200.0, // Slight offset along the z-axis
-        // );
-
-        // move the player toward the target
-        player.model.position = player.model.position.lerp(&sc.player_target, 0.01);
-        // println!("{} {}", sc.player_target, player.model.position);
+        // steer the player toward the target, slowing down on the way in. gravity and
+        // collisions are left to the physics step
+        let player = sc.physics.find("player").unwrap();
+        let body = &mut sc.physics.bodies[player];
+        let to_target = sc.player_target - body.position;
+        let steer = Vector3::new(to_target.x, 0.0, to_target.z) * 2.0;
+        let steer = steer.cap_magnitude(150.0);
+        body.velocity = Vector3::new(steer.x, body.velocity.y, steer.z);
     }
     sc.on_update = on_update;
 
@@ -231,7 +376,14 @@ pub fn scene_one(settings: Settings) -> Scene {
             SDL2Event::MouseMotion {
                 x, y, xrel, yrel, ..
             } => {
-                if let Some((intersect_point)) = ray_intersect_bb_projection(sc, x, y) {
+                // the first light follows the cursor across the ground
+                let ground = sc.screen_point_to_ray(x, y).and_then(|ray| {
+                    sc.raycast(&ray, |name| name == "main_plain")
+                        .into_iter()
+                        .next()
+                });
+                if let Some(hit) = ground {
+                    let intersect_point = hit.point;
                     let first_light = sc.point_lights.get_mut(0).unwrap();
                     first_light.position =
                         Vector3::new(intersect_point.x, first_light.position.y, intersect_point.z)
@@ -243,41 +395,91 @@ pub fn scene_one(settings: Settings) -> Scene {
                 y,
                 ..
             } => {
-                if let Some((intersect_point)) = ray_intersect_bb_projection(sc, x, y) {
+                sc.drag_start = Some((x, y));
+            }
+            SDL2Event::MouseButtonUp {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                let Some(start) = sc.drag_start.take() else {
+                    return;
+                };
+
+                // dragging selects everything in the rectangle
+                if (x - start.0).abs() > 4 || (y - start.1).abs() > 4 {
+                    sc.selection = sc.pick_rect(start, (x, y));
+                    return;
+                }
+
+                // a click selects whatever is drawn under the cursor, clicking the ground sends
+                // the player there
+                sc.selection = sc.pick(x, y, 3).into_iter().collect();
+
+                let Some(ray) = sc.screen_point_to_ray(x, y) else {
+                    return;
+                };
+                let ground = sc.raycast(&ray, |name| name == "main_plain");
+                if let Some(ground) = ground.first() {
                     let player = sc.object_map.get_mut(&"player".to_string()).unwrap();
-                    sc.player_target = Vector3::new(
-                        intersect_point.x,
-                        player.model.position.y,
-                        intersect_point.z,
-                    );
-                    // println!("{} {}", sc.player_target, player.model.position);
-                    // let new = player.model.position.slerp(&sc.player_target, 1.0);
-                    // println!("{}", new);
-                    // player.model.position = Vector3::new(
-                    //     intersect_point.x,
-                    //     player.model.position.y,
-                    //     intersect_point.z,
-                    // )
-                    // sc.player_target = Vector3::new(intersect_point.x, 0.0, intersect_point.z)
+                    sc.player_target =
+                        Vector3::new(ground.point.x, player.model.position.y, ground.point.z);
                 }
             }
             SDL2Event::KeyDown {
                 keycode: Some(keycode),
                 ..
             } => {
-                // Check for WASD keys
+                // wasd, space and ctrl belong to the fly camera
                 match keycode {
-                    Keycode::A => {
-                        let plane = sc.object_map.get_mut(&"main_plain".to_string()).unwrap();
-                        plane.material.toggle_map(material::TextureType::ARM);
+                    Keycode::C => {
+                        // orbit -> fly -> follow the player -> fly-by around it -> orbit
+                        let camera = sc.cameras.get(&sc.active_camera).unwrap();
+                        let next: Box<dyn CameraController> =
+                            match sc.camera_controller.as_ref().map(|c| c.name()) {
+                                Some("orbit") => Box::new(FlyController::from_camera(camera)),
+                                Some("fly") => {
+                                    Box::new(FollowController::new(Vector3::new(0.0, 120.0, 160.0)))
+                                }
+                                Some("follow") => Box::new(fly_by()),
+                                _ => Box::new(OrbitController::from_camera(camera)),
+                            };
+                        println!("camera: {}", next.name());
+                        sc.camera_controller = Some(next);
                     }
                     Keycode::R => {
                         let plane = sc.object_map.get_mut(&"main_plain".to_string()).unwrap();
-                        plane.material.toggle_map(material::TextureType::ARM);
+                        plane.material.toggle_map("arm_texture");
                     }
                     Keycode::N => {
                         let plane = sc.object_map.get_mut(&"main_plain".to_string()).unwrap();
-                        plane.material.toggle_map(material::TextureType::NORMAL);
+                        plane.material.toggle_map("normal_texture");
+                    }
+                    Keycode::P => {
+                        let plane = sc.object_map.get_mut(&"main_plain".to_string()).unwrap();
+                        plane.material.toggle_map("height_texture");
+                    }
+                    Keycode::V => {
+                        // cycle through the material debug views
+                        sc.settings.debug_view = sc.settings.debug_view.next();
+                        println!("debug view: {:?}", sc.settings.debug_view);
+                    }
+                    Keycode::G => {
+                        // dive onto the green sphere, or pull back out when already next to it
+                        let green = Point3::from(sc.object_map["green"].model.position);
+                        let close = (sc.get_active_cam().position - green).norm() < 100.0;
+                        let swoop = swoop(!close);
+                        println!("camera: {}", swoop.name());
+                        sc.camera_controller = Some(Box::new(swoop));
+                    }
+                    Keycode::M => {
+                        let minimap = sc.cameras.get_mut("minimap").unwrap();
+                        minimap.enabled = !minimap.enabled;
+                    }
+                    Keycode::I => {
+                        // what the last frame cost: objects drawn/culled, draw calls and state switches
+                        println!("{:?}", sc.render_stats);
                     }
                     _ => {}
                 }
@@ -289,3 +491,39 @@ pub fn scene_one(settings: Settings) -> Scene {
 
     return sc;
 }
+
+// a slow loop around the middle of the scene that keeps the player in view
+fn fly_by() -> CameraPath {
+    let points = (0..8)
+        .map(|i| {
+            let angle = i as f32 / 8.0 * std::f32::consts::TAU;
+            let height = if i % 2 == 0 { 150.0 } else { 60.0 };
+            Vector3::new(angle.cos() * 300.0, height, angle.sin() * 300.0)
+        })
+        .chain(std::iter::once(Vector3::new(300.0, 150.0, 0.0)))
+        .collect();
+
+    let mut path = CameraPath::new(Spline::catmull_rom(points), LookAt::Target, 20.0);
+    path.easing = Easing::Linear;
+    path.looping = true;
+    path
+}
+
+// one bezier curve from high above the scene down next to the green sphere, watching it the
+// whole way. the dive slows into its end, pulling out speeds up as it leaves
+fn swoop(dive: bool) -> CameraPath {
+    let mut points = vec![
+        Vector3::new(0.0, 400.0, 450.0),
+        Vector3::new(0.0, 400.0, 150.0),
+        Vector3::new(160.0, 120.0, 60.0),
+        Vector3::new(65.0, 45.0, 45.0),
+    ];
+    if !dive {
+        points.reverse();
+    }
+
+    let look_at = LookAt::Object("green".to_string());
+    let mut path = CameraPath::new(Spline::bezier(points), look_at, 4.0);
+    path.easing = if dive { Easing::EaseOut } else { Easing::EaseIn };
+    path
+}
@@ -1,3 +1,5 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::fs::File;
 use std::io::{self, Read};
@@ -26,17 +28,39 @@ fn read_shader_to_string(file_path: String) -> io::Result<String> {
     Ok(contents)
 }
 
+thread_local! {
+    // programs that have already been compiled, keyed by their vert and frag names. gl only ever
+    // runs on the main thread so a thread local is enough
+    static PROGRAMS: RefCell<HashMap<(String, String), u32>> = RefCell::new(HashMap::new());
+}
+
+// compiles shaders/<vert>.vert.glsl and shaders/<frag>.frag.glsl the first time they are asked
+// for, every material after that shares the same program
+pub fn get_or_load(vert_name: &str, frag_name: &str) -> u32 {
+    PROGRAMS.with(|programs| {
+        *programs
+            .borrow_mut()
+            .entry((vert_name.to_string(), frag_name.to_string()))
+            .or_insert_with(|| Shader::from_stages(vert_name, frag_name).program)
+    })
+}
+
 pub struct Shader {
     pub program: u32,
 }
 
 impl Shader {
     pub fn new(shader_name: String) -> Shader {
+        Shader::from_stages(&shader_name, &shader_name)
+    }
+
+    // a vertex stage from one shader and the fragment stage from another
+    pub fn from_stages(vert_name: &str, frag_name: &str) -> Shader {
         // try and open the .vert file first
-        let vert_src = read_shader_to_string(format!("shaders/{}.vert.glsl", shader_name))
+        let vert_src = read_shader_to_string(format!("shaders/{}.vert.glsl", vert_name))
             .expect("failed to read vert source");
         // now the frag file
-        let frag_src = read_shader_to_string(format!("shaders/{}.frag.glsl", shader_name))
+        let frag_src = read_shader_to_string(format!("shaders/{}.frag.glsl", frag_name))
             .expect("failed to load frag source");
 
         // now we compile it
@@ -0,0 +1,245 @@
+// curves through 3d control points and the easing curves used to time moves along them. all pure
+// math, camera_path puts them to use
+
+use nalgebra::Vector3;
+
+pub enum Spline {
+    // passes through every point, the ends are extended by mirroring their neighbours
+    CatmullRom(Vec<Vector3<f32>>),
+    // cubic segments of start, two handles and end, sharing end points: 4, 7, 10... points
+    Bezier(Vec<Vector3<f32>>),
+}
+
+impl Spline {
+    pub fn catmull_rom(points: Vec<Vector3<f32>>) -> Spline {
+        assert!(
+            points.len() >= 2,
+            "a catmull-rom spline needs at least 2 points"
+        );
+        Spline::CatmullRom(points)
+    }
+
+    pub fn bezier(points: Vec<Vector3<f32>>) -> Spline {
+        assert!(
+            points.len() >= 4 && (points.len() - 1).is_multiple_of(3),
+            "a bezier spline needs 3n + 1 points"
+        );
+        Spline::Bezier(points)
+    }
+
+    pub fn segments(&self) -> usize {
+        match self {
+            Spline::CatmullRom(points) => points.len() - 1,
+            Spline::Bezier(points) => (points.len() - 1) / 3,
+        }
+    }
+
+    // t runs from 0 at the first point to 1 at the last, every segment taking an equal share
+    pub fn point(&self, t: f32) -> Vector3<f32> {
+        let segments = self.segments();
+        let scaled = t.clamp(0.0, 1.0) * segments as f32;
+        let segment = (scaled as usize).min(segments - 1);
+        let local = scaled - segment as f32;
+
+        match self {
+            Spline::CatmullRom(points) => {
+                let p1 = points[segment];
+                let p2 = points[segment + 1];
+                let p0 = if segment > 0 {
+                    points[segment - 1]
+                } else {
+                    p1 * 2.0 - p2
+                };
+                let p3 = if segment + 2 < points.len() {
+                    points[segment + 2]
+                } else {
+                    p2 * 2.0 - p1
+                };
+                catmull_rom(p0, p1, p2, p3, local)
+            }
+            Spline::Bezier(points) => {
+                let i = segment * 3;
+                bezier(
+                    points[i],
+                    points[i + 1],
+                    points[i + 2],
+                    points[i + 3],
+                    local,
+                )
+            }
+        }
+    }
+
+    // direction of travel at t, not normalized
+    pub fn tangent(&self, t: f32) -> Vector3<f32> {
+        let step = 1e-3;
+        let (a, b) = if t + step <= 1.0 {
+            (t, t + step)
+        } else {
+            (t - step, t)
+        };
+        (self.point(b) - self.point(a)) / step
+    }
+}
+
+pub fn catmull_rom(
+    p0: Vector3<f32>,
+    p1: Vector3<f32>,
+    p2: Vector3<f32>,
+    p3: Vector3<f32>,
+    t: f32,
+) -> Vector3<f32> {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+pub fn bezier(
+    p0: Vector3<f32>,
+    p1: Vector3<f32>,
+    p2: Vector3<f32>,
+    p3: Vector3<f32>,
+    t: f32,
+) -> Vector3<f32> {
+    let u = 1.0 - t;
+    p0 * (u * u * u) + p1 * (3.0 * u * u * t) + p2 * (3.0 * u * t * t) + p3 * (t * t * t)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    // maps 0..1 to 0..1, always starting at 0 and ending at 1
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+        (a - b).norm() < 1e-4
+    }
+
+    fn points() -> Vec<Vector3<f32>> {
+        vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(10.0, 5.0, 0.0),
+            Vector3::new(20.0, 0.0, 5.0),
+            Vector3::new(30.0, -5.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_its_points() {
+        let spline = Spline::catmull_rom(points());
+        assert_eq!(spline.segments(), 3);
+        for (i, point) in points().iter().enumerate() {
+            assert!(close(spline.point(i as f32 / 3.0), *point));
+        }
+
+        // outside 0..1 sticks to the ends
+        assert!(close(spline.point(-1.0), points()[0]));
+        assert!(close(spline.point(2.0), points()[3]));
+    }
+
+    #[test]
+    fn catmull_rom_of_a_line_stays_on_it() {
+        let spline = Spline::catmull_rom(vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            Vector3::new(2.0, 2.0, 2.0),
+        ]);
+        for i in 0..=20 {
+            let t = i as f32 / 20.0;
+            assert!(close(spline.point(t), Vector3::new(2.0, 2.0, 2.0) * t));
+        }
+        assert!(close(
+            spline.tangent(0.5).normalize(),
+            Vector3::new(1.0, 1.0, 1.0).normalize()
+        ));
+    }
+
+    #[test]
+    fn catmull_rom_is_continuous_across_segments() {
+        let spline = Spline::catmull_rom(points());
+        let joint = 1.0 / 3.0;
+        let before = spline.point(joint - 1e-4);
+        let after = spline.point(joint + 1e-4);
+        assert!((before - after).norm() < 0.05);
+
+        // and so is its direction
+        let tangent_before = spline.tangent(joint - 2e-3).normalize();
+        let tangent_after = spline.tangent(joint + 1e-3).normalize();
+        assert!(tangent_before.dot(&tangent_after) > 0.99);
+    }
+
+    #[test]
+    fn bezier_hits_its_end_points_and_not_its_handles() {
+        let spline = Spline::bezier(vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 10.0, 0.0),
+            Vector3::new(10.0, 10.0, 0.0),
+            Vector3::new(10.0, 0.0, 0.0),
+            Vector3::new(10.0, -10.0, 0.0),
+            Vector3::new(20.0, -10.0, 0.0),
+            Vector3::new(20.0, 0.0, 0.0),
+        ]);
+        assert_eq!(spline.segments(), 2);
+        assert!(close(spline.point(0.0), Vector3::new(0.0, 0.0, 0.0)));
+        assert!(close(spline.point(0.5), Vector3::new(10.0, 0.0, 0.0)));
+        assert!(close(spline.point(1.0), Vector3::new(20.0, 0.0, 0.0)));
+
+        // the middle of a symmetric segment sits 3/4 of the way to its handles
+        assert!(close(spline.point(0.25), Vector3::new(5.0, 7.5, 0.0)));
+        assert!(close(spline.point(0.75), Vector3::new(15.0, -7.5, 0.0)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn bezier_needs_whole_segments() {
+        Spline::bezier(points()[..3].to_vec());
+    }
+
+    #[test]
+    fn easing_curves_start_and_end_in_place() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(1.5), 1.0);
+
+            let mut last = 0.0;
+            for i in 1..=10 {
+                let value = easing.apply(i as f32 / 10.0);
+                assert!(value >= last, "{:?} goes backwards", easing);
+                last = value;
+            }
+        }
+
+        assert!(Easing::EaseIn.apply(0.5) < 0.5);
+        assert!(Easing::EaseOut.apply(0.5) > 0.5);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+}
@@ -6,11 +6,38 @@ use image::GenericImage;
 use image::DynamicImage::*;
 use image::GenericImageView;
 
-pub unsafe fn loadTexture(path: &str) -> u32 {
+use crate::texture_container::{self, TextureData};
+
+// how a texture is sampled, set per texture from material files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Wrap {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Filter {
+    #[default]
+    Linear,
+    // blocky, for pixel art
+    Nearest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Sampler {
+    pub wrap: Wrap,
+    pub filter: Filter,
+}
+
+// the image is opened before any gl object is made, so a file that can't be read leaves nothing
+// behind
+pub unsafe fn load_texture_with_sampler(path: &str, sampler: Sampler) -> Result<u32, String> {
+    let img = image::open(Path::new(path)).map_err(|err| err.to_string())?;
     let mut textureID = 0;
 
     gl::GenTextures(1, &mut textureID);
-    let img = image::open(&Path::new(path)).expect("Texture failed to load");
     let format = match img {
         ImageLuma8(_) => gl::RED,
         ImageLumaA8(_) => gl::RG,
@@ -19,6 +46,8 @@ pub unsafe fn loadTexture(path: &str) -> u32 {
         _ => gl::RGBA
     };
 
+    // the pixels are always expanded to rgba8, the format above only picks what the gpu keeps
+    // so images with an alpha channel hold on to it for transparent materials
     let img = img.to_rgba8();
     let data = img.as_raw();
 
@@ -30,20 +59,173 @@ pub unsafe fn loadTexture(path: &str) -> u32 {
         img.width() as i32,
         img.height() as i32,
         0,
-        format,
+        gl::RGBA,
         gl::UNSIGNED_BYTE,
         data.as_ptr() as *const c_void,
     );
     gl::GenerateMipmap(gl::TEXTURE_2D);
+    apply_sampler(gl::TEXTURE_2D, sampler, true);
+
+    Ok(textureID)
+}
+
+// sets wrapping and filtering on the texture bound to target, textures without mips can't use a
+// mipmapped min filter or they are incomplete and sample black
+unsafe fn apply_sampler(target: u32, sampler: Sampler, mipmapped: bool) {
+    let wrap = match sampler.wrap {
+        Wrap::Repeat => gl::REPEAT,
+        Wrap::Clamp => gl::CLAMP_TO_EDGE,
+        Wrap::Mirror => gl::MIRRORED_REPEAT,
+    };
+    let (min_filter, mag_filter) = match (sampler.filter, mipmapped) {
+        (Filter::Linear, true) => (gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR),
+        (Filter::Linear, false) => (gl::LINEAR, gl::LINEAR),
+        (Filter::Nearest, true) => (gl::NEAREST_MIPMAP_NEAREST, gl::NEAREST),
+        (Filter::Nearest, false) => (gl::NEAREST, gl::NEAREST),
+    };
 
-    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+    gl::TexParameteri(target, gl::TEXTURE_WRAP_S, wrap as i32);
+    gl::TexParameteri(target, gl::TEXTURE_WRAP_T, wrap as i32);
+    gl::TexParameteri(target, gl::TEXTURE_WRAP_R, wrap as i32);
+    gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+    gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, mag_filter as i32);
+}
+
+// uploads a parsed dds/ktx2 file level by level as it is stored, compressed data stays compressed.
+// returns the texture and the target it has to be bound to
+pub unsafe fn upload_container(data: &TextureData, sampler: Sampler) -> (u32, u32) {
+    let target = if data.is_cubemap() {
+        gl::TEXTURE_CUBE_MAP
+    } else if data.array {
+        gl::TEXTURE_2D_ARRAY
+    } else {
+        gl::TEXTURE_2D
+    };
+    let internal_format = data.format.gl_internal_format(data.srgb);
+
+    let mut texture_id = 0;
+    gl::GenTextures(1, &mut texture_id);
+    gl::BindTexture(target, texture_id);
+    // small mips of rgba8 files aren't always 4 byte aligned
+    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+
+    for (level, mip) in data.levels.iter().enumerate() {
+        let (width, height) = (mip.width as i32, mip.height as i32);
+        if target == gl::TEXTURE_2D_ARRAY {
+            upload_image(
+                gl::TEXTURE_2D_ARRAY,
+                level,
+                internal_format,
+                (width, height, data.layers as i32),
+                data.format.is_compressed(),
+                &mip.data,
+            );
+        } else {
+            for face in 0..data.faces {
+                let face_target = if data.is_cubemap() {
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face
+                } else {
+                    gl::TEXTURE_2D
+                };
+                upload_image(
+                    face_target,
+                    level,
+                    internal_format,
+                    (width, height, 0),
+                    data.format.is_compressed(),
+                    data.image(level, 0, face),
+                );
+            }
+        }
+    }
+    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+    // compressed formats can't be rendered to, so their mips only ever come from the file
+    let generate = data.generate_mipmaps && !data.format.is_compressed();
+    if generate {
+        gl::GenerateMipmap(target);
+    }
+    gl::TexParameteri(target, gl::TEXTURE_BASE_LEVEL, 0);
     gl::TexParameteri(
-        gl::TEXTURE_2D,
-        gl::TEXTURE_MIN_FILTER,
-        gl::LINEAR_MIPMAP_LINEAR as i32,
+        target,
+        gl::TEXTURE_MAX_LEVEL,
+        if generate { 1000 } else { data.levels.len() as i32 - 1 },
     );
-    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+    apply_sampler(target, sampler, generate || data.levels.len() > 1);
+
+    (texture_id, target)
+}
+
+// a depth of 0 means a 2d image, anything else is the layer count of an array
+unsafe fn upload_image(
+    target: u32,
+    level: usize,
+    internal_format: u32,
+    (width, height, depth): (i32, i32, i32),
+    compressed: bool,
+    pixels: &[u8],
+) {
+    let ptr = pixels.as_ptr() as *const c_void;
+    match (compressed, depth) {
+        (true, 0) => gl::CompressedTexImage2D(
+            target,
+            level as i32,
+            internal_format,
+            width,
+            height,
+            0,
+            pixels.len() as i32,
+            ptr,
+        ),
+        (true, _) => gl::CompressedTexImage3D(
+            target,
+            level as i32,
+            internal_format,
+            width,
+            height,
+            depth,
+            0,
+            pixels.len() as i32,
+            ptr,
+        ),
+        (false, 0) => gl::TexImage2D(
+            target,
+            level as i32,
+            internal_format as i32,
+            width,
+            height,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            ptr,
+        ),
+        (false, _) => gl::TexImage3D(
+            target,
+            level as i32,
+            internal_format as i32,
+            width,
+            height,
+            depth,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            ptr,
+        ),
+    }
+}
 
-    textureID
+// dds and ktx2 files go through texture_container, everything else through the image crate.
+// returns the texture and the target it has to be bound to
+pub unsafe fn load_texture_file(path: &str, sampler: Sampler) -> Result<(u32, u32), String> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("dds") | Some("ktx2") => {
+            let data = texture_container::load(path).map_err(|err| err.to_string())?;
+            Ok(upload_container(&data, sampler))
+        }
+        _ => Ok((load_texture_with_sampler(path, sampler)?, gl::TEXTURE_2D)),
+    }
 }
@@ -0,0 +1,779 @@
+// dds and ktx2 files: block compressed (bc1-bc7) or plain rgba8 images with their mip chains,
+// array layers and cube faces already laid out, so they go to the gpu as they are without being
+// decoded. parsing doesn't touch gl, see texture::upload_container for that
+
+use std::fmt;
+use std::fs;
+
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+// dds header flags and caps
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALL_FACES: u32 = 0xFC00;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const DDS_DIMENSION_TEXTURE2D: u32 = 3;
+
+// s3tc formats come from EXT_texture_compression_s3tc and aren't in the core bindings
+const COMPRESSED_RGB_S3TC_DXT1: u32 = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1: u32 = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3: u32 = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5: u32 = 0x83F3;
+const COMPRESSED_SRGB_S3TC_DXT1: u32 = 0x8C4C;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1: u32 = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3: u32 = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5: u32 = 0x8C4F;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    Bc1Rgb,
+    Bc1Rgba,
+    Bc2,
+    Bc3,
+    Bc4Unorm,
+    Bc4Snorm,
+    Bc5Unorm,
+    Bc5Snorm,
+    Bc6hUfloat,
+    Bc6hSfloat,
+    Bc7,
+    Rgba8,
+}
+
+impl TextureFormat {
+    pub fn is_compressed(&self) -> bool {
+        *self != TextureFormat::Rgba8
+    }
+
+    // bytes per 4x4 block, or per pixel for rgba8
+    pub fn block_bytes(&self) -> usize {
+        match self {
+            TextureFormat::Bc1Rgb
+            | TextureFormat::Bc1Rgba
+            | TextureFormat::Bc4Unorm
+            | TextureFormat::Bc4Snorm => 8,
+            TextureFormat::Rgba8 => 4,
+            _ => 16,
+        }
+    }
+
+    // size of one image of a mip level, partial blocks at the edges still take a whole block
+    pub fn image_size(&self, width: u32, height: u32) -> usize {
+        self.checked_image_size(width, height)
+            .expect("image sizes are checked against the file when it is parsed")
+    }
+
+    fn checked_image_size(&self, width: u32, height: u32) -> Option<usize> {
+        let (width, height) = (width.max(1) as usize, height.max(1) as usize);
+        if self.is_compressed() {
+            width
+                .div_ceil(4)
+                .checked_mul(height.div_ceil(4))?
+                .checked_mul(self.block_bytes())
+        } else {
+            width.checked_mul(height)?.checked_mul(self.block_bytes())
+        }
+    }
+
+    pub fn gl_internal_format(&self, srgb: bool) -> u32 {
+        match (self, srgb) {
+            (TextureFormat::Bc1Rgb, false) => COMPRESSED_RGB_S3TC_DXT1,
+            (TextureFormat::Bc1Rgb, true) => COMPRESSED_SRGB_S3TC_DXT1,
+            (TextureFormat::Bc1Rgba, false) => COMPRESSED_RGBA_S3TC_DXT1,
+            (TextureFormat::Bc1Rgba, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT1,
+            (TextureFormat::Bc2, false) => COMPRESSED_RGBA_S3TC_DXT3,
+            (TextureFormat::Bc2, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT3,
+            (TextureFormat::Bc3, false) => COMPRESSED_RGBA_S3TC_DXT5,
+            (TextureFormat::Bc3, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT5,
+            (TextureFormat::Bc4Unorm, _) => gl::COMPRESSED_RED_RGTC1,
+            (TextureFormat::Bc4Snorm, _) => gl::COMPRESSED_SIGNED_RED_RGTC1,
+            (TextureFormat::Bc5Unorm, _) => gl::COMPRESSED_RG_RGTC2,
+            (TextureFormat::Bc5Snorm, _) => gl::COMPRESSED_SIGNED_RG_RGTC2,
+            (TextureFormat::Bc6hUfloat, _) => gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+            (TextureFormat::Bc6hSfloat, _) => gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT,
+            (TextureFormat::Bc7, false) => gl::COMPRESSED_RGBA_BPTC_UNORM,
+            (TextureFormat::Bc7, true) => gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+            (TextureFormat::Rgba8, false) => gl::RGBA8,
+            (TextureFormat::Rgba8, true) => gl::SRGB8_ALPHA8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextureFileError {
+    Io(String),
+    UnknownContainer,
+    Truncated,
+    Unsupported(String),
+    // the header contradicts itself, like more mips than the image can have
+    Invalid(String),
+}
+
+impl fmt::Display for TextureFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureFileError::Io(message) => write!(f, "{}", message),
+            TextureFileError::UnknownContainer => write!(f, "not a dds or ktx2 file"),
+            TextureFileError::Truncated => write!(f, "file ends before its image data does"),
+            TextureFileError::Unsupported(what) => write!(f, "unsupported {}", what),
+            TextureFileError::Invalid(what) => write!(f, "invalid header, {}", what),
+        }
+    }
+}
+
+pub struct MipLevel {
+    pub width: u32,
+    pub height: u32,
+    // every layer and face of the level back to back, layer major
+    pub data: Vec<u8>,
+}
+
+pub struct TextureData {
+    pub format: TextureFormat,
+    pub srgb: bool,
+    pub width: u32,
+    pub height: u32,
+    // number of array layers, arrays of one layer still count as arrays
+    pub layers: u32,
+    pub array: bool,
+    // 6 for cubemaps, in +x -x +y -y +z -z order
+    pub faces: u32,
+    pub levels: Vec<MipLevel>,
+    // the file asked for mips to be generated after upload instead of carrying them
+    pub generate_mipmaps: bool,
+}
+
+impl TextureData {
+    pub fn is_cubemap(&self) -> bool {
+        self.faces == 6
+    }
+
+    // one layer and face of a mip level
+    pub fn image(&self, level: usize, layer: u32, face: u32) -> &[u8] {
+        let mip = &self.levels[level];
+        let size = self.format.image_size(mip.width, mip.height);
+        let start = (layer * self.faces + face) as usize * size;
+        &mip.data[start..start + size]
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, TextureFileError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(TextureFileError::Truncated)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, TextureFileError> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(TextureFileError::Truncated)
+}
+
+fn mip_size(size: u32, level: u32) -> u32 {
+    size.checked_shr(level).unwrap_or(0).max(1)
+}
+
+// a chain ends at 1x1, so a 16x8 image has at most 5 levels
+fn check_levels(levels: u32, width: u32, height: u32) -> Result<(), TextureFileError> {
+    let full_chain = u32::BITS - width.max(height).max(1).leading_zeros();
+    if levels > full_chain {
+        return Err(TextureFileError::Invalid(format!(
+            "{} mip levels for a {}x{} image",
+            levels, width, height
+        )));
+    }
+    Ok(())
+}
+
+// bytes taken by every layer and face of the whole mip chain. too big to address is reported as
+// invalid, bigger than what the file holds after data_start as truncated
+fn check_data_size(
+    bytes: &[u8],
+    data_start: usize,
+    format: TextureFormat,
+    (width, height): (u32, u32),
+    levels: u32,
+    images: u32,
+) -> Result<(), TextureFileError> {
+    let overflow = || TextureFileError::Invalid(format!("{}x{} image is too big", width, height));
+    let mut chain: usize = 0;
+    for level in 0..levels {
+        let size = format
+            .checked_image_size(mip_size(width, level), mip_size(height, level))
+            .ok_or_else(overflow)?;
+        chain = chain.checked_add(size).ok_or_else(overflow)?;
+    }
+    let total = chain.checked_mul(images as usize).ok_or_else(overflow)?;
+    if data_start
+        .checked_add(total)
+        .is_none_or(|end| end > bytes.len())
+    {
+        return Err(TextureFileError::Truncated);
+    }
+    Ok(())
+}
+
+pub fn load(path: &str) -> Result<TextureData, TextureFileError> {
+    let bytes = fs::read(path).map_err(|err| TextureFileError::Io(format!("{}: {}", path, err)))?;
+    parse(&bytes)
+}
+
+// picks the container from the file's magic number
+pub fn parse(bytes: &[u8]) -> Result<TextureData, TextureFileError> {
+    if bytes.starts_with(DDS_MAGIC) {
+        parse_dds(bytes)
+    } else if bytes.starts_with(&KTX2_IDENTIFIER) {
+        parse_ktx2(bytes)
+    } else {
+        Err(TextureFileError::UnknownContainer)
+    }
+}
+
+fn dxgi_format(dxgi: u32) -> Option<(TextureFormat, bool)> {
+    Some(match dxgi {
+        28 => (TextureFormat::Rgba8, false),
+        29 => (TextureFormat::Rgba8, true),
+        71 => (TextureFormat::Bc1Rgba, false),
+        72 => (TextureFormat::Bc1Rgba, true),
+        74 => (TextureFormat::Bc2, false),
+        75 => (TextureFormat::Bc2, true),
+        77 => (TextureFormat::Bc3, false),
+        78 => (TextureFormat::Bc3, true),
+        80 => (TextureFormat::Bc4Unorm, false),
+        81 => (TextureFormat::Bc4Snorm, false),
+        83 => (TextureFormat::Bc5Unorm, false),
+        84 => (TextureFormat::Bc5Snorm, false),
+        95 => (TextureFormat::Bc6hUfloat, false),
+        96 => (TextureFormat::Bc6hSfloat, false),
+        98 => (TextureFormat::Bc7, false),
+        99 => (TextureFormat::Bc7, true),
+        _ => return None,
+    })
+}
+
+pub fn parse_dds(bytes: &[u8]) -> Result<TextureData, TextureFileError> {
+    if !bytes.starts_with(DDS_MAGIC) {
+        return Err(TextureFileError::UnknownContainer);
+    }
+    let flags = read_u32(bytes, 8)?;
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    let mip_count = read_u32(bytes, 28)?;
+    let pf_flags = read_u32(bytes, 80)?;
+    let four_cc = bytes.get(84..88).ok_or(TextureFileError::Truncated)?;
+    let bit_count = read_u32(bytes, 88)?;
+    let red_mask = read_u32(bytes, 92)?;
+    let caps2 = read_u32(bytes, 112)?;
+
+    if caps2 & DDSCAPS2_VOLUME != 0 {
+        return Err(TextureFileError::Unsupported("volume texture".to_string()));
+    }
+
+    let levels = if flags & DDSD_MIPMAPCOUNT != 0 {
+        mip_count.max(1)
+    } else {
+        1
+    };
+    let mut faces = 1;
+    if caps2 & DDSCAPS2_CUBEMAP != 0 {
+        if caps2 & DDSCAPS2_CUBEMAP_ALL_FACES != DDSCAPS2_CUBEMAP_ALL_FACES {
+            return Err(TextureFileError::Unsupported("partial cubemap".to_string()));
+        }
+        faces = 6;
+    }
+    let mut layers = 1;
+    let mut array = false;
+    let mut data_start = 128;
+    // legacy bgra files are swizzled to rgba while reading
+    let mut swap_red_blue = false;
+
+    let (format, srgb) = if pf_flags & DDPF_FOURCC != 0 {
+        match four_cc {
+            b"DXT1" if pf_flags & DDPF_ALPHAPIXELS != 0 => (TextureFormat::Bc1Rgba, false),
+            b"DXT1" => (TextureFormat::Bc1Rgb, false),
+            b"DXT2" | b"DXT3" => (TextureFormat::Bc2, false),
+            b"DXT4" | b"DXT5" => (TextureFormat::Bc3, false),
+            b"ATI1" | b"BC4U" => (TextureFormat::Bc4Unorm, false),
+            b"BC4S" => (TextureFormat::Bc4Snorm, false),
+            b"ATI2" | b"BC5U" => (TextureFormat::Bc5Unorm, false),
+            b"BC5S" => (TextureFormat::Bc5Snorm, false),
+            b"DX10" => {
+                let dxgi = read_u32(bytes, 128)?;
+                let dimension = read_u32(bytes, 132)?;
+                let misc = read_u32(bytes, 136)?;
+                let array_size = read_u32(bytes, 140)?;
+                data_start = 148;
+
+                if dimension != DDS_DIMENSION_TEXTURE2D {
+                    return Err(TextureFileError::Unsupported(format!(
+                        "dds resource dimension {}",
+                        dimension
+                    )));
+                }
+                if misc & DDS_RESOURCE_MISC_TEXTURECUBE != 0 {
+                    faces = 6;
+                }
+                layers = array_size.max(1);
+                array = layers > 1;
+                dxgi_format(dxgi)
+                    .ok_or_else(|| TextureFileError::Unsupported(format!("dxgi format {}", dxgi)))?
+            }
+            other => {
+                return Err(TextureFileError::Unsupported(format!(
+                    "fourcc {}",
+                    String::from_utf8_lossy(other)
+                )))
+            }
+        }
+    } else if pf_flags & DDPF_RGB != 0 && bit_count == 32 {
+        match red_mask {
+            0x0000_00FF => (TextureFormat::Rgba8, false),
+            0x00FF_0000 => {
+                swap_red_blue = true;
+                (TextureFormat::Rgba8, false)
+            }
+            _ => {
+                return Err(TextureFileError::Unsupported(
+                    "32 bit channel layout".to_string(),
+                ))
+            }
+        }
+    } else {
+        return Err(TextureFileError::Unsupported(
+            "dds pixel format".to_string(),
+        ));
+    };
+
+    if faces == 6 && array {
+        return Err(TextureFileError::Unsupported(
+            "cubemap array (needs gl 4)".to_string(),
+        ));
+    }
+
+    let images = layers
+        .checked_mul(faces)
+        .ok_or_else(|| TextureFileError::Invalid(format!("{} array layers", layers)))?;
+    check_levels(levels, width, height)?;
+    check_data_size(bytes, data_start, format, (width, height), levels, images)?;
+
+    let mut mips: Vec<MipLevel> = (0..levels)
+        .map(|level| MipLevel {
+            width: mip_size(width, level),
+            height: mip_size(height, level),
+            data: Vec::new(),
+        })
+        .collect();
+
+    // dds stores each layer and face with its whole mip chain before the next one
+    let mut offset = data_start;
+    for _ in 0..images {
+        for mip in mips.iter_mut() {
+            let size = format.image_size(mip.width, mip.height);
+            let image = bytes
+                .get(offset..offset + size)
+                .ok_or(TextureFileError::Truncated)?;
+            mip.data.extend_from_slice(image);
+            offset += size;
+        }
+    }
+
+    if swap_red_blue {
+        for mip in mips.iter_mut() {
+            for pixel in mip.data.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+    }
+
+    Ok(TextureData {
+        format,
+        srgb,
+        width,
+        height,
+        layers,
+        array,
+        faces,
+        levels: mips,
+        generate_mipmaps: false,
+    })
+}
+
+fn vk_format(vk: u32) -> Option<(TextureFormat, bool)> {
+    Some(match vk {
+        37 => (TextureFormat::Rgba8, false),
+        43 => (TextureFormat::Rgba8, true),
+        131 => (TextureFormat::Bc1Rgb, false),
+        132 => (TextureFormat::Bc1Rgb, true),
+        133 => (TextureFormat::Bc1Rgba, false),
+        134 => (TextureFormat::Bc1Rgba, true),
+        135 => (TextureFormat::Bc2, false),
+        136 => (TextureFormat::Bc2, true),
+        137 => (TextureFormat::Bc3, false),
+        138 => (TextureFormat::Bc3, true),
+        139 => (TextureFormat::Bc4Unorm, false),
+        140 => (TextureFormat::Bc4Snorm, false),
+        141 => (TextureFormat::Bc5Unorm, false),
+        142 => (TextureFormat::Bc5Snorm, false),
+        143 => (TextureFormat::Bc6hUfloat, false),
+        144 => (TextureFormat::Bc6hSfloat, false),
+        145 => (TextureFormat::Bc7, false),
+        146 => (TextureFormat::Bc7, true),
+        _ => return None,
+    })
+}
+
+pub fn parse_ktx2(bytes: &[u8]) -> Result<TextureData, TextureFileError> {
+    if !bytes.starts_with(&KTX2_IDENTIFIER) {
+        return Err(TextureFileError::UnknownContainer);
+    }
+    let vk = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?.max(1);
+    let depth = read_u32(bytes, 28)?;
+    let layer_count = read_u32(bytes, 32)?;
+    let faces = read_u32(bytes, 36)?.max(1);
+    let level_count = read_u32(bytes, 40)?;
+    let supercompression = read_u32(bytes, 44)?;
+
+    let (format, srgb) =
+        vk_format(vk).ok_or_else(|| TextureFileError::Unsupported(format!("vk format {}", vk)))?;
+    if supercompression != 0 {
+        return Err(TextureFileError::Unsupported(format!(
+            "supercompression scheme {}",
+            supercompression
+        )));
+    }
+    if depth > 1 {
+        return Err(TextureFileError::Unsupported("volume texture".to_string()));
+    }
+    if faces != 1 && faces != 6 {
+        return Err(TextureFileError::Unsupported(format!("{} faces", faces)));
+    }
+    let array = layer_count > 0;
+    let layers = layer_count.max(1);
+    if faces == 6 && array {
+        return Err(TextureFileError::Unsupported(
+            "cubemap array (needs gl 4)".to_string(),
+        ));
+    }
+
+    let images = layers
+        .checked_mul(faces)
+        .ok_or_else(|| TextureFileError::Invalid(format!("{} array layers", layers)))?;
+    check_levels(level_count, width, height)?;
+
+    // the level index follows the 80 byte header and lists the biggest mip first
+    let mut levels = Vec::new();
+    for level in 0..level_count.max(1) {
+        let entry = 80 + level as usize * 24;
+        let offset = read_u64(bytes, entry)? as usize;
+        let length = read_u64(bytes, entry + 8)? as usize;
+
+        let (w, h) = (mip_size(width, level), mip_size(height, level));
+        let expected = format
+            .checked_image_size(w, h)
+            .and_then(|size| size.checked_mul(images as usize))
+            .ok_or_else(|| {
+                TextureFileError::Invalid(format!("{}x{} image is too big", width, height))
+            })?;
+        if length != expected {
+            return Err(TextureFileError::Unsupported(format!(
+                "level {} holds {} bytes, expected {}",
+                level, length, expected
+            )));
+        }
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| bytes.get(offset..end))
+            .ok_or(TextureFileError::Truncated)?;
+        levels.push(MipLevel {
+            width: w,
+            height: h,
+            data: data.to_vec(),
+        });
+    }
+
+    Ok(TextureData {
+        format,
+        srgb,
+        width,
+        height,
+        layers,
+        array,
+        faces,
+        levels,
+        generate_mipmaps: level_count == 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a dds header for a 2d texture, the pixel format is filled in by the caller
+    fn dds_header(width: u32, height: u32, mips: u32) -> Vec<u8> {
+        let mut bytes = vec![0u8; 128];
+        bytes[0..4].copy_from_slice(DDS_MAGIC);
+        bytes[4..8].copy_from_slice(&124u32.to_le_bytes());
+        bytes[8..12].copy_from_slice(&(0x1007 | DDSD_MIPMAPCOUNT).to_le_bytes());
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[28..32].copy_from_slice(&mips.to_le_bytes());
+        bytes[76..80].copy_from_slice(&32u32.to_le_bytes());
+        bytes
+    }
+
+    fn set_four_cc(bytes: &mut [u8], four_cc: &[u8; 4]) {
+        bytes[80..84].copy_from_slice(&DDPF_FOURCC.to_le_bytes());
+        bytes[84..88].copy_from_slice(four_cc);
+    }
+
+    fn dx10(bytes: &mut Vec<u8>, dxgi: u32, misc: u32, array_size: u32) {
+        set_four_cc(bytes, b"DX10");
+        for value in [dxgi, DDS_DIMENSION_TEXTURE2D, misc, array_size, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    // every image gets a distinct fill byte so the reordering can be checked
+    fn images(format: TextureFormat, sizes: &[(u32, u32)], first: u8) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (i, (w, h)) in sizes.iter().enumerate() {
+            data.extend(std::iter::repeat_n(
+                first + i as u8,
+                format.image_size(*w, *h),
+            ));
+        }
+        data
+    }
+
+    #[test]
+    fn image_sizes_round_up_to_blocks() {
+        assert_eq!(TextureFormat::Bc1Rgb.image_size(16, 16), 128);
+        assert_eq!(TextureFormat::Bc7.image_size(16, 16), 256);
+        // 5x3 still needs 2x1 blocks, mips below 4 pixels take one block
+        assert_eq!(TextureFormat::Bc3.image_size(5, 3), 32);
+        assert_eq!(TextureFormat::Bc4Unorm.image_size(1, 1), 8);
+        assert_eq!(TextureFormat::Rgba8.image_size(3, 2), 24);
+    }
+
+    #[test]
+    fn dds_dxt1_with_mip_chain() {
+        let mut bytes = dds_header(16, 8, 5);
+        set_four_cc(&mut bytes, b"DXT1");
+        let sizes = [(16, 8), (8, 4), (4, 2), (2, 1), (1, 1)];
+        bytes.extend(images(TextureFormat::Bc1Rgb, &sizes, 1));
+
+        let texture = parse(&bytes).unwrap();
+        assert_eq!(texture.format, TextureFormat::Bc1Rgb);
+        assert_eq!((texture.width, texture.height), (16, 8));
+        assert_eq!(texture.levels.len(), 5);
+        assert!(!texture.is_cubemap() && !texture.array);
+        assert_eq!(texture.levels[0].data.len(), 64);
+        assert_eq!((texture.levels[3].width, texture.levels[3].height), (2, 1));
+        assert!(texture.image(4, 0, 0).iter().all(|b| *b == 5));
+    }
+
+    #[test]
+    fn dds_dx10_bc7_array_is_regrouped_by_level() {
+        let mut bytes = dds_header(8, 8, 2);
+        dx10(&mut bytes, 99, 0, 3);
+        // layer by layer, each with its own mip chain
+        for layer in 0..3u8 {
+            bytes.extend(images(TextureFormat::Bc7, &[(8, 8), (4, 4)], 10 * layer));
+        }
+
+        let texture = parse_dds(&bytes).unwrap();
+        assert_eq!(texture.format, TextureFormat::Bc7);
+        assert!(texture.srgb);
+        assert!(texture.array);
+        assert_eq!(texture.layers, 3);
+        assert_eq!(texture.levels[0].data.len(), 3 * 64);
+        assert_eq!(texture.levels[1].data.len(), 3 * 16);
+        assert!(texture.image(0, 2, 0).iter().all(|b| *b == 20));
+        assert!(texture.image(1, 1, 0).iter().all(|b| *b == 11));
+    }
+
+    #[test]
+    fn dds_cubemap_and_bgra() {
+        let mut bytes = dds_header(4, 4, 1);
+        set_four_cc(&mut bytes, b"DXT5");
+        bytes[112..116]
+            .copy_from_slice(&(DDSCAPS2_CUBEMAP | DDSCAPS2_CUBEMAP_ALL_FACES).to_le_bytes());
+        for face in 0..6u8 {
+            bytes.extend(images(TextureFormat::Bc3, &[(4, 4)], face));
+        }
+        let cube = parse_dds(&bytes).unwrap();
+        assert!(cube.is_cubemap());
+        assert!(cube.image(0, 0, 4).iter().all(|b| *b == 4));
+
+        // a partial cube isn't something gl can sample
+        bytes[112..116].copy_from_slice(&(DDSCAPS2_CUBEMAP | 0x0C00).to_le_bytes());
+        assert!(matches!(
+            parse_dds(&bytes),
+            Err(TextureFileError::Unsupported(_))
+        ));
+
+        let mut bgra = dds_header(1, 1, 1);
+        bgra[80..84].copy_from_slice(&(DDPF_RGB | DDPF_ALPHAPIXELS).to_le_bytes());
+        bgra[88..92].copy_from_slice(&32u32.to_le_bytes());
+        bgra[92..96].copy_from_slice(&0x00FF_0000u32.to_le_bytes());
+        bgra.extend([1, 2, 3, 4]);
+        let texture = parse_dds(&bgra).unwrap();
+        assert_eq!(texture.format, TextureFormat::Rgba8);
+        assert_eq!(texture.levels[0].data, vec![3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn dds_errors() {
+        let mut bytes = dds_header(8, 8, 1);
+        set_four_cc(&mut bytes, b"DXT1");
+        bytes.extend([0u8; 16]);
+        assert_eq!(parse_dds(&bytes).err(), Some(TextureFileError::Truncated));
+
+        let mut unknown = dds_header(8, 8, 1);
+        set_four_cc(&mut unknown, b"ETC2");
+        assert!(matches!(
+            parse_dds(&unknown),
+            Err(TextureFileError::Unsupported(_))
+        ));
+
+        assert_eq!(
+            parse(b"PNG...").err(),
+            Some(TextureFileError::UnknownContainer)
+        );
+        assert_eq!(parse_dds(b"DDS ").err(), Some(TextureFileError::Truncated));
+    }
+
+    #[test]
+    fn dds_header_counts_are_checked_against_the_image() {
+        // a 16x8 chain ends after 5 levels, 40 would shift past the width
+        let mut too_many_mips = dds_header(16, 8, 40);
+        set_four_cc(&mut too_many_mips, b"DXT1");
+        assert!(matches!(
+            parse_dds(&too_many_mips),
+            Err(TextureFileError::Invalid(_))
+        ));
+
+        // huge sizes and layer counts fail before anything is allocated for them
+        let mut huge = dds_header(u32::MAX, u32::MAX, 1);
+        set_four_cc(&mut huge, b"DXT1");
+        assert_eq!(parse_dds(&huge).err(), Some(TextureFileError::Truncated));
+
+        let mut layers = dds_header(4, 4, 1);
+        dx10(&mut layers, 71, 0, u32::MAX);
+        layers.extend([0u8; 8]);
+        assert_eq!(parse_dds(&layers).err(), Some(TextureFileError::Truncated));
+    }
+
+    // header, empty dfd/kvd/sgd and the level index, followed by the level data
+    fn ktx2(
+        vk: u32,
+        size: (u32, u32),
+        layers: u32,
+        faces: u32,
+        level_data: &[Vec<u8>],
+        declared_levels: u32,
+    ) -> Vec<u8> {
+        let mut bytes = KTX2_IDENTIFIER.to_vec();
+        for value in [vk, 1, size.0, size.1, 0, layers, faces, declared_levels, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend([0u8; 32]);
+        let mut offset = bytes.len() + level_data.len() * 24;
+        for data in level_data {
+            for value in [offset as u64, data.len() as u64, data.len() as u64] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            offset += data.len();
+        }
+        for data in level_data {
+            bytes.extend(data);
+        }
+        bytes
+    }
+
+    #[test]
+    fn ktx2_bc5_with_mips() {
+        let levels = vec![vec![1u8; 64], vec![2u8; 16], vec![3u8; 16]];
+        let bytes = ktx2(141, (8, 8), 0, 1, &levels, 3);
+
+        let texture = parse(&bytes).unwrap();
+        assert_eq!(texture.format, TextureFormat::Bc5Unorm);
+        assert!(!texture.srgb && !texture.array && !texture.generate_mipmaps);
+        assert_eq!(texture.levels.len(), 3);
+        assert_eq!(texture.levels[2].data, vec![3u8; 16]);
+        assert_eq!((texture.levels[2].width, texture.levels[2].height), (2, 2));
+    }
+
+    #[test]
+    fn ktx2_arrays_cubes_and_generated_mips() {
+        let array = ktx2(145, (4, 4), 2, 1, &[vec![7u8; 32]], 1);
+        let texture = parse_ktx2(&array).unwrap();
+        assert!(texture.array);
+        assert_eq!(texture.layers, 2);
+        assert_eq!(texture.image(0, 1, 0).len(), 16);
+
+        let cube = ktx2(43, (2, 2), 0, 6, &[vec![9u8; 6 * 16]], 0);
+        let texture = parse_ktx2(&cube).unwrap();
+        assert!(texture.is_cubemap());
+        assert!(texture.srgb);
+        assert!(texture.generate_mipmaps);
+        assert_eq!(
+            texture.format.gl_internal_format(texture.srgb),
+            gl::SRGB8_ALPHA8
+        );
+    }
+
+    #[test]
+    fn ktx2_errors() {
+        let wrong_size = ktx2(131, (8, 8), 0, 1, &[vec![0u8; 8]], 1);
+        assert!(matches!(
+            parse_ktx2(&wrong_size),
+            Err(TextureFileError::Unsupported(_))
+        ));
+
+        let mut supercompressed = ktx2(131, (4, 4), 0, 1, &[vec![0u8; 8]], 1);
+        supercompressed[44..48].copy_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(
+            parse_ktx2(&supercompressed),
+            Err(TextureFileError::Unsupported(_))
+        ));
+
+        let astc = ktx2(157, (4, 4), 0, 1, &[vec![0u8; 16]], 1);
+        assert!(matches!(
+            parse_ktx2(&astc),
+            Err(TextureFileError::Unsupported(_))
+        ));
+
+        let mut truncated = ktx2(131, (4, 4), 0, 1, &[vec![0u8; 8]], 1);
+        truncated.truncate(truncated.len() - 4);
+        assert_eq!(
+            parse_ktx2(&truncated).err(),
+            Some(TextureFileError::Truncated)
+        );
+
+        let too_many_mips = ktx2(131, (4, 4), 0, 1, &[vec![0u8; 8]], 33);
+        assert!(matches!(
+            parse_ktx2(&too_many_mips),
+            Err(TextureFileError::Invalid(_))
+        ));
+
+        // a level offset at the end of the address space doesn't wrap around
+        let mut far_offset = ktx2(131, (4, 4), 0, 1, &[vec![0u8; 8]], 1);
+        far_offset[80..88].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            parse_ktx2(&far_offset).err(),
+            Some(TextureFileError::Truncated)
+        );
+    }
+}